@@ -0,0 +1,477 @@
+//! 非交互的命令行，给脚本和定时任务用
+//!
+//! ```text
+//! jobs [--index <file>] [--json] [--disk] [--symlinks skip|leaf|follow] [-x] [--digest blake3|sha256] [--history] <command> [args]
+//!     scan <path>                 扫描并写回索引
+//!     show <path>                 索引里的信息
+//!     tree <path> [--depth N]     索引里的树，默认3层
+//!     du <path>                   扫描，列出直接子项，大的在前
+//!     load [--trust]              读一遍索引，报告节点数
+//!     dump <file> [--format F]    把索引另存一份，默认按扩展名选格式（csv/jidx/json/ndjson）
+//!     stats                       读一遍索引，报告节点表占的内存
+//!     diff <old> [<new>]          比较两份索引，new不给时用--index；--json输出整个Diff
+//!     verify <path>               重新算索引里有摘要的文件，有内容坏了的退出码是1
+//! ```
+//! --disk让show/tree/du按占的磁盘空间算，默认是表观大小（和du --apparent-size相反）。
+//! --symlinks是scan/du碰到符号链接时怎么办，默认leaf。
+//! -x（--one-file-system）让scan/du不进挂载点。
+//! --digest让scan/du给还没有摘要的文件算一个，存进索引，verify拿它来比。
+//! --history让写回索引时在旁边的.history里追加一条快照，默认不记。
+//! scan/du碰到读不了的路径照样做完，读不了的逐行写到stderr。
+//! 不带参数时进交互模式。
+//!
+//! 退出码：0 成功，1 运行出错，2 参数不对，3 路径不存在或者不在索引里
+
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::core::pretty_size;
+use crate::{ChangeKind, DigestAlgo, JError, JManager, JNode, JNodeAction, LoadMode, ManagerAction, ManagerStorage, SizeMode, StorageFormat, SymlinkPolicy};
+
+const DEFAULT_DEPTH: usize = 3;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+const USAGE: &str = "\
+Usage: jobs [--index <file>] [--json] [--disk] [--symlinks P] [-x] [--digest A] [--history] <command> [args]
+Options:
+    --disk                      sizes are disk usage instead of apparent size
+    --symlinks P                skip, leaf (default) or follow symlinks when scanning
+    -x, --one-file-system       do not descend into other filesystems
+    --digest A                  store a blake3 or sha256 content digest for each scanned file
+    --history                   append a snapshot to <index>.history when the index is saved
+Commands:
+    scan <path>                 scan and save the index
+    show <path>                 show an indexed path
+    tree <path> [--depth N]     print the indexed tree (default depth 3)
+    du <path>                   scan and list direct children by size
+    load [--trust]              read the index and report node count
+    dump <file> [--format F]    save the index to another file (csv/jidx/json/ndjson)
+    stats                       load the index and report memory usage
+    diff <old> [<new>]          compare two indexes (new defaults to --index)
+    verify <path>               rehash files with a stored digest, fail if content changed";
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    NotIndexed(PathBuf),
+    Failed(Box<dyn Error>),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{msg}\n\n{USAGE}"),
+            CliError::NotIndexed(path) => write!(f, "[Jobs Error] {} is not indexed, run `jobs scan` first", path.display()),
+            CliError::Failed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl From<Box<dyn Error>> for CliError {
+    fn from(e: Box<dyn Error>) -> Self {
+        CliError::Failed(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Failed(e.into())
+    }
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::NotIndexed(_) => EXIT_NOT_FOUND,
+            CliError::Failed(e) => match e.downcast_ref::<JError>() {
+                Some(JError::NotExistingPath(_)) | Some(JError::NotExistingNode(_, _)) => EXIT_NOT_FOUND,
+                _ => match e.downcast_ref::<std::io::Error>() {
+                    Some(e) if e.kind() == std::io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+                    _ => EXIT_FAILURE,
+                },
+            },
+        }
+    }
+}
+
+struct Options {
+    index: PathBuf,
+    json: bool,
+    size: SizeMode,
+    symlinks: SymlinkPolicy,
+    one_file_system: bool,
+    digest: Option<DigestAlgo>,
+    history: bool,
+    command: String,
+    args: Vec<String>,
+}
+
+/// 默认还是用户根目录下的example.csv，和交互模式一样
+pub fn default_index() -> PathBuf {
+    let home_dir = env::var("HOME")
+        .or_else(|_| env::var("USERPROFILE"))
+        .unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home_dir).join("example.csv")
+}
+
+fn parse_options(args: &[String]) -> Result<Options, CliError> {
+    let mut index = None;
+    let mut json = false;
+    let mut size = SizeMode::Apparent;
+    let mut symlinks = SymlinkPolicy::default();
+    let mut one_file_system = false;
+    let mut digest = None;
+    let mut history = false;
+    let mut rest = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--index" => {
+                let file = iter.next().ok_or(CliError::Usage("--index needs a file".into()))?;
+                index = Some(PathBuf::from(file));
+            }
+            "--json" => json = true,
+            "--disk" => size = SizeMode::Disk,
+            "--symlinks" => {
+                symlinks = match iter.next().map(|s| s.as_str()) {
+                    Some("skip") => SymlinkPolicy::Skip,
+                    Some("leaf") => SymlinkPolicy::Leaf,
+                    Some("follow") => SymlinkPolicy::Follow,
+                    _ => return Err(CliError::Usage("--symlinks needs skip, leaf or follow".into())),
+                };
+            }
+            "-x" | "--one-file-system" => one_file_system = true,
+            "--digest" => {
+                let name = iter.next().map(|s| s.as_str()).unwrap_or_default();
+                digest = Some(DigestAlgo::from_name(name).ok_or(CliError::Usage("--digest needs blake3 or sha256".into()))?);
+            }
+            "--history" => history = true,
+            "-h" | "--help" => return Err(CliError::Usage("".into())),
+            _ => rest.push(arg.clone()),
+        }
+    }
+    if rest.is_empty() {
+        return Err(CliError::Usage("missing command".into()));
+    }
+    let command = rest.remove(0);
+    Ok(Options {
+        index: index.unwrap_or_else(default_index),
+        json,
+        size,
+        symlinks,
+        one_file_system,
+        digest,
+        history,
+        command,
+        args: rest,
+    })
+}
+
+/// 跑一条命令，输出写到out；main里用run_cli，测试直接调这个
+pub fn execute(args: &[String], out: &mut dyn Write) -> Result<(), CliError> {
+    let opts = parse_options(args)?;
+    let mut mng = JManager::new();
+    mng.set_symlink_policy(opts.symlinks);
+    mng.set_one_file_system(opts.one_file_system);
+    mng.set_digest(opts.digest);
+    mng.set_keep_history(opts.history);
+    match opts.command.as_str() {
+        "scan" => {
+            let path = path_arg(&opts)?;
+            mng.load(&opts.index)?;
+            let h = mng.locate_node(&path)?;
+            eprint!("{}", mng.scan(&h)?);
+            mng.dump(&opts.index)?;
+            print_node(out, &opts, &mng.get_info(&h)?)
+        }
+        "show" => {
+            let path = path_arg(&opts)?;
+            mng.set_load_mode(LoadMode::Trust);
+            mng.load(&opts.index)?;
+            let h = indexed(&mut mng, &path)?;
+            print_node(out, &opts, &mng.get_info(&h)?)
+        }
+        "tree" => {
+            let path = path_arg(&opts)?;
+            let depth = match flag_value(&opts.args, "--depth")? {
+                Some(depth) => depth.parse::<usize>().map_err(|_| CliError::Usage(format!("bad depth: {depth}")))?,
+                None => DEFAULT_DEPTH,
+            };
+            mng.set_load_mode(LoadMode::Trust);
+            mng.load(&opts.index)?;
+            let h = indexed(&mut mng, &path)?;
+            if opts.json {
+                let tree = tree_json(&mut mng, h, depth)?;
+                writeln!(out, "{tree}")?;
+            } else {
+                print_tree(out, &mut mng, h, depth, opts.size)?;
+            }
+            Ok(())
+        }
+        "du" => {
+            let path = path_arg(&opts)?;
+            mng.load(&opts.index)?;
+            let h = mng.locate_node(&path)?;
+            eprint!("{}", mng.scan(&h)?);
+            mng.dump(&opts.index)?;
+            let mut children = vec![];
+            for ch in sorted_children(&mut mng, &h)? {
+                children.push(mng.get_info(&ch)?);
+            }
+            let size = |node: &JNode| node.size_as(opts.size);
+            children.sort_by(|a, b| size(b).cmp(&size(a)).then_with(|| a.path().cmp(b.path())));
+            if opts.json {
+                let rows = children.iter().map(node_json).collect::<Vec<_>>();
+                writeln!(out, "{}", Value::Array(rows))?;
+            } else {
+                for node in children.iter() {
+                    writeln!(out, "{}\t{}", pretty_size(size(node)), node.path().display())?;
+                }
+                writeln!(out, "{}\t{}", pretty_size(size(&mng.get_info(&h)?)), path.display())?;
+            }
+            Ok(())
+        }
+        "load" => {
+            if opts.args.iter().any(|a| a == "--trust") {
+                mng.set_load_mode(LoadMode::Trust);
+            }
+            if !opts.index.exists() {
+                return Err(CliError::Failed(JError::NotExistingPath(opts.index.clone()).into()));
+            }
+            mng.load(&opts.index)?;
+            let nodes = mng.dump_rows()?.len();
+            if opts.json {
+                writeln!(out, "{}", json!({ "index": opts.index, "nodes": nodes }))?;
+            } else {
+                writeln!(out, "{}: {} nodes", opts.index.display(), nodes)?;
+            }
+            Ok(())
+        }
+        "dump" => {
+            let to = PathBuf::from(path_or_file(&opts).ok_or(CliError::Usage("dump needs a file".into()))?);
+            let format = match flag_value(&opts.args, "--format")? {
+                Some(name) => StorageFormat::from_name(name).ok_or(CliError::Usage(format!("unknown format: {name}")))?,
+                None => StorageFormat::from_path(&to),
+            };
+            mng.set_load_mode(LoadMode::Trust);
+            mng.load(&opts.index)?;
+            mng.dump_as(&to, format)?;
+            if opts.json {
+                writeln!(out, "{}", json!({ "index": to }))?;
+            }
+            Ok(())
+        }
+        "stats" => {
+            mng.set_load_mode(LoadMode::Trust);
+            mng.load(&opts.index)?;
+            // 没展开的块也算上
+            mng.materialize_all()?;
+            let stats = mng.stats();
+            if opts.json {
+                writeln!(
+                    out,
+                    "{}",
+                    json!({
+                        "nodes": stats.nodes,
+                        "slots": stats.slots,
+                        "free_slots": stats.free_slots,
+                        "names": stats.names,
+                        "name_bytes": stats.name_bytes,
+                        "bytes": stats.bytes,
+                    })
+                )?;
+            } else {
+                writeln!(out, "{stats}")?;
+            }
+            Ok(())
+        }
+        "diff" => {
+            let old = PathBuf::from(path_or_file(&opts).ok_or(CliError::Usage("diff needs an index".into()))?);
+            let new = opts.args.get(1).map(PathBuf::from).unwrap_or_else(|| opts.index.clone());
+            let diff = JManager::diff_files(&old, &new)?;
+            if opts.json {
+                writeln!(out, "{}", serde_json::to_string(&diff).map_err(|e| CliError::Failed(e.into()))?)?;
+            } else {
+                for c in diff.changes.iter() {
+                    let tag = match c.kind {
+                        ChangeKind::Added => "A",
+                        ChangeKind::Removed => "D",
+                        ChangeKind::Resized => "R",
+                        ChangeKind::Modified => "M",
+                    };
+                    writeln!(out, "{tag}\t{:+}\t{}", c.size_delta(), c.abspath)?;
+                }
+            }
+            Ok(())
+        }
+        "verify" => {
+            let path = path_arg(&opts)?;
+            // 按索引里的size、mtime比，不能先stat一遍
+            mng.set_load_mode(LoadMode::Trust);
+            mng.load(&opts.index)?;
+            let h = indexed(&mut mng, &path)?;
+            let report = mng.verify_digests(&h)?;
+            for e in report.errors.iter() {
+                eprintln!("{e}");
+            }
+            if opts.json {
+                let corrupted = report
+                    .corrupted
+                    .iter()
+                    .map(|c| json!({ "path": c.path, "expected": c.expected.to_string(), "actual": c.actual.to_string() }))
+                    .collect::<Vec<_>>();
+                let value = json!({
+                    "checked": report.checked,
+                    "corrupted": corrupted,
+                    "modified": report.modified,
+                    "unhashed": report.unhashed,
+                });
+                writeln!(out, "{value}")?;
+            } else {
+                for c in report.corrupted.iter() {
+                    writeln!(out, "CORRUPTED\t{}\t{}\t{}", c.path.display(), c.expected, c.actual)?;
+                }
+                for path in report.modified.iter() {
+                    writeln!(out, "modified\t{}", path.display())?;
+                }
+            }
+            if !report.is_ok() {
+                return Err(CliError::Failed(JError::IntegrityFailed(report.corrupted.len()).into()));
+            }
+            Ok(())
+        }
+        other => Err(CliError::Usage(format!("unknown command: {other}"))),
+    }
+}
+
+/// 出错时打到stderr，返回退出码
+pub fn run_cli(args: &[String]) -> i32 {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    match execute(args, &mut out) {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            eprintln!("{e}");
+            e.exit_code()
+        }
+    }
+}
+
+/// 第一个不是选项（也不是选项值）的参数
+fn path_or_file(opts: &Options) -> Option<&String> {
+    let mut iter = opts.args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--depth" | "--format" => {
+                iter.next();
+            }
+            a if a.starts_with("--") => {}
+            _ => return Some(arg),
+        }
+    }
+    None
+}
+
+fn path_arg(opts: &Options) -> Result<PathBuf, CliError> {
+    let path = path_or_file(opts).ok_or(CliError::Usage(format!("{} needs a path", opts.command)))?;
+    Ok(PathBuf::from(path).canonicalize()?)
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a String>, CliError> {
+    match args.iter().position(|a| a == flag) {
+        None => Ok(None),
+        Some(i) => args
+            .get(i + 1)
+            .map(Some)
+            .ok_or(CliError::Usage(format!("{flag} needs a value"))),
+    }
+}
+
+fn indexed(mng: &mut JManager, path: &PathBuf) -> Result<u64, CliError> {
+    mng.find_node(path)?.ok_or_else(|| CliError::NotIndexed(path.clone()))
+}
+
+fn sorted_children(mng: &mut JManager, h: &u64) -> Result<Vec<u64>, CliError> {
+    mng.materialize(h)?;
+    let mut children = mng
+        .get_children_node(h)
+        .into_iter()
+        .map(|(v, h)| (!v.is_dir(), v.name(), h))
+        .collect::<Vec<_>>();
+    // 文件夹在前，再按名字
+    children.sort();
+    Ok(children.into_iter().map(|(_, _, h)| h).collect())
+}
+
+fn node_json(node: &JNode) -> Value {
+    json!({
+        "path": node.path(),
+        "name": node.name(),
+        "is_dir": node.is_dir(),
+        "size": node.size(),
+        "disk": node.disk_size(),
+        "last_modified": node.last_modified() as u64,
+        "count_dir": node.count_dir(),
+        "count_file": node.count_file(),
+        "target": node.target(),
+        "inaccessible": node.is_inaccessible(),
+    })
+}
+
+fn print_node(out: &mut dyn Write, opts: &Options, node: &JNode) -> Result<(), CliError> {
+    if opts.json {
+        writeln!(out, "{}", node_json(node))?;
+    } else {
+        writeln!(out, "{node}")?;
+    }
+    Ok(())
+}
+
+fn print_tree(out: &mut dyn Write, mng: &mut JManager, h: u64, depth: usize, size: SizeMode) -> Result<(), CliError> {
+    let mut stack = vec![(h, 0)];
+    while let Some((h, d)) = stack.pop() {
+        let info = mng.get_info(&h)?;
+        writeln!(
+            out,
+            "{}{}{}  [{}]",
+            "|   ".repeat(d),
+            info.name(),
+            match info.target() {
+                Some(target) => format!(" -> {}", target.display()),
+                None if info.is_dir() => "/".to_string(),
+                None => String::new(),
+            },
+            pretty_size(info.size_as(size)),
+        )?;
+        if d < depth {
+            let children = sorted_children(mng, &h)?;
+            stack.extend(children.into_iter().rev().map(|ch| (ch, d + 1)));
+        }
+    }
+    Ok(())
+}
+
+fn tree_json(mng: &mut JManager, h: u64, depth: usize) -> Result<Value, CliError> {
+    let info = mng.get_info(&h)?;
+    let mut value = node_json(&info);
+    if info.is_dir() && depth > 0 {
+        let mut children = vec![];
+        for ch in sorted_children(mng, &h)? {
+            children.push(tree_json(mng, ch, depth - 1)?);
+        }
+        value["children"] = Value::Array(children);
+    }
+    Ok(value)
+}
@@ -3,6 +3,7 @@ use std::error::Error;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use std::vec;
 
 use crossterm::cursor;
@@ -12,25 +13,51 @@ use crossterm::event::KeyCode;
 use crossterm::terminal::Clear;
 use crossterm::terminal::ClearType;
 use crossterm::Command;
+use crossterm::queue;
+use crossterm::style::Print;
 
+use crate::ChangeKind;
+use crate::Diff;
+use crate::DigestAlgo;
+use crate::History;
+use crate::JError;
 use crate::JManager;
+use crate::JNode;
 use crate::JNodeAction;
+use crate::LinkMode;
+use crate::LoadMode;
 use crate::ManagerAction;
 use crate::ManagerStorage;
+use crate::Query;
+use crate::SizeMode;
+use crate::SymlinkPolicy;
+use crate::TopBy;
+use crate::TopKind;
+use crate::core::{parse_size, pretty_last_modified, pretty_size};
 
 pub struct Console {
     pub manager: JManager,
     pub current: PathBuf,
+    /// tree显示哪种大小
+    pub size_mode: SizeMode,
 }
 
 const TREE_INDENT: usize = 4;
+const FIND_LIMIT: usize = 50;
+const TOP_N: usize = 10;
+/// 进度行多久刷新一次
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
 impl Console {
     pub fn new() -> Self {
         let current = std::env::current_dir().unwrap();
         dbg!(&current);
         let manager = JManager::new();
-        Self { manager, current }
+        Self {
+            manager,
+            current,
+            size_mode: SizeMode::Apparent,
+        }
     }
     pub fn prompt(&self) -> String {
         format!("[Jobs]@{} >> ", self.current.display())
@@ -41,6 +68,9 @@ impl Console {
 
         let home_dir = env::var("HOME").or_else(|_| env::var("USERPROFILE"))?;
         let file_path = PathBuf::from(home_dir).join("example.csv");
+        // 先把watch攒下的改动落到表里，show/tree看到的就是最新的
+        #[cfg(feature = "watch")]
+        self.manager.apply_events()?;
         match cmd {
             "cd" => {
                 let to = raw_cmd.to_owned();
@@ -55,8 +85,211 @@ impl Console {
                 let depth = args.next().unwrap_or("3").parse::<usize>().unwrap();
                 self.tree(depth)
             }
+            "find" => {
+                let usage = "Usage: find [-p|-s|-f] <pattern> [distance]";
+                let (mode, pattern) = match args.next() {
+                    Some(flag @ ("-p" | "-s" | "-f")) => (flag, args.next()),
+                    other => ("-p", other),
+                };
+                let pattern = pattern.ok_or(usage)?;
+                let dist = args.next().unwrap_or("2").parse::<usize>()?;
+                self.find(mode, pattern, dist)
+            }
+            "top" => {
+                let usage = "Usage: top [-f|-d] [-c] [N]";
+                let mut kind = TopKind::All;
+                let mut by = match self.size_mode {
+                    SizeMode::Apparent => TopBy::Size,
+                    SizeMode::Disk => TopBy::Disk,
+                };
+                let mut n = TOP_N;
+                for arg in args {
+                    match arg {
+                        "-f" => kind = TopKind::Files,
+                        "-d" => kind = TopKind::Dirs,
+                        "-c" => by = TopBy::FileCount,
+                        _ => n = arg.parse::<usize>().map_err(|_| usage)?,
+                    }
+                }
+                self.top(kind, by, n)
+            }
+            "types" => {
+                let usage = "Usage: types [-c] [N]";
+                let mut by_category = false;
+                let mut n = TOP_N;
+                for arg in args {
+                    match arg {
+                        "-c" => by_category = true,
+                        _ => n = arg.parse::<usize>().map_err(|_| usage)?,
+                    }
+                }
+                self.types(by_category, n)
+            }
+            "dupes" => {
+                let usage = "Usage: dupes [-a] [min_size]";
+                let mut all = false;
+                let mut min_size = 1;
+                for arg in args {
+                    match arg {
+                        "-a" => all = true,
+                        _ => min_size = parse_size(arg).ok_or(usage)?,
+                    }
+                }
+                self.dupes(all, min_size)
+            }
+            "query" => {
+                let src = raw_cmd.trim_start().strip_prefix("query").unwrap_or_default();
+                let query = Query::parse(src)?;
+                let h = self.manager.locate_node(&self.current)?;
+                for (_, node) in self.manager.query(&h, &query)? {
+                    let slash = if node.is_dir() { "/" } else { "" };
+                    println!("{}\t{}{slash}", pretty_size(node.size_as(self.size_mode)), node.path().display());
+                }
+                Ok(())
+            }
+            "ignore" => {
+                let usage = "Usage: ignore [ls | add <rule> | rm <rule>]";
+                let sub = args.next();
+                // 规则里可以有空格（size > 10M），剩下的合起来才是一条
+                let rule = args.collect::<Vec<_>>().join(" ");
+                match sub {
+                    None | Some("ls") => {
+                        for rule in self.manager.rules() {
+                            println!("{rule}");
+                        }
+                        Ok(())
+                    }
+                    Some("add" | "rm") if rule.is_empty() => Err(usage.into()),
+                    Some("add") => self.manager.add_rule(&rule),
+                    Some("rm") => match self.manager.remove_rule(&rule) {
+                        true => Ok(()),
+                        false => Err("No such rule".into()),
+                    },
+                    _ => Err(usage.into()),
+                }
+            }
+            "stats" => {
+                println!("{}", self.manager.stats());
+                Ok(())
+            }
+            "size" => {
+                match args.next() {
+                    None => {}
+                    Some("apparent") => self.size_mode = SizeMode::Apparent,
+                    Some("disk") => self.size_mode = SizeMode::Disk,
+                    _ => return Err("Usage: size [apparent|disk]".into()),
+                }
+                println!("{:?}", self.size_mode);
+                Ok(())
+            }
+            "symlinks" => {
+                match args.next() {
+                    None => {}
+                    Some("skip") => self.manager.set_symlink_policy(SymlinkPolicy::Skip),
+                    Some("leaf") => self.manager.set_symlink_policy(SymlinkPolicy::Leaf),
+                    Some("follow") => self.manager.set_symlink_policy(SymlinkPolicy::Follow),
+                    _ => return Err("Usage: symlinks [skip|leaf|follow]".into()),
+                }
+                println!("{:?}", self.manager.symlink_policy());
+                Ok(())
+            }
+            "onefs" => {
+                match args.next() {
+                    None => {}
+                    Some("on") => self.manager.set_one_file_system(true),
+                    Some("off") => self.manager.set_one_file_system(false),
+                    _ => return Err("Usage: onefs [on|off]".into()),
+                }
+                println!("{}", self.manager.one_file_system());
+                Ok(())
+            }
+            "mounts" => self.mounts(),
+            "links" => {
+                match args.next() {
+                    None => {}
+                    Some("once") => self.manager.set_link_mode(LinkMode::Once),
+                    Some("each") => self.manager.set_link_mode(LinkMode::EachLink),
+                    _ => return Err("Usage: links [once|each]".into()),
+                }
+                println!("{:?}", self.manager.link_mode());
+                Ok(())
+            }
+            "digest" => {
+                match args.next() {
+                    None => {}
+                    Some("off") => self.manager.set_digest(None),
+                    Some(name) => {
+                        let algo = DigestAlgo::from_name(name).ok_or("Usage: digest [off|blake3|sha256]")?;
+                        self.manager.set_digest(Some(algo));
+                    }
+                }
+                println!("{}", self.manager.digest().map(|a| a.name()).unwrap_or("off"));
+                Ok(())
+            }
+            "verify" => self.verify(),
             "dump" => self.manager.dump(&file_path),
-            "load" => self.manager.load(&file_path),
+            "history" => {
+                let usage = "Usage: history [on | off | ls | diff <a> <b> | trend]";
+                let sub = args.next();
+                if let Some(on @ ("on" | "off")) = sub {
+                    self.manager.set_keep_history(on == "on");
+                    println!("{}", self.manager.keep_history());
+                    return Ok(());
+                }
+                let history = History::open(&History::path_for(&file_path))?;
+                match sub {
+                    None | Some("ls") => {
+                        for s in history.list() {
+                            let full = if s.full { " (full)" } else { "" };
+                            println!("#{} {} {} dirs{}", s.id, pretty_last_modified(s.time), s.entries, full);
+                        }
+                        Ok(())
+                    }
+                    Some("diff") => {
+                        let mut id = || args.next().and_then(|a| a.trim_start_matches('#').parse::<u32>().ok());
+                        match (id(), id()) {
+                            (Some(a), Some(b)) => self.history_diff(&history, a, b),
+                            _ => Err(usage.into()),
+                        }
+                    }
+                    Some("trend") => self.history_trend(&history),
+                    _ => Err(usage.into()),
+                }
+            }
+            "diff" => {
+                let diff = match (args.next(), args.next()) {
+                    (Some(a), None) => self.manager.diff_since(&PathBuf::from(a))?,
+                    (Some(a), Some(b)) => JManager::diff_files(&PathBuf::from(a), &PathBuf::from(b))?,
+                    _ => return Err("Usage: diff <index> [<index>]".into()),
+                };
+                self.diff(&diff)
+            }
+            "load" => {
+                let mode = match args.next() {
+                    Some("--trust") => LoadMode::Trust,
+                    _ => LoadMode::Verify,
+                };
+                self.manager.set_load_mode(mode);
+                self.manager.load(&file_path)
+            }
+            #[cfg(feature = "watch")]
+            "watch" => match args.next() {
+                Some("-l") => {
+                    for root in self.manager.watched() {
+                        println!("{}", root.display());
+                    }
+                    Ok(())
+                }
+                _ => {
+                    let h = self.manager.locate_node(&self.current)?;
+                    self.manager.watch(&h)
+                }
+            },
+            #[cfg(feature = "watch")]
+            "unwatch" => {
+                let h = self.manager.locate_node(&self.current)?;
+                self.manager.unwatch(&h)
+            }
             #[cfg(debug_assertions)]
             "debug" => {
                 let h = self.manager.locate_node(&self.current)?;
@@ -112,9 +345,37 @@ impl Console {
         println!("{result}");
         Ok(())
     }
+    /// 扫描时一行进度原地刷新，Ctrl-C停下；读不了的路径列出来，扫描本身不算失败
     pub fn scan(&mut self) -> Result<(), Box<dyn Error>> {
         let h: u64 = self.manager.locate_node(&self.current)?;
-        self.manager.update_node(&h)?;
+        let mut last = Instant::now();
+        self.manager.set_observer(move |p| {
+            if last.elapsed() < PROGRESS_INTERVAL {
+                return;
+            }
+            last = Instant::now();
+            let line = format!("\r{} dirs, {} files, {} {}", p.dirs, p.files, pretty_size(p.bytes), p.current.display());
+            let mut out = std::io::stdout();
+            let _ = queue!(out, Print(line), Clear(ClearType::UntilNewLine));
+            let _ = out.flush();
+        });
+        let res = self.manager.scan(&h);
+        self.manager.clear_observer();
+        let p = self.manager.progress();
+        print!("\r{} dirs, {} files, {}", p.dirs, p.files, pretty_size(p.bytes));
+        queue!(std::io::stdout(), Clear(ClearType::UntilNewLine))?;
+        println!();
+        let report = match res {
+            Err(e) if matches!(e.downcast_ref::<JError>(), Some(JError::Cancelled)) => {
+                println!("Cancelled, unfinished folders are left dirty");
+                return Ok(());
+            }
+            res => res?,
+        };
+        if !report.is_empty() {
+            print!("{}", report);
+            println!("{} path(s) could not be read", report.len());
+        }
         Ok(())
     }
     pub fn show(&mut self) -> Result<(), Box<dyn Error>> {
@@ -123,10 +384,190 @@ impl Console {
         println!("{}", info);
         Ok(())
     }
+    /// 当前目录下扫到的挂载点：设备号 路径
+    pub fn mounts(&mut self) -> Result<(), Box<dyn Error>> {
+        let h = self.manager.locate_node(&self.current)?;
+        for m in self.manager.mounts(&h)? {
+            if let JNode::Dir(dir) = self.manager.get_info(&m)? {
+                println!("[{}] {}", dir.dev, dir.abspath.display());
+            }
+        }
+        Ok(())
+    }
+    /// 两个快照之间当前目录下变了的文件夹，变得多的在前
+    pub fn history_diff(&mut self, history: &History, a: u32, b: u32) -> Result<(), Box<dyn Error>> {
+        let Some(growth) = history.compare(a, b) else {
+            return Err(format!("No such snapshot: #{a} or #{b}").into());
+        };
+        let signed = |d: i64| format!("{}{}", if d < 0 { "-" } else { "+" }, pretty_size(d.unsigned_abs()));
+        for g in growth.iter().filter(|g| g.path.starts_with(&self.current)).take(FIND_LIMIT) {
+            let note = match (g.before, g.after) {
+                (None, _) => " (new)",
+                (_, None) => " (removed)",
+                _ => "",
+            };
+            println!("{}	{:+} files	{}{}", signed(g.size_delta()), g.files_delta(), g.path.display(), note);
+        }
+        Ok(())
+    }
+    /// 当前目录在每个快照里的大小
+    pub fn history_trend(&mut self, history: &History) -> Result<(), Box<dyn Error>> {
+        for (s, stat) in history.series(&self.current) {
+            match stat {
+                Some(stat) => println!(
+                    "#{} {}\t{}\t{} files\t{} dirs",
+                    s.id,
+                    pretty_last_modified(s.time),
+                    pretty_size(stat.size),
+                    stat.count_file,
+                    stat.count_dir
+                ),
+                None => println!("#{} {}\t-", s.id, pretty_last_modified(s.time)),
+            }
+        }
+        Ok(())
+    }
+    /// 当前目录下面变了的文件夹和节点，各最多FIND_LIMIT个
+    pub fn diff(&mut self, diff: &Diff) -> Result<(), Box<dyn Error>> {
+        let diff = diff.within(&self.current);
+        let signed = |d: i64| format!("{}{}", if d < 0 { "-" } else { "+" }, pretty_size(d.unsigned_abs()));
+        for d in diff.dirs.iter().take(FIND_LIMIT) {
+            println!(
+                "{}\t+{} -{} ~{} files\t{}",
+                signed(d.size_delta),
+                d.added,
+                d.removed,
+                d.resized + d.modified,
+                d.abspath
+            );
+        }
+        for c in diff.changes.iter().take(FIND_LIMIT) {
+            let tag = match c.kind {
+                ChangeKind::Added => "A",
+                ChangeKind::Removed => "D",
+                ChangeKind::Resized => "R",
+                ChangeKind::Modified => "M",
+            };
+            println!("{tag} {}\t{}", signed(c.size_delta()), c.abspath);
+        }
+        println!(
+            "{} added, {} removed, {} resized, {} modified",
+            diff.count(ChangeKind::Added),
+            diff.count(ChangeKind::Removed),
+            diff.count(ChangeKind::Resized),
+            diff.count(ChangeKind::Modified)
+        );
+        Ok(())
+    }
+    /// 当前目录下面最大的n个
+    pub fn top(&mut self, kind: TopKind, by: TopBy, n: usize) -> Result<(), Box<dyn Error>> {
+        let h = self.manager.locate_node(&self.current)?;
+        for (_, node) in self.manager.top_n(&h, kind, by, n)? {
+            let value = match by {
+                TopBy::FileCount => format!("{} files", node.count_file().unwrap_or(0)),
+                _ => pretty_size(node.size_as(self.size_mode)),
+            };
+            let slash = if node.is_dir() { "/" } else { "" };
+            println!("{value}\t{}{slash}", node.path().display());
+        }
+        Ok(())
+    }
+    /// 重新算当前目录下面文件的摘要，列出内容变了但size、mtime没变的
+    pub fn verify(&mut self) -> Result<(), Box<dyn Error>> {
+        let h = self.manager.locate_node(&self.current)?;
+        let report = self.manager.verify_digests(&h)?;
+        for c in report.corrupted.iter() {
+            println!("CORRUPTED\t{}\n    expected {}\n    actual   {}", c.path.display(), c.expected, c.actual);
+        }
+        for path in report.modified.iter() {
+            println!("modified\t{}", path.display());
+        }
+        for e in report.errors.iter() {
+            println!("{e}");
+        }
+        println!(
+            "{} checked, {} corrupted, {} modified, {} without digest",
+            report.checked,
+            report.corrupted.len(),
+            report.modified.len(),
+            report.unhashed
+        );
+        Ok(())
+    }
+    /// 当前目录（all时是所有扫过的树）下面的重复文件，每组列出路径，最后是能省多少
+    pub fn dupes(&mut self, all: bool, min_size: u64) -> Result<(), Box<dyn Error>> {
+        let h = self.manager.locate_node(&self.current)?;
+        let report = self.manager.find_dupes((!all).then_some(&h), min_size)?;
+        for group in report.groups.iter() {
+            println!(
+                "{} x {}\t{} reclaimable",
+                group.files.len(),
+                pretty_size(group.size),
+                pretty_size(group.reclaimable())
+            );
+            for file in group.files.iter() {
+                println!("    {}", file.display());
+            }
+        }
+        for e in report.errors.iter() {
+            println!("{e}");
+        }
+        println!(
+            "{} groups, {} redundant files, {} reclaimable",
+            report.groups.len(),
+            report.redundant(),
+            pretty_size(report.reclaimable())
+        );
+        Ok(())
+    }
+    /// 当前目录下面字节数最多的n种扩展名（或分类），大小总是表观大小
+    pub fn types(&mut self, by_category: bool, n: usize) -> Result<(), Box<dyn Error>> {
+        let h = self.manager.locate_node(&self.current)?;
+        let types = self.manager.types(&h)?.ok_or(JError::NotDirectory(self.current.clone()))?;
+        let rows = if by_category {
+            types
+                .by_category()
+                .into_iter()
+                .map(|(c, stat)| (c.name().to_string(), stat))
+                .collect::<Vec<_>>()
+        } else {
+            types
+                .sorted()
+                .into_iter()
+                .map(|(ext, stat)| (if ext.is_empty() { "(none)".to_string() } else { format!(".{ext}") }, stat))
+                .collect()
+        };
+        for (name, stat) in rows.into_iter().take(n) {
+            println!("{}	{} files	{name}", pretty_size(stat.bytes), stat.files);
+        }
+        Ok(())
+    }
+    /// -p 前缀，-s 后缀，-f 模糊；只列当前目录下面的
+    pub fn find(&mut self, mode: &str, pattern: &str, dist: usize) -> Result<(), Box<dyn Error>> {
+        let found = match mode {
+            "-s" => self.manager.search_suffix(pattern)?,
+            "-f" => self.manager.search_fuzzy(pattern, dist)?,
+            _ => self.manager.search_prefix(pattern)?,
+        };
+        let mut cnt = 0;
+        for (h, score) in found {
+            let info = self.manager.get_info(&h)?;
+            if !info.path().starts_with(&self.current) {
+                continue;
+            }
+            println!("[{score}] {}", info.path().display());
+            cnt += 1;
+            if cnt == FIND_LIMIT {
+                break;
+            }
+        }
+        Ok(())
+    }
     pub fn tree(&mut self, depth: usize) -> Result<(), Box<dyn Error>> {
         let indent = format!("|{}", " ".repeat(TREE_INDENT));
         let mut chs = vec![];
         let h = self.manager.locate_node(&self.current)?;
+        self.manager.materialize(&h)?;
         chs.push((h, 0));
         while let Some((h, d)) = chs.pop() {
             if d < depth {
@@ -145,9 +586,15 @@ impl Console {
             }
             let info = self.manager.get_info(&h)?;
             println!(
-                "{}{}",
+                "{}{}  [{}]",
                 indent.repeat(d),
-                info.name() + (if info.is_dir() { "/" } else { "" }),
+                info.name()
+                    + &match info.target() {
+                        Some(target) => format!(" -> {}", target.display()),
+                        None if info.is_dir() => "/".to_string(),
+                        None => String::new(),
+                    },
+                pretty_size(info.size_as(self.size_mode)),
             );
         }
         Ok(())
@@ -159,6 +606,15 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     let mut history: Vec<String> = vec![];
     let mut buffer = String::new(); // 用于存储按键输入的缓冲区
     let mut arrow_cursor = history.len();
+    // 扫描时Ctrl-C只停扫描，别的时候照常退出
+    let token = console.manager.cancel_token();
+    ctrlc::set_handler(move || {
+        if token.is_active() {
+            token.cancel();
+        } else {
+            std::process::exit(130);
+        }
+    })?;
     println!("Press up or down arrow key, 'q' to quit.");
     print!("{}", console.prompt());
     std::io::stdout().flush()?;
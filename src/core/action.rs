@@ -2,14 +2,144 @@
 #![allow(unused_variables)]
 
 use std::hash::Hash;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 
+/// 按扩展名选存储格式，默认CSV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageFormat {
+    /// 每行一个DumpData
+    Csv,
+    /// 二进制分块索引，扩展名 .jidx
+    Index,
+    /// 嵌套的整棵树，扩展名 .json
+    Json,
+    /// 每行一个节点，带父节点id，扩展名 .ndjson / .jsonl
+    NdJson,
+}
+
+impl StorageFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jidx") => Self::Index,
+            Some("json") => Self::Json,
+            Some("ndjson" | "jsonl") => Self::NdJson,
+            _ => Self::Csv,
+        }
+    }
+
+    /// 命令行里的名字：csv、jidx、json、ndjson
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "jidx" | "index" => Some(Self::Index),
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::NdJson),
+            _ => None,
+        }
+    }
+}
+
+/// load时怎么对待缓存
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    /// 每一行都stat一遍，不存在的路径直接丢掉
+    Verify,
+    /// 相信缓存，按父路径直接挂进树里，
+    /// 等update_node/get_info碰到时再跑is_valid
+    Trust,
+}
+
+/// 硬链接的大小怎么算
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkMode {
+    /// 同一个inode在一棵子树里只算一次
+    #[default]
+    Once,
+    /// 每个链接都算一遍，和以前一样
+    EachLink,
+}
+
+/// 扫描时碰到符号链接怎么办
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// 当没看见
+    Skip,
+    /// 记成JNode::Symlink叶子，不跟进去
+    #[default]
+    Leaf,
+    /// 跟过去按目标建节点；指向自己祖先的（会绕圈）和悬空的还是记成叶子
+    Follow,
+}
+
+/// show/tree/du显示哪种大小，和du --apparent-size一个意思
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeMode {
+    /// metadata.len()
+    #[default]
+    Apparent,
+    /// 分配的块，st_blocks * 512
+    Disk,
+}
+
+/// 文件内容摘要用哪种算法，见digest.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DigestAlgo {
+    Blake3,
+    Sha256,
+}
+
+impl DigestAlgo {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "blake3" => Some(Self::Blake3),
+            "sha256" | "sha-256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// top_n要哪些节点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TopKind {
+    /// 文件和符号链接
+    Files,
+    Dirs,
+    #[default]
+    All,
+}
+
+/// top_n按什么排
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TopBy {
+    /// 表观大小
+    #[default]
+    Size,
+    /// 占的磁盘空间
+    Disk,
+    /// 下面的文件数，只有文件夹有
+    FileCount,
+}
+
 /// aka serialization and deserialization
 pub trait ManagerStorage {
-    /// serialize and deserialize
-    fn dump(&self, path: &PathBuf) -> Result<(), Box<dyn Error>>;
-    fn load(&mut self, path: &PathBuf) -> Result<(), Box<dyn Error>>;
+    /// serialize and deserialize, format picked by extension
+    fn dump(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        self.dump_as(path, StorageFormat::from_path(path))
+    }
+    fn load(&mut self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        self.load_as(path, StorageFormat::from_path(path))
+    }
+    /// 不看扩展名，指定格式
+    fn dump_as(&self, path: &PathBuf, format: StorageFormat) -> Result<(), Box<dyn Error>>;
+    fn load_as(&mut self, path: &PathBuf, format: StorageFormat) -> Result<(), Box<dyn Error>>;
 }
 
 
@@ -33,6 +163,14 @@ where Self: Sized + Hash
     fn path(&self) -> &PathBuf;
     fn last_modified(&self) -> u128;
     fn size(&self) -> u64;
+    /// 实际占的磁盘空间，文件夹是汇总的
+    fn disk_size(&self) -> u64;
+    fn size_as(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.size(),
+            SizeMode::Disk => self.disk_size(),
+        }
+    }
     fn count_dir(&self) -> Option<u64>;
     fn count_file(&self) -> Option<u64>;
 }
@@ -0,0 +1,594 @@
+//! 节点的slab：所有节点放在一个Vec里，每个节点只存自己那一段名字（驻留，多个节点共用一份），
+//! 路径顺着parent往上拼，子节点是slot下标的Vec<u32>。
+//!
+//! 外面还是用u64的id（见id.rs），ids把id映射到slot；删掉的slot进free，下次插入复用。
+//! get拿到的JNode是现拼的一份拷贝，要改就用update，改完写回slot。
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::action::{JNodeAction, TopBy, TopKind};
+use super::manager::ROOT_PARENT;
+use super::node::{pretty_size, DirNode, FileNode, JNode, SymlinkNode};
+use super::digest::Digest;
+use super::dupes::FileHash;
+use super::top::{RankKey, Ranking};
+use super::types::{TypeStat, TypeStats};
+
+const NIL: u32 = u32::MAX;
+
+/// 驻留的名字段，带引用计数，没节点用了就回收
+#[derive(Debug, Default)]
+struct Interner {
+    /// (名字, 引用数)，None是空位
+    strings: Vec<Option<(Arc<OsStr>, u32)>>,
+    lookup: HashMap<Arc<OsStr>, u32>,
+    free: Vec<u32>,
+    bytes: usize,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &OsStr) -> u32 {
+        if let Some(&k) = self.lookup.get(name) {
+            self.strings[k as usize].as_mut().unwrap().1 += 1;
+            return k;
+        }
+        let name: Arc<OsStr> = Arc::from(name);
+        self.bytes += name.len();
+        let entry = Some((name.clone(), 1));
+        let k = match self.free.pop() {
+            Some(k) => {
+                self.strings[k as usize] = entry;
+                k
+            }
+            None => {
+                self.strings.push(entry);
+                (self.strings.len() - 1) as u32
+            }
+        };
+        self.lookup.insert(name, k);
+        k
+    }
+
+    fn release(&mut self, k: u32) {
+        let entry = self.strings[k as usize].as_mut().unwrap();
+        entry.1 -= 1;
+        if entry.1 == 0 {
+            let (name, _) = self.strings[k as usize].take().unwrap();
+            self.bytes -= name.len();
+            self.lookup.remove(&name);
+            self.free.push(k);
+        }
+    }
+
+    fn get(&self, k: u32) -> &OsStr {
+        &self.strings[k as usize].as_ref().unwrap().0
+    }
+
+    fn len(&self) -> usize {
+        self.lookup.len()
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    id: u64,
+    /// 父节点的slot，根是NIL
+    parent: u32,
+    /// 名字段，根节点存整个路径
+    name: u32,
+    is_dir: bool,
+    dirty: bool,
+    inaccessible: bool,
+    size: u64,
+    disk: u64,
+    last_write_time: u128,
+    count_dir: usize,
+    count_file: usize,
+    /// 文件的设备号、inode、硬链接数；文件夹只有设备号
+    dev: u64,
+    ino: u64,
+    nlink: u32,
+    /// 符号链接的目标，也驻留在名字表里；不是链接的是NIL
+    target: u32,
+    /// 文件夹按扩展名的汇总，扩展名也驻留在名字表里
+    types: Box<[(u32, TypeStat)]>,
+    /// 文件的内容hash，大部分文件没有，装箱省地方
+    hash: Option<Box<FileHash>>,
+    /// 文件的内容摘要，同上
+    digest: Option<Box<Digest>>,
+    children: Vec<u32>,
+}
+
+impl Slot {
+    /// target和types要驻留，由NodeStore处理
+    fn set_data(&mut self, node: &JNode) {
+        match node {
+            JNode::File(file) => {
+                self.is_dir = false;
+                self.dirty = false;
+                self.inaccessible = file.inaccessible;
+                self.size = file.size;
+                self.disk = file.disk;
+                self.last_write_time = file.last_write_time;
+                self.count_dir = 0;
+                self.count_file = 0;
+                self.dev = file.dev;
+                self.ino = file.ino;
+                self.nlink = file.nlink.min(u32::MAX as u64) as u32;
+                self.hash = file.hash.map(Box::new);
+                self.digest = file.digest.map(Box::new);
+            }
+            JNode::Dir(dir) => {
+                self.is_dir = true;
+                self.dirty = dir._dirty;
+                self.inaccessible = dir.inaccessible;
+                self.size = dir.size;
+                self.disk = dir.disk;
+                self.last_write_time = dir.last_write_time;
+                self.count_dir = dir.count_dir;
+                self.count_file = dir.count_file;
+                self.dev = dir.dev;
+                self.ino = 0;
+                self.nlink = 0;
+                self.hash = None;
+                self.digest = None;
+            }
+            JNode::Symlink(link) => {
+                self.is_dir = false;
+                self.dirty = false;
+                self.inaccessible = false;
+                self.size = link.size;
+                self.disk = link.disk;
+                self.last_write_time = link.last_write_time;
+                self.count_dir = 0;
+                self.count_file = 0;
+                self.dev = 0;
+                self.ino = 0;
+                self.nlink = 1;
+                self.hash = None;
+                self.digest = None;
+            }
+        }
+    }
+
+    fn rank_key(&self) -> RankKey {
+        RankKey {
+            is_dir: self.is_dir,
+            size: self.size,
+            disk: self.disk,
+            count_file: self.count_file as u64,
+        }
+    }
+
+    fn to_node(&self, abspath: PathBuf, target: Option<&OsStr>, types: TypeStats) -> JNode {
+        if let Some(target) = target {
+            JNode::Symlink(SymlinkNode {
+                abspath,
+                target: PathBuf::from(target),
+                last_write_time: self.last_write_time,
+                size: self.size,
+                disk: self.disk,
+            })
+        } else if self.is_dir {
+            JNode::Dir(DirNode {
+                abspath,
+                last_write_time: self.last_write_time,
+                size: self.size,
+                disk: self.disk,
+                count_dir: self.count_dir,
+                count_file: self.count_file,
+                dev: self.dev,
+                _dirty: self.dirty,
+                inaccessible: self.inaccessible,
+                types,
+            })
+        } else {
+            JNode::File(FileNode {
+                abspath,
+                last_write_time: self.last_write_time,
+                size: self.size,
+                disk: self.disk,
+                dev: self.dev,
+                ino: self.ino,
+                nlink: self.nlink as u64,
+                inaccessible: self.inaccessible,
+                hash: self.hash.as_deref().copied(),
+                digest: self.digest.as_deref().copied(),
+            })
+        }
+    }
+}
+
+/// stats命令看的内存占用，bytes是按容量估的
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub nodes: usize,
+    /// 包括空着等复用的
+    pub slots: usize,
+    pub free_slots: usize,
+    /// 驻留的不同名字段
+    pub names: usize,
+    pub name_bytes: usize,
+    pub bytes: usize,
+}
+
+impl std::fmt::Display for StoreStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nodes: {}\nslots: {} ({} free)\nnames: {} ({})\nmemory: {}",
+            self.nodes,
+            self.slots,
+            self.free_slots,
+            self.names,
+            pretty_size(self.name_bytes as u64),
+            pretty_size(self.bytes as u64),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NodeStore {
+    slots: Vec<Option<Slot>>,
+    free: Vec<u32>,
+    ids: HashMap<u64, u32>,
+    roots: Vec<u32>,
+    names: Interner,
+    ranks: Ranking,
+    /// 每个slot的先序区间，插入、删除之后过期，下次top时重新编号
+    spans: Vec<(u32, u32)>,
+    spans_stale: bool,
+    top_visits: usize,
+}
+
+impl NodeStore {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains_key(&self, h: &u64) -> bool {
+        self.ids.contains_key(h)
+    }
+
+    /// 拼好路径的一份拷贝
+    pub fn get(&self, h: &u64) -> Option<JNode> {
+        let k = *self.ids.get(h)?;
+        Some(self.node_of(k, self.path_of(k)))
+    }
+
+    pub fn path(&self, h: &u64) -> Option<PathBuf> {
+        self.ids.get(h).map(|k| self.path_of(*k))
+    }
+
+    /// 只有自己那一段，根节点是整个路径
+    pub fn name(&self, h: &u64) -> Option<&OsStr> {
+        self.ids.get(h).map(|k| self.names.get(self.slot(*k).name))
+    }
+
+    /// 有别的硬链接的文件：((dev, ino), (size, disk))，不用拼路径
+    pub fn link(&self, h: &u64) -> Option<((u64, u64), (u64, u64))> {
+        let slot = self.slot(*self.ids.get(h)?);
+        (!slot.is_dir && slot.nlink > 1).then_some(((slot.dev, slot.ino), (slot.size, slot.disk)))
+    }
+
+    pub fn is_dir(&self, h: &u64) -> bool {
+        self.ids.get(h).map(|k| self.slot(*k).is_dir).unwrap_or(false)
+    }
+
+    /// 文件夹所在的设备号，不知道时是0
+    pub fn dev(&self, h: &u64) -> u64 {
+        match self.ids.get(h).map(|k| self.slot(*k)) {
+            Some(slot) if slot.is_dir => slot.dev,
+            _ => 0,
+        }
+    }
+
+    pub fn is_symlink(&self, h: &u64) -> bool {
+        self.ids.get(h).map(|k| self.slot(*k).target != NIL).unwrap_or(false)
+    }
+
+    /// 表里的节点和扫到的metadata是不是同一类（文件夹、文件、符号链接）
+    pub fn same_kind(&self, h: &u64, metadata: &Metadata) -> bool {
+        self.is_dir(h) == metadata.is_dir() && self.is_symlink(h) == metadata.file_type().is_symlink()
+    }
+
+    /// 根节点和不在表里的返回ROOT_PARENT
+    pub fn parent(&self, h: &u64) -> u64 {
+        match self.ids.get(h).map(|k| self.slot(*k).parent) {
+            Some(p) if p != NIL => self.slot(p).id,
+            _ => ROOT_PARENT,
+        }
+    }
+
+    /// ROOT_PARENT的子节点是所有根
+    pub fn children(&self, h: &u64) -> Vec<u64> {
+        let list = if *h == ROOT_PARENT {
+            &self.roots
+        } else {
+            match self.ids.get(h) {
+                Some(k) => &self.slot(*k).children,
+                None => return vec![],
+            }
+        };
+        list.iter().map(|k| self.slot(*k).id).collect()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ids.keys().cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, JNode)> + '_ {
+        self.ids.iter().map(|(h, k)| (*h, self.node_of(*k, self.path_of(*k))))
+    }
+
+    /// 挂到ph下面；ph不在表里（或者是ROOT_PARENT）时当根，名字段存整个路径。
+    /// id已经在表里的先整个删掉
+    pub fn insert(&mut self, h: u64, node: JNode, ph: u64) {
+        if self.ids.contains_key(&h) {
+            self.remove(&h);
+        }
+        let parent = self.ids.get(&ph).cloned().unwrap_or(NIL);
+        let name = match node.path().file_name() {
+            Some(name) if parent != NIL => self.names.intern(name),
+            _ => self.names.intern(node.path().as_os_str()),
+        };
+        let mut slot = Slot {
+            id: h,
+            parent,
+            name,
+            is_dir: false,
+            dirty: false,
+            inaccessible: false,
+            size: 0,
+            disk: 0,
+            last_write_time: 0,
+            count_dir: 0,
+            count_file: 0,
+            dev: 0,
+            ino: 0,
+            nlink: 0,
+            target: NIL,
+            types: Box::new([]),
+            hash: None,
+            digest: None,
+            children: vec![],
+        };
+        slot.set_data(&node);
+        if let Some(target) = node.target() {
+            slot.target = self.names.intern(target.as_os_str());
+        }
+        if let Some(types) = node.types() {
+            slot.types = self.intern_types(types);
+        }
+        let k = match self.free.pop() {
+            Some(k) => {
+                self.slots[k as usize] = Some(slot);
+                k
+            }
+            None => {
+                self.slots.push(Some(slot));
+                (self.slots.len() - 1) as u32
+            }
+        };
+        self.ids.insert(h, k);
+        self.ranks.insert(k, self.slot(k).rank_key());
+        self.spans_stale = true;
+        match parent {
+            NIL => self.roots.push(k),
+            p => self.slot_mut(p).children.push(k),
+        }
+    }
+
+    /// 文件夹标脏，直接改slot，不拼路径、不动排名。
+    /// 已经是脏的、不是文件夹或者不在表里返回false
+    pub fn set_dirty(&mut self, h: &u64) -> bool {
+        let Some(&k) = self.ids.get(h) else {
+            return false;
+        };
+        let slot = self.slot_mut(k);
+        if !slot.is_dir || slot.dirty {
+            return false;
+        }
+        slot.dirty = true;
+        true
+    }
+
+    /// 改拷贝再写回，路径改不了。只改脏标记的用set_dirty。不在表里返回false
+    pub fn update(&mut self, h: &u64, f: impl FnOnce(&mut JNode)) -> bool {
+        let Some(mut node) = self.get(h) else {
+            return false;
+        };
+        f(&mut node);
+        let k = self.ids[h];
+        self.ranks.remove(k, self.slot(k).rank_key());
+        self.slot_mut(k).set_data(&node);
+        self.ranks.insert(k, self.slot(k).rank_key());
+        // 链接目标可能变了，先驻留新的再放掉旧的
+        let target = node.target().map(|t| self.names.intern(t.as_os_str())).unwrap_or(NIL);
+        let old = std::mem::replace(&mut self.slot_mut(k).target, target);
+        if old != NIL {
+            self.names.release(old);
+        }
+        let types = node.types().map(|t| self.intern_types(t)).unwrap_or_default();
+        let old = std::mem::replace(&mut self.slot_mut(k).types, types);
+        self.release_types(&old);
+        true
+    }
+
+    /// 删掉h和它下面的整棵子树，返回删掉的节点（删之前的样子）
+    pub fn remove(&mut self, h: &u64) -> Vec<(u64, JNode)> {
+        let Some(&k) = self.ids.get(h) else {
+            return vec![];
+        };
+        self.spans_stale = true;
+        match self.slot(k).parent {
+            NIL => self.roots.retain(|v| *v != k),
+            p => self.slot_mut(p).children.retain(|v| *v != k),
+        }
+        let mut removed = vec![];
+        // 父节点的slot先拿掉，子节点的路径跟着栈往下拼
+        let mut stack = vec![(k, self.path_of(k))];
+        while let Some((k, path)) = stack.pop() {
+            let slot = self.slots[k as usize].take().unwrap();
+            self.ranks.remove(k, slot.rank_key());
+            for ch in slot.children.iter() {
+                let name = self.names.get(self.slot(*ch).name);
+                stack.push((*ch, path.join(name)));
+            }
+            let target = (slot.target != NIL).then(|| self.names.get(slot.target));
+            let node = slot.to_node(path, target, self.types_of(&slot));
+            self.names.release(slot.name);
+            if slot.target != NIL {
+                self.names.release(slot.target);
+            }
+            self.release_types(&slot.types);
+            self.ids.remove(&slot.id);
+            self.free.push(k);
+            removed.push((slot.id, node));
+        }
+        removed
+    }
+
+    /// h下面（不含h自己）按by排最大的n个。顺着排名往下走，
+    /// 先序区间判断在不在子树里，够n个（或者子树里的都拿到了）就停
+    pub fn top(&mut self, h: &u64, kind: TopKind, by: TopBy, n: usize) -> Vec<u64> {
+        let Some(&root) = self.ids.get(h) else {
+            return vec![];
+        };
+        if self.spans_stale {
+            self.renumber();
+        }
+        let (lo, hi) = self.spans[root as usize];
+        let mut visits = 0;
+        let found = self
+            .ranks
+            .iter(kind, by)
+            .inspect(|_| visits += 1)
+            .filter(|k| (lo + 1..=hi).contains(&self.spans[*k as usize].0))
+            .take(n.min((hi - lo) as usize))
+            .map(|k| self.slot(k).id)
+            .collect();
+        self.top_visits = visits;
+        found
+    }
+
+    /// 上一次top在排名里看了几条
+    pub fn top_visits(&self) -> usize {
+        self.top_visits
+    }
+
+    /// 按先序重新编号：spans[k] = (k的序号, 子树里最大的序号)
+    fn renumber(&mut self) {
+        self.spans.clear();
+        self.spans.resize(self.slots.len(), (0, 0));
+        let mut next = 0;
+        // (slot, 子节点是不是已经压栈了)
+        let mut stack: Vec<(u32, bool)> = self.roots.iter().rev().map(|k| (*k, false)).collect();
+        while let Some((k, done)) = stack.pop() {
+            if done {
+                self.spans[k as usize].1 = next - 1;
+                continue;
+            }
+            self.spans[k as usize].0 = next;
+            next += 1;
+            stack.push((k, true));
+            stack.extend(self.slot(k).children.iter().rev().map(|ch| (*ch, false)));
+        }
+        self.spans_stale = false;
+    }
+
+    pub fn stats(&self) -> StoreStats {
+        let children = self
+            .slots
+            .iter()
+            .flatten()
+            .map(|s| {
+                s.children.capacity() * size_of::<u32>()
+                    + s.types.len() * size_of::<(u32, TypeStat)>()
+                    + s.hash.as_ref().map_or(0, |_| size_of::<FileHash>())
+                    + s.digest.as_ref().map_or(0, |_| size_of::<Digest>())
+            })
+            .sum::<usize>();
+        let names = &self.names;
+        let bytes = self.slots.capacity() * size_of::<Option<Slot>>()
+            + children
+            + (self.free.capacity() + self.roots.capacity()) * size_of::<u32>()
+            + self.spans.capacity() * size_of::<(u32, u32)>()
+            + self.ids.capacity() * (size_of::<u64>() + size_of::<u32>() + 1)
+            + names.strings.capacity() * size_of::<Option<(Arc<OsStr>, u32)>>()
+            + names.lookup.capacity() * (size_of::<Arc<OsStr>>() + size_of::<u32>() + 1)
+            + names.free.capacity() * size_of::<u32>()
+            // 排名里每条(值, slot)，不算BTree节点的开销
+            + self.ranks.len() * size_of::<(u64, u32)>()
+            // Arc的两个计数
+            + names.len() * 2 * size_of::<usize>()
+            + names.bytes;
+        StoreStats {
+            nodes: self.len(),
+            slots: self.slots.len(),
+            free_slots: self.free.len(),
+            names: names.len(),
+            name_bytes: names.bytes,
+            bytes,
+        }
+    }
+
+    fn node_of(&self, k: u32, path: PathBuf) -> JNode {
+        let slot = self.slot(k);
+        let target = (slot.target != NIL).then(|| self.names.get(slot.target));
+        slot.to_node(path, target, self.types_of(slot))
+    }
+
+    fn intern_types(&mut self, types: &TypeStats) -> Box<[(u32, TypeStat)]> {
+        types
+            .iter()
+            .map(|(ext, stat)| (self.names.intern(OsStr::new(ext)), stat))
+            .collect()
+    }
+
+    fn release_types(&mut self, types: &[(u32, TypeStat)]) {
+        for (ext, _) in types.iter() {
+            self.names.release(*ext);
+        }
+    }
+
+    fn types_of(&self, slot: &Slot) -> TypeStats {
+        let mut types = TypeStats::new();
+        for (ext, stat) in slot.types.iter() {
+            types.add(&self.names.get(*ext).to_string_lossy(), stat.bytes, stat.files);
+        }
+        types
+    }
+
+    fn slot(&self, k: u32) -> &Slot {
+        self.slots[k as usize].as_ref().unwrap()
+    }
+
+    fn slot_mut(&mut self, k: u32) -> &mut Slot {
+        self.slots[k as usize].as_mut().unwrap()
+    }
+
+    fn path_of(&self, k: u32) -> PathBuf {
+        let mut segments = vec![];
+        let mut cur = k;
+        while cur != NIL {
+            let slot = self.slot(cur);
+            segments.push(self.names.get(slot.name));
+            cur = slot.parent;
+        }
+        let mut path = PathBuf::new();
+        for seg in segments.into_iter().rev() {
+            path.push(Path::new(seg));
+        }
+        path
+    }
+}
@@ -0,0 +1,213 @@
+//! 比较两棵树：两个manager、或者manager和一份dump，逐行比DumpData。
+//!
+//! 每个路径只看size、last_write_time、target、是不是文件夹：
+//! size变了是Resized，size没变别的变了是Modified，一边没有的是Added/Removed。
+//! 文件夹的汇总只从文件（包括符号链接）的变化往上加，不信文件夹行里的size，
+//! 脏的文件夹也算得对。
+//!
+//! 机器读的格式就是Diff本身的JSON：
+//! ```text
+//! {"changes":[{"kind":"Resized","abspath":"/data/a.txt","before":{...DumpData},"after":{...DumpData}}, ...],
+//!  "dirs":[{"abspath":"/data","size_delta":5,"disk_delta":4096,"added":0,"removed":0,"resized":1,"modified":0}, ...]}
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::action::{LoadMode, ManagerStorage, StorageFormat};
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::{DumpData, JNode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Resized,
+    Modified,
+}
+
+/// 一个路径的变化，Added没有before，Removed没有after
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub abspath: String,
+    pub before: Option<DumpData>,
+    pub after: Option<DumpData>,
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        Path::new(&self.abspath)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.after.as_ref().or(self.before.as_ref()).is_some_and(is_dir)
+    }
+
+    pub fn size_delta(&self) -> i64 {
+        size_of(&self.after) as i64 - size_of(&self.before) as i64
+    }
+
+    pub fn disk_delta(&self) -> i64 {
+        disk_of(&self.after) as i64 - disk_of(&self.before) as i64
+    }
+}
+
+/// 一个文件夹下面（所有层）文件变化的汇总
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirDelta {
+    pub abspath: String,
+    pub size_delta: i64,
+    pub disk_delta: i64,
+    pub added: usize,
+    pub removed: usize,
+    pub resized: usize,
+    pub modified: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Diff {
+    /// 按路径排
+    pub changes: Vec<Change>,
+    /// size变化大的在前
+    pub dirs: Vec<DirDelta>,
+}
+
+impl Diff {
+    /// before -> after，两边的行不用排好序
+    pub fn between(before: Vec<DumpData>, after: Vec<DumpData>) -> Self {
+        let mut rows: BTreeMap<String, (Option<DumpData>, Option<DumpData>)> = BTreeMap::new();
+        for data in before {
+            let row = rows.entry(data.abspath.clone()).or_default();
+            row.0 = Some(data);
+        }
+        for data in after {
+            let row = rows.entry(data.abspath.clone()).or_default();
+            row.1 = Some(data);
+        }
+        let dir_paths = rows
+            .iter()
+            .filter(|(_, (b, a))| b.as_ref().is_some_and(is_dir) || a.as_ref().is_some_and(is_dir))
+            .map(|(p, _)| PathBuf::from(p))
+            .collect::<HashSet<_>>();
+
+        let mut changes = vec![];
+        for (abspath, (before, after)) in rows.into_iter() {
+            let kind = match (&before, &after) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(b), Some(a)) if b.size != a.size => ChangeKind::Resized,
+                (Some(b), Some(a))
+                    if b.last_write_time != a.last_write_time || b.target != a.target || is_dir(b) != is_dir(a) =>
+                {
+                    ChangeKind::Modified
+                }
+                _ => continue,
+            };
+            changes.push(Change {
+                kind,
+                abspath,
+                before,
+                after,
+            });
+        }
+
+        let mut dirs: BTreeMap<&Path, DirDelta> = BTreeMap::new();
+        for change in changes.iter().filter(|c| !c.is_dir()) {
+            for dir in change.path().ancestors().skip(1).take_while(|p| dir_paths.contains(*p)) {
+                let delta = dirs.entry(dir).or_default();
+                delta.size_delta += change.size_delta();
+                delta.disk_delta += change.disk_delta();
+                match change.kind {
+                    ChangeKind::Added => delta.added += 1,
+                    ChangeKind::Removed => delta.removed += 1,
+                    ChangeKind::Resized => delta.resized += 1,
+                    ChangeKind::Modified => delta.modified += 1,
+                }
+            }
+        }
+        let mut dirs = dirs
+            .into_iter()
+            .map(|(path, delta)| DirDelta {
+                abspath: path.to_str().unwrap().to_string(),
+                ..delta
+            })
+            .collect::<Vec<_>>();
+        dirs.sort_by(|x, y| {
+            y.size_delta
+                .unsigned_abs()
+                .cmp(&x.size_delta.unsigned_abs())
+                .then_with(|| x.abspath.cmp(&y.abspath))
+        });
+        Self { changes, dirs }
+    }
+
+    /// 只留path和它下面的
+    pub fn within(&self, path: &Path) -> Self {
+        Self {
+            changes: self.changes.iter().filter(|c| c.path().starts_with(path)).cloned().collect(),
+            dirs: self
+                .dirs
+                .iter()
+                .filter(|d| Path::new(&d.abspath).starts_with(path))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    /// path这个文件夹的汇总，下面没变化时是None
+    pub fn dir(&self, path: &Path) -> Option<&DirDelta> {
+        self.dirs.iter().find(|d| Path::new(&d.abspath) == path)
+    }
+}
+
+fn is_dir(data: &DumpData) -> bool {
+    // 旧缓存没有is_dir列，和from_dump_trusted一样去stat
+    data.target.is_none() && data.is_dir.unwrap_or_else(|| Path::new(&data.abspath).is_dir())
+}
+
+fn size_of(data: &Option<DumpData>) -> u64 {
+    data.as_ref().map(|d| d.size).unwrap_or(0)
+}
+
+fn disk_of(data: &Option<DumpData>) -> u64 {
+    data.as_ref().map(|d| d.disk.unwrap_or(d.size)).unwrap_or(0)
+}
+
+impl JManager<u64, JNode> {
+    /// self -> other
+    pub fn diff(&self, other: &Self) -> Result<Diff, Box<dyn std::error::Error>> {
+        Ok(Diff::between(self.dump_rows()?, other.dump_rows()?))
+    }
+
+    /// 从index_path那份dump到现在，dump按LoadMode::Trust读，不碰文件系统
+    pub fn diff_since(&self, index_path: &PathBuf) -> Result<Diff, Box<dyn std::error::Error>> {
+        Ok(Diff::between(Self::read_rows(index_path)?, self.dump_rows()?))
+    }
+
+    /// 比较两份dump
+    pub fn diff_files(before: &PathBuf, after: &PathBuf) -> Result<Diff, Box<dyn std::error::Error>> {
+        Ok(Diff::between(Self::read_rows(before)?, Self::read_rows(after)?))
+    }
+
+    fn read_rows(index_path: &PathBuf) -> Result<Vec<DumpData>, Box<dyn std::error::Error>> {
+        if !index_path.exists() {
+            return Err(JError::NotExistingPath(index_path.clone()).into());
+        }
+        let mut mng = Self::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load_as(index_path, StorageFormat::from_path(index_path))?;
+        mng.dump_rows()
+    }
+}
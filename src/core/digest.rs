@@ -0,0 +1,217 @@
+//! 内容摘要和完整性校验：打开set_digest之后，update_node给每个文件算一个BLAKE3或者SHA-256，
+//! 存在FileNode上，跟着索引一起落盘。
+//!
+//! verify_digests重新读文件算一遍，和存下来的比：size、mtime都没变但内容变了的就是坏了（位翻转、被人改过又把时间改回去）。
+//! size、mtime变了的是正常修改，只列出来，不算坏；下次update_node会重新算。
+//! 摘要只在size、mtime变了的时候扔掉（FileNode::update），所以校验的基准一直是第一次算的那个。
+//!
+//! CSV里是一列`算法:十六进制`，比如`blake3:af13..`，没有的是空串。
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rayon::prelude::*;
+use sha2::{Digest as _, Sha256};
+
+use super::action::{DigestAlgo, JNodeAction};
+use super::dupes::to_hex;
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::{metadata_last_modified, JNode};
+
+/// 一个文件的内容摘要，两种算法都是32字节
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Digest {
+    pub algo: DigestAlgo,
+    pub bytes: [u8; 32],
+}
+
+impl Digest {
+    pub fn of_file(path: &Path, algo: DigestAlgo) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let bytes = match algo {
+            DigestAlgo::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update_reader(file)?;
+                *hasher.finalize().as_bytes()
+            }
+            DigestAlgo::Sha256 => {
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher)?;
+                hasher.finalize().into()
+            }
+        };
+        Ok(Self { algo, bytes })
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algo.name(), to_hex(&self.bytes))
+    }
+}
+
+impl FromStr for Digest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algo, hex) = s.split_once(':').ok_or(format!("bad digest: {s}"))?;
+        let algo = DigestAlgo::from_name(algo).ok_or(format!("unknown digest algorithm: {algo}"))?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(format!("bad digest: {s}"));
+        }
+        let mut bytes = [0u8; 32];
+        for (k, b) in bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[2 * k..2 * k + 2], 16).map_err(|_| format!("bad digest: {s}"))?;
+        }
+        Ok(Self { algo, bytes })
+    }
+}
+
+/// CSV里的一列，空的是空串；旧缓存没有这一列
+pub(crate) mod column {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Digest;
+
+    pub(crate) fn serialize<S: Serializer>(digest: &Option<Digest>, s: S) -> Result<S::Ok, S::Error> {
+        match digest {
+            Some(digest) => s.collect_str(digest),
+            None => s.serialize_str(""),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Digest>, D::Error> {
+        let text = String::deserialize(d)?;
+        if text.is_empty() {
+            return Ok(None);
+        }
+        text.parse().map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 内容变了但size、mtime没变的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    pub path: PathBuf,
+    pub expected: Digest,
+    pub actual: Digest,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// 重新算过的文件数
+    pub checked: usize,
+    /// 坏了的，按路径排
+    pub corrupted: Vec<Corruption>,
+    /// size或mtime变了，正常修改，没重新算
+    pub modified: Vec<PathBuf>,
+    /// 还没有摘要的文件数
+    pub unhashed: usize,
+    /// 读不了的（包括已经删掉的）
+    pub errors: Vec<JError>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupted.is_empty()
+    }
+}
+
+enum Checked {
+    Same,
+    Modified,
+    Corrupted(Digest),
+}
+
+fn check(path: &Path, size: u64, last_write_time: u128, expected: Digest) -> Result<Checked, JError> {
+    let metadata = fs::metadata(path).map_err(|e| JError::from_io(path, e))?;
+    if metadata.len() != size || metadata_last_modified(&metadata) != last_write_time {
+        return Ok(Checked::Modified);
+    }
+    let actual = Digest::of_file(path, expected.algo).map_err(|e| JError::from_io(path, e))?;
+    Ok(if actual == expected { Checked::Same } else { Checked::Corrupted(actual) })
+}
+
+impl JManager<u64, JNode> {
+    /// update_node时要不要给文件算摘要、用哪种；None（默认）不算，已经有的不动
+    pub fn set_digest(&mut self, algo: Option<DigestAlgo>) {
+        self.digest = algo;
+    }
+
+    pub fn digest(&self) -> Option<DigestAlgo> {
+        self.digest
+    }
+
+    /// h下面还没有摘要（或者算法不一样）的文件补上，读不了的记进report
+    pub(crate) fn fill_digests(&mut self, h: &u64, algo: DigestAlgo) {
+        let mut todo = vec![];
+        let mut stack = vec![*h];
+        while let Some(h) = stack.pop() {
+            stack.extend(self.nodes.children(&h));
+            if let Some(JNode::File(file)) = self.nodes.get(&h) {
+                if !file.inaccessible && file.digest.is_none_or(|d| d.algo != algo) {
+                    todo.push((h, file.abspath));
+                }
+            }
+        }
+        let digests = self.pool.install(|| {
+            todo.into_par_iter()
+                .map(|(h, path)| (h, Digest::of_file(&path, algo).map_err(|e| JError::from_io(&path, e))))
+                .collect::<Vec<_>>()
+        });
+        for (h, res) in digests {
+            match res {
+                Ok(digest) => {
+                    self.nodes.update(&h, |v| v.set_digest(Some(digest)));
+                }
+                Err(e) => self.report.push(e),
+            }
+        }
+    }
+
+    /// 重新读h下面有摘要的文件，找出size、mtime没变但内容变了的；不改表里的任何东西
+    pub fn verify_digests(&mut self, h: &u64) -> Result<VerifyReport, Box<dyn std::error::Error>> {
+        if !self.nodes.contains_key(h) {
+            return Err(JError::NotExistingNode(line!(), *h).into());
+        }
+        self.materialize(h)?;
+        let mut report = VerifyReport::default();
+        let mut todo = vec![];
+        let mut stack = vec![*h];
+        while let Some(h) = stack.pop() {
+            stack.extend(self.nodes.children(&h));
+            if let Some(JNode::File(file)) = self.nodes.get(&h) {
+                match file.digest {
+                    Some(digest) => todo.push((file.abspath, file.size, file.last_write_time, digest)),
+                    None => report.unhashed += 1,
+                }
+            }
+        }
+        let results = self.pool.install(|| {
+            todo.into_par_iter()
+                .map(|(path, size, last_write_time, expected)| {
+                    let res = check(&path, size, last_write_time, expected);
+                    (path, expected, res)
+                })
+                .collect::<Vec<_>>()
+        });
+        for (path, expected, res) in results {
+            match res {
+                Ok(Checked::Same) => report.checked += 1,
+                Ok(Checked::Modified) => report.modified.push(path),
+                Ok(Checked::Corrupted(actual)) => {
+                    report.checked += 1;
+                    report.corrupted.push(Corruption { path, expected, actual });
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+        report.corrupted.sort_by(|x, y| x.path.cmp(&y.path));
+        report.modified.sort();
+        Ok(report)
+    }
+}
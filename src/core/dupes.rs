@@ -0,0 +1,256 @@
+//! 找重复文件：先按大小分组，再按头尾两块的hash（partial），最后按整个文件的BLAKE3（full），
+//! 每一步只留还有两个以上的组，大部分文件只读头尾两块甚至不读。
+//!
+//! 算出来的hash缓存在FileNode上（arena的slot里），不落盘；
+//! 用之前先is_valid，mtime、size变了的重新stat，FileNode::update会顺手扔掉旧的hash。
+//! 同一个inode的多个硬链接只算一个，它们本来就不占多余的空间。
+//! 已经有BLAKE3摘要（见digest.rs）的文件直接拿摘要当full，不再读。
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use super::action::{DigestAlgo, JNodeAction};
+use super::errors::JError;
+use super::manager::{JManager, ROOT_PARENT};
+use super::node::JNode;
+
+/// partial读头尾各多少字节；不超过两块的文件partial就是整个文件
+const BLOCK: u64 = 4096;
+
+/// 一个文件的内容hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileHash {
+    /// 头尾两块的BLAKE3，取前8个字节
+    pub partial: u64,
+    /// 整个文件的BLAKE3，partial撞了才算；小文件读partial时顺便就有了
+    pub full: Option<[u8; 32]>,
+}
+
+/// 内容一样的一组文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DupeGroup {
+    pub size: u64,
+    pub digest: [u8; 32],
+    /// 按路径排
+    pub files: Vec<PathBuf>,
+}
+
+impl DupeGroup {
+    /// 只留一份能省下的字节数
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+
+    pub fn digest_hex(&self) -> String {
+        to_hex(&self.digest)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DupeReport {
+    /// 能省的多的在前
+    pub groups: Vec<DupeGroup>,
+    /// 这次读了几个文件（头尾两块、整个文件分开算）
+    pub partial_reads: usize,
+    pub full_reads: usize,
+    /// 读不了的文件，跳过
+    pub errors: Vec<JError>,
+}
+
+impl DupeReport {
+    pub fn reclaimable(&self) -> u64 {
+        self.groups.iter().map(DupeGroup::reclaimable).sum()
+    }
+
+    /// 重复的文件数，每组不算留下的那一个
+    pub fn redundant(&self) -> usize {
+        self.groups.iter().map(|g| g.files.len() - 1).sum()
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 头尾两块；不超过两块的整个读进来，full一起算好
+fn partial_hash(path: &Path, size: u64) -> io::Result<FileHash> {
+    let mut file = File::open(path)?;
+    if size <= 2 * BLOCK {
+        let mut buf = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut buf)?;
+        let digest = *blake3::hash(&buf).as_bytes();
+        return Ok(FileHash {
+            partial: u64::from_le_bytes(digest[..8].try_into().unwrap()),
+            full: Some(digest),
+        });
+    }
+    let mut buf = vec![0u8; 2 * BLOCK as usize];
+    file.read_exact(&mut buf[..BLOCK as usize])?;
+    file.seek(SeekFrom::Start(size - BLOCK))?;
+    file.read_exact(&mut buf[BLOCK as usize..])?;
+    let digest = blake3::hash(&buf);
+    Ok(FileHash {
+        partial: u64::from_le_bytes(digest.as_bytes()[..8].try_into().unwrap()),
+        full: None,
+    })
+}
+
+fn full_hash(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// 按key分组，只留两个以上的
+fn groups<K: Eq + std::hash::Hash>(items: impl Iterator<Item = (K, u64)>) -> Vec<Vec<u64>> {
+    let mut map: HashMap<K, Vec<u64>> = HashMap::new();
+    for (k, h) in items {
+        map.entry(k).or_default().push(h);
+    }
+    map.into_values().filter(|v| v.len() > 1).collect()
+}
+
+impl JManager<u64, JNode> {
+    /// h下面（h是None时是表里所有的树）至少min_size字节的重复文件，没展开的块先展开
+    pub fn find_dupes(&mut self, h: Option<&u64>, min_size: u64) -> Result<DupeReport, Box<dyn std::error::Error>> {
+        let roots = match h {
+            Some(h) if !self.nodes.contains_key(h) => return Err(JError::NotExistingNode(line!(), *h).into()),
+            Some(h) => {
+                self.materialize(h)?;
+                vec![*h]
+            }
+            None => {
+                self.materialize_all()?;
+                self.nodes.children(&ROOT_PARENT)
+            }
+        };
+        let mut report = DupeReport::default();
+
+        // 按大小分组，同一个inode只留一个
+        let mut inodes = HashSet::new();
+        let mut by_size = vec![];
+        let mut stack = roots;
+        while let Some(h) = stack.pop() {
+            stack.extend(self.nodes.children(&h));
+            if let Some(JNode::File(file)) = self.nodes.get(&h) {
+                if file.inaccessible || file.size < min_size.max(1) {
+                    continue;
+                }
+                if file.nlink > 1 && !inodes.insert((file.dev, file.ino)) {
+                    continue;
+                }
+                by_size.push((file.size, h));
+            }
+        }
+        let candidates = groups(by_size.into_iter()).concat();
+
+        // 过期的重新stat，再补上没缓存的partial
+        let nodes = &self.nodes;
+        let stale = self.pool.install(|| {
+            candidates
+                .par_iter()
+                .filter(|h| !nodes.get(h).unwrap().is_valid())
+                .cloned()
+                .collect::<HashSet<_>>()
+        });
+        let mut todo = vec![];
+        let mut sized = vec![];
+        for h in candidates {
+            if stale.contains(&h) {
+                let mut res = Ok(());
+                self.nodes.update(&h, |v| res = v.update());
+                if let Err(e) = res {
+                    report.errors.push(JError::from_io(&self.nodes.path(&h).unwrap(), e));
+                    continue;
+                }
+            }
+            let node = self.nodes.get(&h).unwrap();
+            if node.content_hash().is_none() {
+                todo.push((h, node.path().clone(), node.size()));
+            }
+            sized.push((node.size(), h));
+        }
+        // 重新stat之后大小可能变了，再分一次
+        let candidates = groups(sized.into_iter()).concat();
+        let keep = candidates.iter().collect::<HashSet<_>>();
+        let todo = todo.into_iter().filter(|(h, ..)| keep.contains(h)).collect::<Vec<_>>();
+        report.partial_reads = todo.len();
+        let hashed = self.pool.install(|| {
+            todo.into_par_iter()
+                .map(|(h, path, size)| (h, partial_hash(&path, size).map_err(|e| JError::from_io(&path, e))))
+                .collect::<Vec<_>>()
+        });
+        for (h, res) in hashed {
+            match res {
+                Ok(hash) => {
+                    self.nodes.update(&h, |v| v.set_content_hash(Some(hash)));
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+
+        // 大小和partial都一样的才读整个文件
+        let by_partial = candidates.iter().filter_map(|h| {
+            let node = self.nodes.get(h)?;
+            Some(((node.size(), node.content_hash()?.partial), *h))
+        });
+        let candidates = groups(by_partial).concat();
+        for h in candidates.iter() {
+            self.nodes.update(h, |v| match (v.content_hash().copied(), v.digest().copied()) {
+                (Some(mut hash), Some(digest)) if hash.full.is_none() && digest.algo == DigestAlgo::Blake3 => {
+                    hash.full = Some(digest.bytes);
+                    v.set_content_hash(Some(hash));
+                }
+                _ => {}
+            });
+        }
+        let todo = candidates
+            .iter()
+            .map(|h| (*h, self.nodes.get(h).unwrap()))
+            .filter(|(_, node)| node.content_hash().is_some_and(|c| c.full.is_none()))
+            .map(|(h, node)| (h, node.path().clone()))
+            .collect::<Vec<_>>();
+        report.full_reads = todo.len();
+        let hashed = self.pool.install(|| {
+            todo.into_par_iter()
+                .map(|(h, path)| (h, full_hash(&path).map_err(|e| JError::from_io(&path, e))))
+                .collect::<Vec<_>>()
+        });
+        for (h, res) in hashed {
+            match res {
+                Ok(digest) => {
+                    self.nodes.update(&h, |v| {
+                        if let Some(mut hash) = v.content_hash().copied() {
+                            hash.full = Some(digest);
+                            v.set_content_hash(Some(hash));
+                        }
+                    });
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+
+        let by_full = candidates.iter().filter_map(|h| {
+            let node = self.nodes.get(h)?;
+            Some(((node.size(), node.content_hash()?.full?), *h))
+        });
+        let mut map: HashMap<(u64, [u8; 32]), Vec<PathBuf>> = HashMap::new();
+        for (key, h) in by_full {
+            map.entry(key).or_default().push(self.nodes.path(&h).unwrap());
+        }
+        for ((size, digest), mut files) in map.into_iter().filter(|(_, v)| v.len() > 1) {
+            files.sort();
+            report.groups.push(DupeGroup { size, digest, files });
+        }
+        report.groups.sort_by(|x, y| {
+            y.reclaimable()
+                .cmp(&x.reclaimable())
+                .then_with(|| x.files[0].cmp(&y.files[0]))
+        });
+        Ok(report)
+    }
+}
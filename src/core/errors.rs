@@ -1,6 +1,8 @@
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -10,6 +12,17 @@ pub enum JError {
     NotDirectory(PathBuf),
     NotExistingNode(u32, u64),
     CacheError,
+    CorruptedCache(PathBuf, &'static str),
+    /// 规则原文, 原因
+    InvalidRule(String, String),
+    /// 查询原文, 原因
+    InvalidQuery(String, String),
+    /// 其它读文件系统的错误
+    Io(PathBuf, io::Error),
+    /// 扫描被CancelToken叫停了
+    Cancelled,
+    /// verify发现内容变了但size、mtime没变的文件数
+    IntegrityFailed(usize),
 }
 
 impl Display for JError {
@@ -30,8 +43,85 @@ impl Display for JError {
             JError::CacheError => {
                 write!(f, "[Jobs Error::CacheError] Cache file is used by another process")
             },
+            JError::CorruptedCache(path, why) => {
+                write!(f, "[Jobs Error::CorruptedCache] {}: {}", path.display(), why)
+            }
+            JError::InvalidRule(rule, why) => {
+                write!(f, "[Jobs Error::InvalidRule] {}: {}", rule, why)
+            }
+            JError::InvalidQuery(query, why) => {
+                write!(f, "[Jobs Error::InvalidQuery] {}: {}", query, why)
+            }
+            JError::Io(path, e) => {
+                write!(f, "[Jobs Error::Io] {}: {}", path.display(), e)
+            }
+            JError::Cancelled => {
+                write!(f, "[Jobs Error::Cancelled] Scan was cancelled")
+            }
+            JError::IntegrityFailed(n) => {
+                write!(f, "[Jobs Error::IntegrityFailed] {} files changed content with the same size and mtime", n)
+            }
         }
     }
 }
 
 impl Error for JError {}
+
+impl JError {
+    /// 没权限的是NoAuthorization，不存在的是NotExistingPath，别的原样放进Io
+    pub fn from_io(path: &Path, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => JError::NoAuthorization(path.to_path_buf()),
+            io::ErrorKind::NotFound => JError::NotExistingPath(path.to_path_buf()),
+            _ => JError::Io(path.to_path_buf(), e),
+        }
+    }
+
+    /// 出错的路径，和路径无关的错误是None
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            JError::NoAuthorization(path)
+            | JError::NotExistingPath(path)
+            | JError::NotDirectory(path)
+            | JError::CorruptedCache(path, _)
+            | JError::Io(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// 一次扫描里读不了的路径，扫描本身照样做完。
+/// 同一个路径只记一次
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub errors: Vec<JError>,
+    seen: HashSet<PathBuf>,
+}
+
+impl ScanReport {
+    pub(crate) fn push(&mut self, e: JError) {
+        if let Some(path) = e.path() {
+            if !self.seen.insert(path.to_path_buf()) {
+                return;
+            }
+        }
+        self.errors.push(e);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+}
+
+impl Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for e in self.errors.iter() {
+            writeln!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
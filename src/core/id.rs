@@ -0,0 +1,33 @@
+//! 节点id：路径的SipHash-2-4，key固定，跨进程、跨版本、跨机器都一样
+//!
+//! ```text
+//! id = SipHash-2-4(k0, k1, bytes(path))
+//!     k0 = 0x6a6f62732d696430 ("jobs-id0")   k1 = 0x706174682d6b6579 ("path-key")
+//!     bytes(path) = 路径的UTF-8，`\`换成`/`，不补结尾的`/`
+//! ```
+//! 0留给ROOT_PARENT。算出来是0、或者已经被别的路径占了（碰撞）时，
+//! 依次试 SipHash-2-4(k0 + salt, k1, bytes(path))，salt = 1, 2, ...，
+//! 试出来的id记在manager的冲突表里，查的时候先看冲突表。
+//!
+//! 0.1以前用的是std的DefaultHasher，每个Rust版本都可能变。
+//! csv/jidx里本来就不存id，load时按路径重算；json/ndjson里的旧id按路径映射到新id。
+
+use std::hash::Hasher;
+use std::path::Path;
+
+use siphasher::sip::SipHasher24;
+
+const K0: u64 = 0x6a6f_6273_2d69_6430;
+const K1: u64 = 0x7061_7468_2d6b_6579;
+
+/// 路径的自然id，不看冲突表
+pub fn stable_id(path: &Path) -> u64 {
+    salted_id(path, 0)
+}
+
+/// salt为0时就是stable_id
+pub(crate) fn salted_id(path: &Path, salt: u64) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(K0.wrapping_add(salt), K1);
+    hasher.write(path.to_string_lossy().replace('\\', "/").as_bytes());
+    hasher.finish()
+}
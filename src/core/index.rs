@@ -0,0 +1,845 @@
+//! 二进制分块索引 (.jidx)
+//!
+//! ```text
+//! +--------------------------------------------------------------+
+//! | header   magic "JOBSIDX\0" | version u16 | flags u16          |
+//! |          string_count u32  | chunk_count u32                  |
+//! |          meta_len u64      | meta_crc u32                     |
+//! +--------------------------------------------------------------+
+//! | meta     string table: (len u32, bytes)*                       |
+//! |          chunk directory: (offset u64, len u64, first u32,    |
+//! |                            count u32, crc u32, roots u32,     |
+//! |                            root ordinal u32 * roots,          |
+//! |                            parents u32,                       |
+//! |                            parent ordinal u32 * parents)*     |
+//! +--------------------------------------------------------------+
+//! | chunk 0  spine：根附近的文件夹，以及每个子树块的根节点          |
+//! | chunk 1..子树块：同一个文件夹下的若干个叶子（文件、空文件夹），   |
+//! |          接着是若干个兄弟子树的全部后代，先序                   |
+//! +--------------------------------------------------------------+
+//! ```
+//!
+//! 每条记录：parent ordinal u32 | name u32 | kind u8 | flags u8 |
+//! last_write_time u128 | size u64 | disk u64 | (dir only) count_dir u64, count_file u64, dev u64,
+//! type_count u32, (ext u32, bytes u64, files u64) * type_count |
+//! (link only) dev u64, ino u64, nlink u64 | (symlink only) target u32 |
+//! (file with digest) algo u8, digest [u8; 32]
+//!
+//! kind：0 文件，1 文件夹，2 有多个硬链接的文件，3 符号链接，
+//! 符号链接的target和types里的扩展名也放在字符串表里；disk是占的磁盘空间。
+//! 字符串在unix上是原样的字节（文件名不一定是UTF-8），别的平台是UTF-8
+//! flags：bit 0 dirty，bit 1 扫描时读不了（inaccessible），bit 2 文件有内容摘要
+//! algo：0 BLAKE3，1 SHA-256
+//!
+//! ordinal是节点在文件里的全局序号，spine从0开始，子树块接着往后编。
+//! roots是块里装了全部后代的子树根，parents是块里装了部分叶子的文件夹，都在spine里。
+//! 记录里只存路径的最后一段，完整路径靠parent拼出来，
+//! 所以节点id不落盘，load时按路径重新算。
+//!
+//! load只读header、meta和spine，子树块在被locate_node/update_node碰到时才解析，
+//! 一个文件夹下面几百万个文件也是分块放的，不会全塞进spine。
+//! 解析出来的节点一律按LoadMode::Trust处理，不stat。
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::jhash;
+
+use super::action::{DigestAlgo, JNodeAction, ManagerAction};
+use super::digest::Digest;
+use super::errors::JError;
+use super::manager::{JManager, ROOT_PARENT};
+use super::node::{DirNode, FileNode, JNode, SymlinkNode};
+use super::types::{TypeStat, TypeStats};
+use super::utils::segment_os;
+
+const MAGIC: &[u8; 8] = b"JOBSIDX\0";
+/// 格式改了就加一，不认识的版本拒绝load
+const VERSION: u16 = 1;
+const HEADER_LEN: u64 = 32;
+const NO_PARENT: u32 = u32::MAX;
+/// 一个子树块大概装多少个节点
+const CHUNK_TARGET: usize = 4096;
+
+/// 一条记录最少多少字节，按它限制从文件里读出来的个数
+const MIN_RECORD: usize = 4 + 4 + 1 + 1 + 16 + 8 + 8;
+
+const KIND_FILE: u8 = 0;
+const KIND_DIR: u8 = 1;
+const KIND_LINK: u8 = 2;
+const KIND_SYMLINK: u8 = 3;
+
+const ALGO_BLAKE3: u8 = 0;
+const ALGO_SHA256: u8 = 1;
+
+#[derive(Debug)]
+struct ChunkEntry {
+    offset: u64,
+    len: u64,
+    first: u32,
+    count: u32,
+    crc: u32,
+    roots: Vec<u32>,
+    parents: Vec<u32>,
+    loaded: bool,
+}
+
+/// (hash, parent hash, node)
+pub(crate) type Entries = Vec<(u64, u64, JNode)>;
+
+/// load之后还没解析的子树块
+#[derive(Debug)]
+pub(crate) struct LazyIndex {
+    file: PathBuf,
+    strings: Vec<OsString>,
+    /// spine ordinal -> 路径
+    spine: Vec<PathBuf>,
+    chunks: Vec<ChunkEntry>,
+    /// 子树块根节点的路径 -> chunk下标
+    roots: HashMap<PathBuf, Vec<usize>>,
+    /// 文件夹的路径 -> 装了它的叶子的chunk下标
+    leaves: HashMap<PathBuf, Vec<usize>>,
+}
+
+impl LazyIndex {
+    fn is_done(&self) -> bool {
+        self.chunks.iter().all(|c| c.loaded)
+    }
+}
+
+/// 同一个文件夹下装进一个子树块的东西
+struct Pack {
+    dir: u64,
+    /// 整棵子树都在块里，自己在spine里
+    roots: Vec<u64>,
+    /// 没有后代的子节点，自己也在块里
+    leaves: Vec<u64>,
+    size: usize,
+}
+
+impl Pack {
+    fn new(dir: u64) -> Self {
+        Self {
+            dir,
+            roots: vec![],
+            leaves: vec![],
+            size: 0,
+        }
+    }
+}
+
+/// 编码好的一块，还没算offset
+struct Body {
+    buf: Vec<u8>,
+    first: u32,
+    count: u32,
+    roots: Vec<u32>,
+    parents: Vec<u32>,
+}
+
+struct Record {
+    parent: u32,
+    name: u32,
+    kind: u8,
+    dirty: bool,
+    inaccessible: bool,
+    last_write_time: u128,
+    size: u64,
+    disk: u64,
+    count_dir: u64,
+    count_file: u64,
+    dev: u64,
+    ino: u64,
+    nlink: u64,
+    target: u32,
+    /// (扩展名, 字节数, 文件数)
+    types: Vec<(u32, u64, u64)>,
+    digest: Option<Digest>,
+}
+
+impl JManager<u64, JNode> {
+    pub(crate) fn dump_index(&self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        // 还没展开的块也要写回去
+        let pending = self.pending_nodes()?;
+        let mut nodes: HashMap<u64, JNode> = self.nodes.iter().collect();
+        let mut parents: HashMap<u64, u64> = HashMap::new();
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+        for h in self.nodes.ids() {
+            let ph = self.nodes.parent(&h);
+            parents.insert(h, ph);
+            children.entry(ph).or_default().push(h);
+        }
+        for (h, ph, node) in pending.iter() {
+            nodes.insert(*h, node.clone());
+            parents.insert(*h, *ph);
+            children.entry(*ph).or_default().push(*h);
+        }
+        for chs in children.values_mut() {
+            chs.retain(|h| nodes.contains_key(h));
+            chs.sort_by(|a, b| nodes[a].path().cmp(nodes[b].path()));
+        }
+
+        // 子树节点数
+        let mut descendants: HashMap<u64, usize> = HashMap::new();
+        let mut stack = children.get(&ROOT_PARENT).cloned().unwrap_or_default();
+        let mut order = vec![];
+        while let Some(h) = stack.pop() {
+            order.push(h);
+            stack.extend(children.get(&h).into_iter().flatten());
+        }
+        for h in order.iter().rev() {
+            let cnt = children
+                .get(h)
+                .into_iter()
+                .flatten()
+                .map(|c| 1 + descendants[c])
+                .sum();
+            descendants.insert(*h, cnt);
+        }
+
+        // 划分spine和子树块：大子树留在spine里接着拆，小子树和叶子按文件夹装块
+        let mut spine = vec![];
+        let mut packs: Vec<Pack> = vec![];
+        let mut stack = children.get(&ROOT_PARENT).cloned().unwrap_or_default();
+        stack.reverse();
+        spine.extend(stack.iter().cloned());
+        while let Some(h) = stack.pop() {
+            let mut pack = Pack::new(h);
+            let mut big = vec![];
+            for c in children.get(&h).into_iter().flatten() {
+                let d = descendants[c];
+                if d > CHUNK_TARGET {
+                    spine.push(*c);
+                    big.push(*c);
+                    continue;
+                }
+                if d > 0 {
+                    spine.push(*c);
+                    pack.roots.push(*c);
+                } else {
+                    pack.leaves.push(*c);
+                }
+                pack.size += 1 + d;
+                if pack.size >= CHUNK_TARGET {
+                    packs.push(std::mem::replace(&mut pack, Pack::new(h)));
+                }
+            }
+            if pack.size > 0 {
+                packs.push(pack);
+            }
+            big.reverse();
+            stack.extend(big);
+        }
+
+        let mut strings: Vec<OsString> = vec![];
+        let mut string_ids: HashMap<OsString, u32> = HashMap::new();
+        let mut intern = |s: &OsStr| -> u32 {
+            if let Some(id) = string_ids.get(s) {
+                return *id;
+            }
+            let id = strings.len() as u32;
+            string_ids.insert(s.to_os_string(), id);
+            strings.push(s.to_os_string());
+            id
+        };
+
+        let mut ordinals: HashMap<u64, u32> = HashMap::new();
+        let mut bodies: Vec<Body> = vec![];
+
+        let encode = |list: &[u64], ordinals: &mut HashMap<u64, u32>, intern: &mut dyn FnMut(&OsStr) -> u32| {
+            let first = ordinals.len() as u32;
+            let mut buf = vec![];
+            for h in list {
+                let node = &nodes[h];
+                let ph = *parents.get(h).unwrap_or(&ROOT_PARENT);
+                let parent = if ph == ROOT_PARENT {
+                    NO_PARENT
+                } else {
+                    ordinals[&ph]
+                };
+                ordinals.insert(*h, ordinals.len() as u32);
+                let name = intern(segment_os(node.path()));
+                let target = node.target().map(|t| intern(t.as_os_str()));
+                let types: Vec<_> = node
+                    .types()
+                    .map(|t| t.iter().map(|(ext, stat)| (intern(OsStr::new(ext)), stat)).collect())
+                    .unwrap_or_default();
+                write_record(&mut buf, parent, name, target, &types, node);
+            }
+            (buf, first, list.len() as u32)
+        };
+
+        let (buf, first, count) = encode(&spine, &mut ordinals, &mut intern);
+        bodies.push(Body {
+            buf,
+            first,
+            count,
+            roots: vec![],
+            parents: vec![],
+        });
+        for pack in packs {
+            let roots = pack.roots.iter().map(|h| ordinals[h]).collect::<Vec<_>>();
+            let parents = match pack.leaves.is_empty() {
+                true => vec![],
+                false => vec![ordinals[&pack.dir]],
+            };
+            let mut list = pack.leaves;
+            for root in pack.roots {
+                let mut stack = children.get(&root).cloned().unwrap_or_default();
+                stack.reverse();
+                while let Some(h) = stack.pop() {
+                    list.push(h);
+                    let mut chs = children.get(&h).cloned().unwrap_or_default();
+                    chs.reverse();
+                    stack.extend(chs);
+                }
+            }
+            let (buf, first, count) = encode(&list, &mut ordinals, &mut intern);
+            bodies.push(Body {
+                buf,
+                first,
+                count,
+                roots,
+                parents,
+            });
+        }
+
+        // meta
+        let mut meta = vec![];
+        for s in strings.iter() {
+            let bytes = os_bytes(s);
+            put_u32(&mut meta, bytes.len() as u32);
+            meta.extend_from_slice(&bytes);
+        }
+        let mut dir_len = 0;
+        for body in bodies.iter() {
+            dir_len += 8 + 8 + 4 + 4 + 4 + 4 + 4 * body.roots.len() as u64 + 4 + 4 * body.parents.len() as u64;
+        }
+        let mut offset = HEADER_LEN + meta.len() as u64 + dir_len;
+        for body in bodies.iter() {
+            put_u64(&mut meta, offset);
+            put_u64(&mut meta, body.buf.len() as u64);
+            put_u32(&mut meta, body.first);
+            put_u32(&mut meta, body.count);
+            put_u32(&mut meta, crc32fast::hash(&body.buf));
+            put_u32(&mut meta, body.roots.len() as u32);
+            for r in body.roots.iter() {
+                put_u32(&mut meta, *r);
+            }
+            put_u32(&mut meta, body.parents.len() as u32);
+            for p in body.parents.iter() {
+                put_u32(&mut meta, *p);
+            }
+            offset += body.buf.len() as u64;
+        }
+
+        let mut header = vec![];
+        header.extend_from_slice(MAGIC);
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, 0);
+        put_u32(&mut header, strings.len() as u32);
+        put_u32(&mut header, bodies.len() as u32);
+        put_u64(&mut header, meta.len() as u64);
+        put_u32(&mut header, crc32fast::hash(&meta));
+
+        let mut wtr = BufWriter::new(File::create(file_path)?);
+        wtr.write_all(&header)?;
+        wtr.write_all(&meta)?;
+        for body in bodies.iter() {
+            wtr.write_all(&body.buf)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn load_index(&mut self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let corrupted = |why| JError::CorruptedCache(file_path.clone(), why);
+        let mut file = File::open(file_path).map_err(|_| JError::CacheError)?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header).map_err(|_| corrupted("truncated header"))?;
+        let mut rdr = Cursor::new(&header);
+        if rdr.bytes(8)? != MAGIC {
+            return Err(corrupted("bad magic").into());
+        }
+        let version = rdr.u16()?;
+        if version != VERSION {
+            return Err(corrupted("unsupported version").into());
+        }
+        let _flags = rdr.u16()?;
+        let string_count = rdr.u32()?;
+        let chunk_count = rdr.u32()?;
+        let meta_len = rdr.u64()?;
+        let meta_crc = rdr.u32()?;
+
+        // 长度都是从文件里读的，先和文件大小比一下再分配
+        if meta_len > file_len - HEADER_LEN {
+            return Err(corrupted("truncated meta").into());
+        }
+        let mut meta = vec![0u8; meta_len as usize];
+        file.read_exact(&mut meta).map_err(|_| corrupted("truncated meta"))?;
+        if crc32fast::hash(&meta) != meta_crc {
+            return Err(corrupted("meta checksum mismatch").into());
+        }
+        let mut rdr = Cursor::new(&meta);
+        let mut strings = Vec::with_capacity((string_count as usize).min(meta.len() / 4));
+        for _ in 0..string_count {
+            let len = rdr.u32()? as usize;
+            let s = os_string(rdr.bytes(len)?.to_vec()).ok_or(corrupted("bad string"))?;
+            strings.push(s);
+        }
+        let mut chunks = Vec::with_capacity((chunk_count as usize).min(meta.len() / 32));
+        for _ in 0..chunk_count {
+            let offset = rdr.u64()?;
+            let len = rdr.u64()?;
+            let first = rdr.u32()?;
+            let count = rdr.u32()?;
+            let crc = rdr.u32()?;
+            if offset.checked_add(len).is_none_or(|end| end > file_len) {
+                return Err(corrupted("truncated chunk").into());
+            }
+            let roots = rdr.u32_list()?;
+            let parents = rdr.u32_list()?;
+            chunks.push(ChunkEntry {
+                offset,
+                len,
+                first,
+                count,
+                crc,
+                roots,
+                parents,
+                loaded: false,
+            });
+        }
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        // 上一个索引还没展开的部分先展开，lazy只记一个文件
+        self.materialize_all()?;
+
+        let mut lazy = LazyIndex {
+            file: file_path.clone(),
+            strings,
+            spine: vec![],
+            chunks,
+            roots: HashMap::new(),
+            leaves: HashMap::new(),
+        };
+        // spine直接展开
+        let spine = decode_chunk(&lazy, 0)?;
+        lazy.chunks[0].loaded = true;
+        lazy.spine = spine.iter().map(|(_, _, node)| node.path().clone()).collect();
+        for (i, chunk) in lazy.chunks.iter().enumerate().skip(1) {
+            for r in chunk.roots.iter() {
+                let path = lazy.spine.get(*r as usize).ok_or(corrupted("bad chunk root"))?;
+                lazy.roots.entry(path.clone()).or_default().push(i);
+            }
+            for p in chunk.parents.iter() {
+                let path = lazy.spine.get(*p as usize).ok_or(corrupted("bad chunk parent"))?;
+                lazy.leaves.entry(path.clone()).or_default().push(i);
+            }
+        }
+        for (_, _, node) in spine {
+            if let Some(ph) = self.parent_id(node.path()) {
+                self.merge_loaded(ph, node);
+            }
+        }
+        self.lazy = if lazy.is_done() { None } else { Some(lazy) };
+        Ok(())
+    }
+
+    /// 所有还没解析的块都展开
+    pub fn materialize_all(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(lazy) = &self.lazy else {
+            return Ok(());
+        };
+        let all = (0..lazy.chunks.len()).collect();
+        self.load_chunks(all)
+    }
+
+    /// 展开node下面所有还没解析的子树块
+    pub fn materialize(&mut self, node: &u64) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(lazy) = &self.lazy else {
+            return Ok(());
+        };
+        let Some(base) = self.nodes.path(node) else {
+            return Ok(());
+        };
+        let mut todo = vec![];
+        for (root, chunks) in lazy.roots.iter().chain(lazy.leaves.iter()) {
+            if !chunks.iter().any(|c| !lazy.chunks[*c].loaded) {
+                continue;
+            }
+            // root在node的子树里，而且还没被删掉
+            if root.starts_with(&base) && self.lookup(root).is_some() {
+                todo.extend(chunks.iter().cloned());
+            }
+        }
+        self.load_chunks(todo)
+    }
+
+    /// 展开包含path的子树块：从表里已有的最深的祖先往下，只展开这个祖先自己的块，
+    /// 叶子块一次一个，找到下一段就停
+    pub(crate) fn materialize_path(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let Some(lazy) = &self.lazy else {
+                return Ok(());
+            };
+            let Some(known) = path.ancestors().find(|p| self.lookup(p).is_some()) else {
+                return Ok(());
+            };
+            if known == path {
+                return Ok(());
+            }
+            let unloaded = |chunks: Option<&Vec<usize>>| {
+                chunks
+                    .into_iter()
+                    .flatten()
+                    .filter(|k| !lazy.chunks[**k].loaded)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            let mut todo = unloaded(lazy.roots.get(known));
+            if todo.is_empty() {
+                todo = unloaded(lazy.leaves.get(known));
+                todo.truncate(1);
+            }
+            if todo.is_empty() {
+                return Ok(());
+            }
+            self.load_chunks(todo)?;
+        }
+    }
+
+    fn load_chunks(&mut self, todo: Vec<usize>) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(lazy) = self.lazy.take() else {
+            return Ok(());
+        };
+        let mut lazy = lazy;
+        let mut res = Ok(());
+        for k in todo {
+            if lazy.chunks[k].loaded {
+                continue;
+            }
+            match decode_chunk(&lazy, k) {
+                Ok(nodes) => {
+                    for (_, _, node) in nodes {
+                        // 块根已经被删掉的，整块丢弃
+                        if let Some(ph) = self.parent_id(node.path()) {
+                            self.merge_loaded(ph, node);
+                        }
+                    }
+                    lazy.chunks[k].loaded = true;
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        if !lazy.is_done() {
+            self.lazy = Some(lazy);
+        }
+        res
+    }
+
+    /// 还没展开的块里的节点：(hash, parent hash, node)
+    pub(crate) fn pending_nodes(&self) -> Result<Entries, Box<dyn std::error::Error>> {
+        let mut result = vec![];
+        let Some(lazy) = &self.lazy else {
+            return Ok(result);
+        };
+        for (k, chunk) in lazy.chunks.iter().enumerate() {
+            if chunk.loaded {
+                continue;
+            }
+            let mut alive = HashSet::new();
+            for (h, ph, node) in decode_chunk(lazy, k)? {
+                // 父节点已经展开的用表里的id，块里的还是自然id
+                let ph = match self.parent_id(node.path()) {
+                    Some(ph) => ph,
+                    None if alive.contains(&ph) => ph,
+                    None => continue,
+                };
+                alive.insert(h);
+                result.push((h, ph, node));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// target是符号链接目标在字符串表里的下标，types里的扩展名也是
+fn write_record(
+    buf: &mut Vec<u8>,
+    parent: u32,
+    name: u32,
+    target: Option<u32>,
+    types: &[(u32, TypeStat)],
+    node: &JNode,
+) {
+    put_u32(buf, parent);
+    put_u32(buf, name);
+    match node {
+        JNode::File(file) => {
+            buf.push(if file.nlink > 1 { KIND_LINK } else { KIND_FILE });
+            buf.push(flags(false, file.inaccessible) | (file.digest.is_some() as u8) << 2);
+            buf.extend_from_slice(&file.last_write_time.to_le_bytes());
+            put_u64(buf, file.size);
+            put_u64(buf, file.disk);
+            if file.nlink > 1 {
+                put_u64(buf, file.dev);
+                put_u64(buf, file.ino);
+                put_u64(buf, file.nlink);
+            }
+            if let Some(digest) = &file.digest {
+                buf.push(match digest.algo {
+                    DigestAlgo::Blake3 => ALGO_BLAKE3,
+                    DigestAlgo::Sha256 => ALGO_SHA256,
+                });
+                buf.extend_from_slice(&digest.bytes);
+            }
+        }
+        JNode::Dir(dir) => {
+            buf.push(KIND_DIR);
+            buf.push(flags(dir._dirty, dir.inaccessible));
+            buf.extend_from_slice(&dir.last_write_time.to_le_bytes());
+            put_u64(buf, dir.size);
+            put_u64(buf, dir.disk);
+            put_u64(buf, dir.count_dir as u64);
+            put_u64(buf, dir.count_file as u64);
+            put_u64(buf, dir.dev);
+            put_u32(buf, types.len() as u32);
+            for (ext, stat) in types {
+                put_u32(buf, *ext);
+                put_u64(buf, stat.bytes);
+                put_u64(buf, stat.files);
+            }
+        }
+        JNode::Symlink(link) => {
+            buf.push(KIND_SYMLINK);
+            buf.push(0);
+            buf.extend_from_slice(&link.last_write_time.to_le_bytes());
+            put_u64(buf, link.size);
+            put_u64(buf, link.disk);
+            put_u32(buf, target.unwrap());
+        }
+    }
+}
+
+fn flags(dirty: bool, inaccessible: bool) -> u8 {
+    dirty as u8 | (inaccessible as u8) << 1
+}
+
+fn read_record(rdr: &mut Cursor) -> Result<Record, JError> {
+    let parent = rdr.u32()?;
+    let name = rdr.u32()?;
+    let kind = rdr.u8()?;
+    let flags = rdr.u8()?;
+    let (dirty, inaccessible) = (flags & 1 != 0, flags & 2 != 0);
+    let last_write_time = u128::from_le_bytes(rdr.bytes(16)?.try_into().unwrap());
+    let size = rdr.u64()?;
+    let disk = rdr.u64()?;
+    let (count_dir, count_file) = match kind {
+        KIND_DIR => (rdr.u64()?, rdr.u64()?),
+        _ => (0, 0),
+    };
+    let (dev, ino, nlink) = match kind {
+        KIND_LINK => (rdr.u64()?, rdr.u64()?, rdr.u64()?),
+        KIND_DIR => (rdr.u64()?, 0, 1),
+        _ => (0, 0, 1),
+    };
+    let mut types = vec![];
+    if kind == KIND_DIR {
+        for _ in 0..rdr.u32()? {
+            types.push((rdr.u32()?, rdr.u64()?, rdr.u64()?));
+        }
+    }
+    let target = match kind {
+        KIND_SYMLINK => rdr.u32()?,
+        _ => u32::MAX,
+    };
+    let digest = match kind {
+        KIND_FILE | KIND_LINK if flags & 4 != 0 => {
+            let algo = match rdr.u8()? {
+                ALGO_BLAKE3 => DigestAlgo::Blake3,
+                ALGO_SHA256 => DigestAlgo::Sha256,
+                _ => return Err(JError::CorruptedCache(PathBuf::new(), "bad digest algorithm")),
+            };
+            let bytes = rdr.bytes(32)?.try_into().unwrap();
+            Some(Digest { algo, bytes })
+        }
+        _ => None,
+    };
+    Ok(Record {
+        parent,
+        name,
+        kind,
+        dirty,
+        inaccessible,
+        last_write_time,
+        size,
+        disk,
+        count_dir,
+        count_file,
+        dev,
+        ino,
+        nlink,
+        target,
+        types,
+        digest,
+    })
+}
+
+/// 读出第k块，返回 (hash, parent hash, node)，父节点一定排在子节点前面
+fn decode_chunk(lazy: &LazyIndex, k: usize) -> Result<Entries, Box<dyn std::error::Error>> {
+    let corrupted = |why| JError::CorruptedCache(lazy.file.clone(), why);
+    let chunk = &lazy.chunks[k];
+    let mut file = File::open(&lazy.file).map_err(|_| JError::CacheError)?;
+    file.seek(SeekFrom::Start(chunk.offset))?;
+    let mut buf = vec![0u8; chunk.len as usize];
+    file.read_exact(&mut buf).map_err(|_| corrupted("truncated chunk"))?;
+    if crc32fast::hash(&buf) != chunk.crc {
+        return Err(corrupted("chunk checksum mismatch").into());
+    }
+
+    let mut rdr = Cursor::new(&buf);
+    let cap = (chunk.count as usize).min(buf.len() / MIN_RECORD);
+    let mut local: Vec<(u64, PathBuf)> = Vec::with_capacity(cap);
+    let mut result = Vec::with_capacity(cap);
+    for _ in 0..chunk.count {
+        let record = read_record(&mut rdr)?;
+        let name = lazy.strings.get(record.name as usize).ok_or(corrupted("bad name"))?;
+        let (ph, path) = if record.parent == NO_PARENT {
+            (ROOT_PARENT, PathBuf::from(name))
+        } else if record.parent >= chunk.first {
+            let (ph, pp) = local
+                .get((record.parent - chunk.first) as usize)
+                .ok_or(corrupted("bad parent"))?;
+            (*ph, pp.join(name))
+        } else {
+            let pp = lazy.spine.get(record.parent as usize).ok_or(corrupted("bad parent"))?;
+            (jhash!(pp), pp.join(name))
+        };
+        let h = jhash!(path);
+        local.push((h, path.clone()));
+        let mut types = TypeStats::new();
+        for (ext, bytes, files) in record.types.iter() {
+            let ext = lazy.strings.get(*ext as usize).ok_or(corrupted("bad type"))?;
+            types.add(&ext.to_string_lossy(), *bytes, *files);
+        }
+        let node = match record.kind {
+            KIND_DIR => JNode::Dir(DirNode {
+                abspath: path,
+                last_write_time: record.last_write_time,
+                size: record.size,
+                disk: record.disk,
+                count_dir: record.count_dir as usize,
+                count_file: record.count_file as usize,
+                dev: record.dev,
+                _dirty: record.dirty,
+                inaccessible: record.inaccessible,
+                types,
+            }),
+            KIND_SYMLINK => JNode::Symlink(SymlinkNode {
+                abspath: path,
+                target: PathBuf::from(lazy.strings.get(record.target as usize).ok_or(corrupted("bad target"))?),
+                last_write_time: record.last_write_time,
+                size: record.size,
+                disk: record.disk,
+            }),
+            _ => JNode::File(FileNode {
+                abspath: path,
+                last_write_time: record.last_write_time,
+                size: record.size,
+                disk: record.disk,
+                dev: record.dev,
+                ino: record.ino,
+                nlink: record.nlink,
+                inaccessible: record.inaccessible,
+                hash: None,
+                digest: record.digest,
+            }),
+        };
+        result.push((h, ph, node));
+    }
+    Ok(result)
+}
+
+/// -----------------------------------------------------------------------------------------------
+fn put_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+/// 字符串表里的字节
+#[cfg(unix)]
+fn os_bytes(s: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(s: &OsStr) -> Vec<u8> {
+    s.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn os_string(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(OsString::from)
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], JError> {
+        if n > self.buf.len() - self.pos {
+            return Err(JError::CorruptedCache(PathBuf::new(), "unexpected end of data"));
+        }
+        let s = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(s)
+    }
+    fn u8(&mut self) -> Result<u8, JError> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, JError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, JError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    /// (len u32, u32 * len)
+    fn u32_list(&mut self) -> Result<Vec<u32>, JError> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len.saturating_mul(4))?;
+        Ok(bytes.chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect())
+    }
+    fn u64(&mut self) -> Result<u64, JError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
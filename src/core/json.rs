@@ -0,0 +1,304 @@
+//! JSON / NDJSON，给看板之类的外部程序读
+//!
+//! .json 整棵树嵌套：
+//! ```text
+//! {"version":1,"roots":[{"id":..,"path":"/data","is_dir":true,...,"children":[...]}]}
+//! ```
+//! .ndjson 每行一个节点，先序，父节点总在前面，parent为0的是根：
+//! ```text
+//! {"id":..,"parent":0,"path":"/data","is_dir":true,"size":..,...}
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::action::{JNodeAction, LoadMode, ManagerAction};
+use super::errors::JError;
+use super::index::Entries;
+use super::manager::{JManager, ROOT_PARENT};
+use super::node::{DumpData, JNode};
+use super::digest::{column, Digest};
+use super::types::TypeStats;
+
+/// 格式改了就加一，比这个新的拒绝load
+const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NdRecord {
+    id: u64,
+    parent: u64,
+    path: String,
+    is_dir: bool,
+    size: u64,
+    disk: u64,
+    last_write_time: u128,
+    count_dir: usize,
+    count_file: usize,
+    dirty: bool,
+    /// 有多个硬链接的文件才有：[dev, ino, nlink]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link: Option<(u64, u64, u64)>,
+    /// 文件夹的设备号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dev: Option<u64>,
+    /// 符号链接才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    /// 扫描时读不了的才有
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    inaccessible: bool,
+    /// 文件夹按扩展名的汇总：{"log":{"bytes":..,"files":..},...}
+    #[serde(default, skip_serializing_if = "TypeStats::is_empty")]
+    types: TypeStats,
+    /// 有内容摘要的文件才有："blake3:十六进制"
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "column")]
+    digest: Option<Digest>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TreeNode {
+    id: u64,
+    path: String,
+    is_dir: bool,
+    size: u64,
+    disk: u64,
+    last_write_time: u128,
+    count_dir: usize,
+    count_file: usize,
+    dirty: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link: Option<(u64, u64, u64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dev: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    inaccessible: bool,
+    #[serde(default, skip_serializing_if = "TypeStats::is_empty")]
+    types: TypeStats,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "column")]
+    digest: Option<Digest>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TreeDoc {
+    version: u32,
+    roots: Vec<TreeNode>,
+}
+
+impl NdRecord {
+    fn new(h: u64, ph: u64, node: &JNode) -> Self {
+        let data: DumpData = node.clone().into();
+        Self {
+            id: h,
+            parent: ph,
+            path: data.abspath,
+            is_dir: node.is_dir(),
+            size: data.size,
+            disk: data.disk.unwrap_or(data.size),
+            last_write_time: data.last_write_time,
+            count_dir: data.count_dir,
+            count_file: data.count_file,
+            dirty: data._dirty,
+            link: (data.nlink > 1).then_some((data.dev, data.ino, data.nlink)),
+            dev: (node.is_dir() && data.dev != 0).then_some(data.dev),
+            target: data.target,
+            inaccessible: data.inaccessible,
+            types: data.types,
+            digest: data.digest,
+        }
+    }
+
+    fn into_node(self) -> JNode {
+        let (dev, ino, nlink) = self.link.unwrap_or((self.dev.unwrap_or(0), 0, 1));
+        JNode::from_dump_trusted(DumpData {
+            abspath: self.path,
+            last_write_time: self.last_write_time,
+            size: self.size,
+            count_dir: self.count_dir,
+            count_file: self.count_file,
+            _dirty: self.dirty,
+            is_dir: Some(self.is_dir),
+            dev,
+            ino,
+            nlink,
+            disk: Some(self.disk),
+            target: self.target,
+            inaccessible: self.inaccessible,
+            types: self.types,
+            digest: self.digest,
+        })
+    }
+}
+
+impl TreeNode {
+    fn into_record(self, parent: u64) -> (NdRecord, Vec<TreeNode>) {
+        let record = NdRecord {
+            id: self.id,
+            parent,
+            path: self.path,
+            is_dir: self.is_dir,
+            size: self.size,
+            disk: self.disk,
+            last_write_time: self.last_write_time,
+            count_dir: self.count_dir,
+            count_file: self.count_file,
+            dirty: self.dirty,
+            link: self.link,
+            dev: self.dev,
+            target: self.target,
+            inaccessible: self.inaccessible,
+            types: self.types,
+            digest: self.digest,
+        };
+        (record, self.children)
+    }
+}
+
+/// 文件里的id -> 表里的id。
+/// 文件里的id和表里的对不上（别的程序写的、或者冲突时分的id不一样）时按路径重算，树的形状不变
+#[derive(Default)]
+struct Remap(HashMap<u64, u64>);
+
+impl Remap {
+    fn parent(&self, ph: u64) -> Option<u64> {
+        if ph == ROOT_PARENT {
+            Some(ROOT_PARENT)
+        } else {
+            self.0.get(&ph).cloned()
+        }
+    }
+}
+
+impl JManager<u64, JNode> {
+    /// (hash, parent hash, node)，包括还没展开的块，先序，兄弟按路径排
+    fn preorder(&self) -> Result<Entries, Box<dyn std::error::Error>> {
+        let mut nodes: HashMap<u64, (u64, JNode)> = self
+            .nodes
+            .iter()
+            .map(|(h, node)| (h, (self.get_parent(&h), node)))
+            .collect();
+        for (h, ph, node) in self.pending_nodes()? {
+            nodes.insert(h, (ph, node));
+        }
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (h, (ph, _)) in nodes.iter() {
+            children.entry(*ph).or_default().push(*h);
+        }
+        for chs in children.values_mut() {
+            // 倒着排，出栈时就是正序
+            chs.sort_by(|a, b| nodes[b].1.path().cmp(nodes[a].1.path()));
+        }
+        let mut result = Vec::with_capacity(nodes.len());
+        let mut stack = children.get(&ROOT_PARENT).cloned().unwrap_or_default();
+        while let Some(h) = stack.pop() {
+            stack.extend(children.get(&h).into_iter().flatten());
+            let (ph, node) = nodes.remove(&h).unwrap();
+            result.push((h, ph, node));
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn dump_ndjson(&self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let mut wtr = BufWriter::new(File::create(file_path)?);
+        for (h, ph, node) in self.preorder()? {
+            serde_json::to_writer(&mut wtr, &NdRecord::new(h, ph, &node))?;
+            wtr.write_all(b"\n")?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn dump_json(&self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        // 先序倒过来，子节点总比父节点先处理完
+        let mut built: HashMap<u64, Vec<TreeNode>> = HashMap::new();
+        for (h, ph, node) in self.preorder()?.into_iter().rev() {
+            let record = NdRecord::new(h, ph, &node);
+            let mut children = built.remove(&h).unwrap_or_default();
+            children.reverse();
+            built.entry(ph).or_default().push(TreeNode {
+                id: record.id,
+                path: record.path,
+                is_dir: record.is_dir,
+                size: record.size,
+                disk: record.disk,
+                last_write_time: record.last_write_time,
+                count_dir: record.count_dir,
+                count_file: record.count_file,
+                dirty: record.dirty,
+                link: record.link,
+                dev: record.dev,
+                target: record.target,
+                inaccessible: record.inaccessible,
+                types: record.types,
+                digest: record.digest,
+                children,
+            });
+        }
+        let mut roots = built.remove(&ROOT_PARENT).unwrap_or_default();
+        roots.reverse();
+        let doc = TreeDoc {
+            version: JSON_VERSION,
+            roots,
+        };
+        let mut wtr = BufWriter::new(File::create(file_path)?);
+        serde_json::to_writer(&mut wtr, &doc)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn load_ndjson(&mut self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::open(file_path).map_err(|_| JError::CacheError)?;
+        let mut remap = Remap::default();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: NdRecord = serde_json::from_str(&line)
+                .map_err(|_| JError::CorruptedCache(file_path.clone(), "bad ndjson line"))?;
+            self.load_record(record, &mut remap);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load_json(&mut self, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::open(file_path).map_err(|_| JError::CacheError)?;
+        // 每层文件夹占两层嵌套（对象和children），默认128层的限制不够；去掉限制，栈不够时在堆上续
+        let mut de = serde_json::Deserializer::from_reader(BufReader::new(file));
+        de.disable_recursion_limit();
+        let de = serde_stacker::Deserializer::new(&mut de);
+        let doc = TreeDoc::deserialize(de).map_err(|_| JError::CorruptedCache(file_path.clone(), "bad json"))?;
+        if doc.version == 0 || doc.version > JSON_VERSION {
+            return Err(JError::CorruptedCache(file_path.clone(), "unsupported version").into());
+        }
+        let mut remap = Remap::default();
+        let mut stack = doc.roots.into_iter().rev().map(|v| (ROOT_PARENT, v)).collect::<Vec<_>>();
+        while let Some((ph, tree)) = stack.pop() {
+            let (record, children) = tree.into_record(ph);
+            let id = record.id;
+            self.load_record(record, &mut remap);
+            stack.extend(children.into_iter().rev().map(|v| (id, v)));
+        }
+        Ok(())
+    }
+
+    /// 父节点不在（被丢了或者Verify时路径没了）的整棵子树都跳过
+    fn load_record(&mut self, record: NdRecord, remap: &mut Remap) {
+        let Some(ph) = remap.parent(record.parent) else {
+            return;
+        };
+        if self.load_mode() == LoadMode::Verify && !PathBuf::from(&record.path).exists() {
+            return;
+        }
+        let id = record.id;
+        let h = self.merge_loaded(ph, record.into_node());
+        remap.0.insert(id, h);
+    }
+}
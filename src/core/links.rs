@@ -0,0 +1,94 @@
+//! 硬链接：同一个inode在一棵子树里只算一次大小（LinkMode::Once）。
+//!
+//! 每个文件夹记一张表：子树里有多个硬链接的inode -> (大小, 磁盘占用)。
+//! aggregate时把子节点的表并起来，撞上的就是重复算了的。
+//! 没有硬链接的文件夹不占表项；表不落盘，load之后第一次用时从文件节点整个重建。
+//! 文件数还是按目录项算，每个链接都算一个文件。
+
+use std::collections::HashMap;
+
+use super::action::{JNodeAction, LinkMode};
+use super::manager::{JManager, ROOT_PARENT};
+use super::node::JNode;
+
+/// (dev, ino) -> (大小, 磁盘占用)
+type Table = HashMap<(u64, u64), (u64, u64)>;
+
+#[derive(Debug, Default)]
+pub(crate) struct LinkTables {
+    tables: HashMap<u64, Table>,
+    ready: bool,
+}
+
+impl LinkTables {
+    /// 表里没有的节点被load进来了，下次用之前重建
+    pub(crate) fn invalidate(&mut self) {
+        self.ready = false;
+    }
+
+    pub(crate) fn forget(&mut self, h: &u64) {
+        self.tables.remove(h);
+    }
+}
+
+impl JManager<u64, JNode> {
+    pub fn link_mode(&self) -> LinkMode {
+        self.link_mode
+    }
+
+    /// 换模式后所有文件夹标脏，下次update_node重算
+    pub fn set_link_mode(&mut self, mode: LinkMode) {
+        if mode != self.link_mode {
+            self.link_mode = mode;
+            self.invalidate_dirs();
+        }
+    }
+
+    fn rebuild_links(&mut self) {
+        self.links.tables.clear();
+        for h in self.nodes.ids().collect::<Vec<_>>() {
+            let Some((key, size)) = self.nodes.link(&h) else {
+                continue;
+            };
+            let mut ph = self.nodes.parent(&h);
+            while ph != ROOT_PARENT {
+                self.links.tables.entry(ph).or_default().insert(key, size);
+                ph = self.nodes.parent(&ph);
+            }
+        }
+        self.links.ready = true;
+    }
+
+    /// aggregate用：子节点之间重复算了多少(大小, 磁盘占用)，顺便更新node_h的表
+    pub(crate) fn dedup_children(&mut self, node_h: &u64, children: &[(JNode, u64)]) -> (u64, u64) {
+        if self.link_mode == LinkMode::EachLink {
+            return (0, 0);
+        }
+        if !self.links.ready {
+            self.rebuild_links();
+        }
+        let mut table = Table::new();
+        let mut dup = (0, 0);
+        for (v, h) in children {
+            if let Some(key) = v.link() {
+                if table.insert(key, (v.size(), v.disk_size())).is_some() {
+                    dup.0 += v.size();
+                    dup.1 += v.disk_size();
+                }
+            } else if let Some(sub) = self.links.tables.get(h) {
+                for (key, (size, disk)) in sub {
+                    if table.insert(*key, (*size, *disk)).is_some() {
+                        dup.0 += size;
+                        dup.1 += disk;
+                    }
+                }
+            }
+        }
+        if table.is_empty() {
+            self.links.tables.remove(node_h);
+        } else {
+            self.links.tables.insert(*node_h, table);
+        }
+        dup
+    }
+}
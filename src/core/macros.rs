@@ -1,27 +1,18 @@
-
-
+/// 路径（或者节点）的自然id，见id.rs
 #[macro_export]
 macro_rules! jhash {
     ($x:expr) => {
-        {
-            use std::hash::{DefaultHasher, Hasher};
-
-            let mut hasher = DefaultHasher::new();
-            ($x).hash(&mut hasher);
-            hasher.finish() as u64
-        }
+        $crate::stable_id(::std::convert::AsRef::<::std::path::Path>::as_ref(&$x))
     };
 }
 
 #[test]
 fn test_jhash() {
-    use std::{hash::{DefaultHasher, Hash, Hasher}, path::Path};
+    use std::path::Path;
 
     let pathbuf = Path::new("E:/QQ/obj/HummerSetupDll").to_path_buf();
 
-    let mut hasher = DefaultHasher::new();
-    pathbuf.hash(&mut hasher);
-    let hash = hasher.finish();
-
-    assert_eq!(jhash!(pathbuf), hash);
-}
\ No newline at end of file
+    assert_eq!(jhash!(pathbuf), crate::stable_id(&pathbuf));
+    // 分隔符不影响id
+    assert_eq!(jhash!(pathbuf), jhash!(Path::new("E:\\QQ\\obj\\HummerSetupDll")));
+}
@@ -1,15 +1,56 @@
 #![allow(unused)]
 
 mod action;
+mod arena;
 mod node;
 mod manager;
 mod macros;
+mod id;
 mod utils; 
 mod errors;
+mod index;
+mod json;
+mod search;
+mod rules;
+mod links;
+mod symlinks;
+mod mounts;
+mod progress;
+mod snapshot;
+mod diff;
+mod top;
+mod query;
+mod types;
+mod dupes;
+mod digest;
+#[cfg(feature = "watch")]
+mod watch;
 
 pub use node::JNode;
+pub use arena::{NodeStore, StoreStats};
+pub use node::DumpData;
+pub(crate) use node::{pretty_last_modified, pretty_size};
+pub(crate) use rules::parse_size;
+pub use id::stable_id;
+pub use errors::{JError, ScanReport};
 pub use action::JNodeAction;
 pub use action::ManagerAction;
 pub use action::ManagerStorage;
+pub use action::StorageFormat;
+pub use action::LoadMode;
+pub use action::LinkMode;
+pub use action::SizeMode;
+pub use action::DigestAlgo;
+pub use action::SymlinkPolicy;
+pub use action::TopBy;
+pub use action::TopKind;
+pub use progress::{CancelToken, Progress};
+pub use diff::{Change, ChangeKind, DirDelta, Diff};
+pub use snapshot::{DirStat, Growth, History, SnapshotInfo};
+pub use query::{CmpOp, Literal, Query, QueryExpr, QueryField};
+pub use types::{ext_of, Category, TypeStat, TypeStats};
+pub use dupes::{DupeGroup, DupeReport, FileHash};
+pub use digest::{Corruption, Digest, VerifyReport};
+pub use rules::{Rule, RuleSet, DEFAULT_RULES, IGNORE_FILE};
 
 pub type JManager = manager::JManager<u64, JNode>;
\ No newline at end of file
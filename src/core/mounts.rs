@@ -0,0 +1,63 @@
+//! 单一文件系统：one_file_system打开后，扫描碰到设备号和父文件夹不一样的文件夹（挂载点）就不进去，
+//! 记成空文件夹，和`du -x`一样。
+//!
+//! 每个文件夹节点记着自己的设备号，mounts靠它把子树里的挂载点找出来，不用再读文件系统。
+//! 跟进去的符号链接指到别的设备上，也算挂载点。
+
+use std::fs;
+use std::path::Path;
+
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::{metadata_dev, JNode};
+
+impl JManager<u64, JNode> {
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    /// 改了之后所有文件夹标脏，下次update_node重扫
+    pub fn set_one_file_system(&mut self, on: bool) {
+        if on != self.one_file_system {
+            self.one_file_system = on;
+            self.invalidate_dirs();
+        }
+    }
+
+    /// h子树里的挂载点（不含h自己），按路径排序
+    pub fn mounts(&mut self, h: &u64) -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+        if !self.nodes.contains_key(h) {
+            return Err(JError::NotExistingNode(line!(), *h).into());
+        }
+        self.materialize(h)?;
+        let mut res = vec![];
+        let mut stack = vec![*h];
+        while let Some(parent) = stack.pop() {
+            let dev = self.nodes.dev(&parent);
+            for ch in self.nodes.children(&parent) {
+                if !self.nodes.is_dir(&ch) {
+                    continue;
+                }
+                let ch_dev = self.nodes.dev(&ch);
+                if dev != 0 && ch_dev != 0 && ch_dev != dev {
+                    res.push(ch);
+                }
+                stack.push(ch);
+            }
+        }
+        res.sort_by_key(|h| self.nodes.path(h));
+        Ok(res)
+    }
+}
+
+/// path（metadata是它的）和上一层文件夹是不是在不同的设备上；拿不到设备号的平台上总是false
+pub(crate) fn crosses_device(path: &Path, metadata: &fs::Metadata) -> bool {
+    let dev = metadata_dev(metadata);
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    match fs::metadata(parent) {
+        Ok(p) => dev != 0 && metadata_dev(&p) != 0 && dev != metadata_dev(&p),
+        Err(_) => false,
+    }
+}
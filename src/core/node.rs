@@ -1,24 +1,43 @@
 use serde::{Deserialize, Serialize};
 
 use super::action::JNodeAction;
-use super::utils::is_root;
+use super::digest::Digest;
+use super::dupes::FileHash;
+use super::types::{column, TypeStats};
+use super::utils::{is_root, segment_name};
 use std::fmt::Debug;
 use std::fs;
+use std::io;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone)]
 pub enum JNode {
     File(FileNode),
     Dir(DirNode),
+    /// 不跟进去的符号链接，只记自己和指向哪
+    Symlink(SymlinkNode),
 }
 
 #[derive(Debug, Clone)]
 pub struct FileNode {
     pub abspath: PathBuf,
     pub last_write_time: u128,
+    /// 表观大小（metadata.len()）
     pub size: u64,
+    /// 实际占的磁盘空间（st_blocks * 512）
+    pub disk: u64,
+    /// 设备号、inode、硬链接数，不支持的平台上都是0
+    pub dev: u64,
+    pub ino: u64,
+    pub nlink: u64,
+    /// 上次stat失败（没权限之类），数据是旧的或者是0
+    pub inaccessible: bool,
+    /// 找重复文件时算的内容hash，不落盘；size、mtime变了就扔掉
+    pub hash: Option<FileHash>,
+    /// 内容摘要，set_digest之后update_node算，跟着索引落盘；size、mtime变了就扔掉
+    pub digest: Option<Digest>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,13 +45,31 @@ pub struct DirNode {
     pub abspath: PathBuf,
     pub last_write_time: u128,
     pub size: u64,
+    pub disk: u64,
     pub count_dir: usize,
     pub count_file: usize,
+    /// 所在的设备号，和父文件夹不一样的就是挂载点；旧缓存没有时是0
+    pub dev: u64,
     // pub _scaned: bool,
     pub _dirty: bool,
+    /// 上次读不了（没权限之类），当空文件夹
+    pub inaccessible: bool,
+    /// 下面按扩展名汇总的字节数和文件数，见types.rs
+    pub types: TypeStats,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Serialize, Deserialize)]
+/// 时间和大小都是链接自己的（lstat）
+#[derive(Debug, Clone)]
+pub struct SymlinkNode {
+    pub abspath: PathBuf,
+    /// read_link读出来的，可能是相对路径，也可能已经悬空
+    pub target: PathBuf,
+    pub last_write_time: u128,
+    pub size: u64,
+    pub disk: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Eq, Serialize, Deserialize)]
 pub struct DumpData {
     pub abspath: String,
     pub last_write_time: u128,
@@ -41,89 +78,230 @@ pub struct DumpData {
     pub count_file: usize,
     // pub _scaned: bool,
     pub _dirty: bool,
+    /// 旧缓存没有这一列，读出来是None
+    #[serde(default)]
+    pub is_dir: Option<bool>,
+    /// 文件的设备号、inode、硬链接数，文件夹只有设备号；旧缓存没有这几列，当只有一个链接
+    #[serde(default)]
+    pub dev: u64,
+    #[serde(default)]
+    pub ino: u64,
+    #[serde(default = "one_link")]
+    pub nlink: u64,
+    /// 占的磁盘空间；旧缓存没有这一列，当和size一样
+    #[serde(default)]
+    pub disk: Option<u64>,
+    /// 符号链接指向哪，有这一列的就是符号链接
+    #[serde(default)]
+    pub target: Option<String>,
+    /// 扫描时读不了的节点
+    #[serde(default)]
+    pub inaccessible: bool,
+    /// 文件夹按扩展名的汇总，一列JSON
+    #[serde(default, with = "column")]
+    pub types: TypeStats,
+    /// 文件的内容摘要，`blake3:十六进制`
+    #[serde(default, with = "super::digest::column")]
+    pub digest: Option<Digest>,
+}
+
+fn one_link() -> u64 {
+    1
 }
 
 /// All implementation is down below
 /// -----------------------------------------------------------------------------------------------
 /// -----------------------------------------------------------------------------------------------
-
 impl JNodeAction for JNode {
     fn name(&self) -> String {
-        match self {
-            Self::File(file) => file
-                .abspath
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            Self::Dir(dir) => dir
-                .abspath
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        }
+        segment_name(self.path())
     }
 
     fn path(&self) -> &PathBuf {
         match self {
             JNode::File(file) => &file.abspath,
             JNode::Dir(dir) => &dir.abspath,
+            JNode::Symlink(link) => &link.abspath,
         }
     }
     fn last_modified(&self) -> u128 {
         match self {
             Self::File(file) => file.last_write_time,
             Self::Dir(dir) => dir.last_write_time,
+            Self::Symlink(link) => link.last_write_time,
         }
     }
     fn size(&self) -> u64 {
         match self {
             Self::File(file) => file.size,
             Self::Dir(dir) => dir.size,
+            Self::Symlink(link) => link.size,
+        }
+    }
+    fn disk_size(&self) -> u64 {
+        match self {
+            Self::File(file) => file.disk,
+            Self::Dir(dir) => dir.disk,
+            Self::Symlink(link) => link.disk,
         }
     }
 
     fn count_dir(&self) -> Option<u64> {
         match self {
-            Self::File(_) => None,
             Self::Dir(dir) => Some(dir.count_dir as u64),
+            _ => None,
         }
     }
 
     fn count_file(&self) -> Option<u64> {
         match self {
-            Self::File(_) => None,
             Self::Dir(dir) => Some(dir.count_file as u64),
+            _ => None,
         }
     }
 }
 
 impl JNode {
-    pub(crate) fn new(path: &PathBuf) -> Self {
+    pub(crate) fn new(path: &PathBuf) -> io::Result<Self> {
         // dbg!("[Jobs DEBUG] JNode::new: {:?}", path);
         if path.is_dir() {
-            Self::Dir(DirNode::new(path))
+            Ok(Self::Dir(DirNode::new(path)?))
+        } else {
+            Ok(Self::File(FileNode::new(path)?))
+        }
+    }
+    /// stat不了的路径，数据都是0；is_dir是read_dir给的类型
+    pub(crate) fn inaccessible(path: PathBuf, is_dir: bool) -> Self {
+        if is_dir {
+            Self::Dir(DirNode {
+                abspath: path,
+                last_write_time: 0,
+                size: 0,
+                disk: 0,
+                count_dir: 0,
+                count_file: 0,
+                dev: 0,
+                _dirty: false,
+                inaccessible: true,
+                types: TypeStats::default(),
+            })
+        } else {
+            Self::File(FileNode {
+                abspath: path,
+                last_write_time: 0,
+                size: 0,
+                disk: 0,
+                dev: 0,
+                ino: 0,
+                nlink: 1,
+                inaccessible: true,
+                hash: None,
+                digest: None,
+            })
+        }
+    }
+    /// 用已经拿到的metadata建节点，扫描线程里stat过一次就不用再stat了。
+    /// lstat拿到的符号链接建成Symlink，跟过去的按目标建
+    pub(crate) fn with_metadata(path: PathBuf, metadata: &fs::Metadata) -> Self {
+        if metadata.file_type().is_symlink() {
+            Self::Symlink(SymlinkNode::with_metadata(path, metadata))
+        } else if metadata.is_dir() {
+            Self::Dir(DirNode::with_metadata(path, metadata))
         } else {
-            Self::File(FileNode::new(path))
+            Self::File(FileNode::with_metadata(path, metadata))
+        }
+    }
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Self::Dir(_))
+    }
+    pub fn is_symlink(&self) -> bool {
+        matches!(self, Self::Symlink(_))
+    }
+    /// 上次扫描时读不了
+    pub fn is_inaccessible(&self) -> bool {
+        match self {
+            Self::File(file) => file.inaccessible,
+            Self::Dir(dir) => dir.inaccessible,
+            Self::Symlink(_) => false,
+        }
+    }
+    /// 文件夹按扩展名的汇总
+    pub fn types(&self) -> Option<&TypeStats> {
+        match self {
+            Self::Dir(dir) => Some(&dir.types),
+            _ => None,
         }
     }
-    pub(crate) fn is_dir(&self) -> bool {
+    /// 找重复文件时缓存的内容hash
+    pub fn content_hash(&self) -> Option<&FileHash> {
         match self {
-            Self::File(_) => false,
-            Self::Dir(_) => true,
+            Self::File(file) => file.hash.as_ref(),
+            _ => None,
+        }
+    }
+    pub(crate) fn set_content_hash(&mut self, hash: Option<FileHash>) {
+        if let Self::File(file) = self {
+            file.hash = hash;
         }
     }
+    /// 文件的内容摘要，见digest.rs
+    pub fn digest(&self) -> Option<&Digest> {
+        match self {
+            Self::File(file) => file.digest.as_ref(),
+            _ => None,
+        }
+    }
+    pub(crate) fn set_digest(&mut self, digest: Option<Digest>) {
+        if let Self::File(file) = self {
+            file.digest = digest;
+        }
+    }
+    /// 符号链接指向哪
+    pub fn target(&self) -> Option<&PathBuf> {
+        match self {
+            Self::Symlink(link) => Some(&link.target),
+            _ => None,
+        }
+    }
+    /// 文件、符号链接重新stat一次，文件夹不动；stat失败的文件标成inaccessible
+    pub(crate) fn update(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.update(),
+            Self::Symlink(link) => {
+                link.update();
+                Ok(())
+            }
+            Self::Dir(_) => Ok(()),
+        }
+    }
+    /// 文件夹读得了读不了，merge_scan用
+    pub(crate) fn set_inaccessible(&mut self, inaccessible: bool) {
+        match self {
+            Self::File(file) => file.inaccessible = inaccessible,
+            Self::Dir(dir) => dir.inaccessible = inaccessible,
+            Self::Symlink(_) => {}
+        }
+    }
+    /// 有别的硬链接的文件：(dev, ino)，硬链接数变了也算改动
+    pub(crate) fn link(&self) -> Option<(u64, u64)> {
+        match self {
+            Self::File(file) if file.nlink > 1 => Some((file.dev, file.ino)),
+            _ => None,
+        }
+    }
+    /// 读不了的节点总是过期，每次扫描都重试一遍
     pub(crate) fn is_valid(&self) -> bool {
+        if self.is_inaccessible() {
+            return false;
+        }
         match self {
             Self::File(file) => {
-                if fs::metadata(&file.abspath).is_err() {
+                let Ok(metadata) = fs::metadata(&file.abspath) else {
                     return false; // node not exists
-                }
-                get_last_modified(&file.abspath) == file.last_write_time
-                    && file.size == fs::metadata(&file.abspath).unwrap().len()
+                };
+                metadata_last_modified(&metadata) == file.last_write_time
+                    && file.size == metadata.len()
+                    && metadata_inode(&metadata).2 == file.nlink
             }
             Self::Dir(dir) => {
                 if fs::metadata(&dir.abspath).is_err() {
@@ -131,6 +309,14 @@ impl JNode {
                 }
                 !dir._dirty && get_last_modified(&dir.abspath) == dir.last_write_time
             }
+            Self::Symlink(link) => {
+                let Ok(metadata) = fs::symlink_metadata(&link.abspath) else {
+                    return false;
+                };
+                metadata.file_type().is_symlink()
+                    && metadata_last_modified(&metadata) == link.last_write_time
+                    && fs::read_link(&link.abspath).is_ok_and(|t| t == link.target)
+            }
         }
     }
 
@@ -152,6 +338,7 @@ impl JNode {
                     file.last_write_time = last_write_time;
                 }
             }
+            Self::Symlink(_) => {}
             Self::Dir(dir) => {
                 if let Some(size) = size {
                     dir.size = size;
@@ -175,6 +362,20 @@ impl JNode {
         }
     }
 
+    /// 文件夹汇总出来的扩展名，文件不用这个
+    pub(crate) fn set_types(&mut self, types: TypeStats) {
+        if let Self::Dir(dir) = self {
+            dir.types = types;
+        }
+    }
+
+    /// 文件夹汇总出来的磁盘占用，文件不用这个
+    pub(crate) fn set_disk(&mut self, disk: u64) {
+        if let Self::Dir(dir) = self {
+            dir.disk = disk;
+        }
+    }
+
     /// for Dumper
     pub(crate) fn load(&mut self, dumped: &JNode) {
         // TODO: advanced check for last dump date
@@ -182,17 +383,27 @@ impl JNode {
             (JNode::File(me), JNode::File(dumped)) => {
                 me.last_write_time = dumped.last_write_time;
                 me.size = dumped.size;
+                me.disk = dumped.disk;
+                me.inaccessible = dumped.inaccessible;
             }
             (JNode::Dir(me), JNode::Dir(dumped)) => {
                 // me._scaned = dumped._scaned;
                 me.last_write_time = dumped.last_write_time;
                 me.size = dumped.size;
+                me.disk = dumped.disk;
                 me.count_dir = dumped.count_dir;
                 me.count_file = dumped.count_file;
                 me._dirty = dumped._dirty;
+                me.inaccessible = dumped.inaccessible;
+                me.types = dumped.types.clone();
                 // me._scaned = dumped._scaned;
             }
-            _ => panic!("Node type mismatch"),
+            (JNode::Symlink(me), JNode::Symlink(dumped)) => {
+                me.last_write_time = dumped.last_write_time;
+                me.target = dumped.target.clone();
+            }
+            // 类型对不上（比如缓存里是符号链接、这边跟过去了），整个换成缓存里的，之后is_valid再核对
+            (me, dumped) => *me = dumped.clone(),
         }
     }
 }
@@ -200,7 +411,9 @@ impl JNode {
 impl From<DumpData> for JNode {
     fn from(value: DumpData) -> Self {
         let pathbuf = PathBuf::from(&value.abspath);
-        if pathbuf.is_dir() {
+        if value.target.is_some() {
+            Self::Symlink(SymlinkNode::from(value))
+        } else if pathbuf.is_dir() {
             let dir = DirNode::from(value);
             Self::Dir(dir)
         } else {
@@ -210,11 +423,51 @@ impl From<DumpData> for JNode {
     }
 }
 
+impl JNode {
+    /// LoadMode::Trust用，不stat也不canonicalize，
+    /// 只有旧缓存缺is_dir列时才stat一次
+    pub(crate) fn from_dump_trusted(value: DumpData) -> Self {
+        let abspath = PathBuf::from(&value.abspath);
+        if value.target.is_some() {
+            return Self::Symlink(SymlinkNode::from(value));
+        }
+        let is_dir = value.is_dir.unwrap_or_else(|| abspath.is_dir());
+        if is_dir {
+            Self::Dir(DirNode {
+                abspath,
+                last_write_time: value.last_write_time,
+                size: value.size,
+                disk: value.disk.unwrap_or(value.size),
+                count_dir: value.count_dir,
+                count_file: value.count_file,
+                dev: value.dev,
+                _dirty: value._dirty,
+                inaccessible: value.inaccessible,
+                types: value.types,
+            })
+        } else {
+            Self::File(FileNode {
+                abspath,
+                last_write_time: value.last_write_time,
+                size: value.size,
+                disk: value.disk.unwrap_or(value.size),
+                dev: value.dev,
+                ino: value.ino,
+                nlink: value.nlink,
+                inaccessible: value.inaccessible,
+                hash: None,
+                digest: value.digest,
+            })
+        }
+    }
+}
+
 impl Into<DumpData> for JNode {
     fn into(self) -> DumpData {
         match self {
             Self::File(file) => file.into(),
             Self::Dir(dir) => dir.into(),
+            Self::Symlink(link) => link.into(),
         }
     }
 }
@@ -224,15 +477,23 @@ impl Hash for JNode {
         match self {
             Self::File(file) => file.hash(state),
             Self::Dir(dir) => dir.hash(state),
+            Self::Symlink(link) => link.abspath.hash(state),
         }
     }
 }
 
+impl AsRef<Path> for JNode {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
 impl std::fmt::Display for JNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::File(file) => write!(f, "{}", file),
             Self::Dir(dir) => write!(f, "{}", dir),
+            Self::Symlink(link) => write!(f, "{}", link),
         }
     }
 }
@@ -257,6 +518,13 @@ impl From<DumpData> for FileNode {
             abspath: pathbuf.canonicalize().unwrap(),
             last_write_time: value.last_write_time,
             size: value.size,
+            disk: value.disk.unwrap_or(value.size),
+            dev: value.dev,
+            ino: value.ino,
+            nlink: value.nlink,
+            inaccessible: value.inaccessible,
+            hash: None,
+            digest: value.digest,
         }
     }
 }
@@ -271,6 +539,15 @@ impl Into<DumpData> for FileNode {
             count_file: 0,
             // _scaned: true,
             _dirty: false,
+            is_dir: Some(false),
+            dev: self.dev,
+            ino: self.ino,
+            nlink: self.nlink,
+            disk: Some(self.disk),
+            target: None,
+            inaccessible: self.inaccessible,
+            types: TypeStats::default(),
+            digest: self.digest,
         }
     }
 }
@@ -278,31 +555,59 @@ impl Into<DumpData> for FileNode {
 impl std::fmt::Display for FileNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let content = format!(
-            "****[FileNode]****\nname: {:?}\nabspath: {:?}\nmodify: {:?}\nsize: {:?}",
+            "****[FileNode]****{}\nname: {:?}\nabspath: {:?}\nmodify: {:?}\nsize: {:?}\ndisk: {:?}{}",
+            if self.inaccessible { " [inaccessible]" } else { "" },
             self.abspath.file_name().unwrap(),
             self.abspath,
             pretty_last_modified(self.last_write_time),
             pretty_size(self.size),
+            pretty_size(self.disk),
+            if self.nlink > 1 { format!("\nlinks: {}", self.nlink) } else { String::new() },
         );
         write!(f, "{}", content)
     }
 }
 
 impl FileNode {
-    fn new(abspath: &PathBuf) -> Self {
-        let metadata = fs::metadata(&abspath).unwrap();
-        let last_write_time = get_last_modified(&abspath);
-        let size = metadata.len();
+    fn new(abspath: &PathBuf) -> io::Result<Self> {
+        let metadata = fs::metadata(abspath)?;
+        Ok(Self::with_metadata(abspath.to_path_buf(), &metadata))
+    }
+    fn with_metadata(abspath: PathBuf, metadata: &fs::Metadata) -> Self {
+        let (dev, ino, nlink) = metadata_inode(metadata);
         Self {
-            abspath: abspath.to_path_buf(),
-            last_write_time,
-            size,
+            abspath,
+            last_write_time: metadata_last_modified(metadata),
+            size: metadata.len(),
+            disk: metadata_disk(metadata),
+            dev,
+            ino,
+            nlink,
+            inaccessible: false,
+            hash: None,
+            digest: None,
         }
     }
-    pub fn update(&mut self) {
-        let metadata = fs::metadata(&self.abspath).unwrap();
-        self.last_write_time = get_last_modified(&self.abspath);
+    /// size、mtime变了的话缓存的hash和摘要也扔掉
+    pub fn update(&mut self) -> io::Result<()> {
+        let metadata = match fs::metadata(&self.abspath) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.inaccessible = true;
+                return Err(e);
+            }
+        };
+        let changed = (metadata_last_modified(&metadata), metadata.len()) != (self.last_write_time, self.size);
+        if changed {
+            self.hash = None;
+            self.digest = None;
+        }
+        self.last_write_time = metadata_last_modified(&metadata);
         self.size = metadata.len();
+        self.disk = metadata_disk(&metadata);
+        (self.dev, self.ino, self.nlink) = metadata_inode(&metadata);
+        self.inaccessible = false;
+        Ok(())
     }
 }
 
@@ -320,10 +625,14 @@ impl From<DumpData> for DirNode {
             abspath: path.canonicalize().unwrap(),
             last_write_time: data.last_write_time,
             size: data.size,
+            disk: data.disk.unwrap_or(data.size),
             count_dir: data.count_dir,
             count_file: data.count_file,
+            dev: data.dev,
             // _scaned: data._scaned,
             _dirty: data._dirty,
+            inaccessible: data.inaccessible,
+            types: data.types,
         }
     }
 }
@@ -338,6 +647,12 @@ impl Into<DumpData> for DirNode {
             count_file: self.count_file,
             // _scaned: self._scaned,
             _dirty: self._dirty,
+            is_dir: Some(true),
+            disk: Some(self.disk),
+            dev: self.dev,
+            inaccessible: self.inaccessible,
+            types: self.types,
+            ..Default::default()
         }
     }
 }
@@ -351,12 +666,14 @@ impl Hash for DirNode {
 impl std::fmt::Display for DirNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let content = format!(
-            "****[DirNode]****{}\nname: {:?}\npath: {:?}\nmodify: {:?}\nsize: {:?}\nfolders: {:?}\nfiles: {:?}",
+            "****[DirNode]****{}{}\nname: {:?}\npath: {:?}\nmodify: {:?}\nsize: {:?}\ndisk: {:?}\nfolders: {:?}\nfiles: {:?}",
             if self._dirty { " [dirty]" } else { "" },
+            if self.inaccessible { " [inaccessible]" } else { "" },
             self.abspath.file_name().unwrap(),
             self.abspath,
             pretty_last_modified(self.last_write_time),
             pretty_size(self.size),
+            pretty_size(self.disk),
             self.count_dir,
             self.count_file
         );
@@ -366,21 +683,96 @@ impl std::fmt::Display for DirNode {
 
 /// Implement NodeAction trait for DirNode
 impl DirNode {
-    fn new(abspath: &PathBuf) -> Self {
-        let metadata = fs::metadata(&abspath).unwrap();
-        let last_write_time = get_last_modified(&abspath);
+    fn new(abspath: &PathBuf) -> io::Result<Self> {
+        let metadata = fs::metadata(abspath)?;
+        Ok(Self::with_metadata(abspath.to_path_buf(), &metadata))
+    }
+    fn with_metadata(abspath: PathBuf, metadata: &fs::Metadata) -> Self {
+        let last_write_time = metadata_last_modified(metadata);
         let size = metadata.len();
+        let disk = metadata_disk(metadata);
         let (count_dir, count_file) = (0, 0);
         Self {
-            abspath: abspath.to_path_buf(),
+            abspath,
             last_write_time,
             size,
+            disk,
             count_dir,
             count_file,
+            dev: metadata_dev(metadata),
             // _scaned: false,
             _dirty: true,
+            inaccessible: false,
+            types: TypeStats::default(),
+        }
+    }
+}
+
+/// -----------------------------------------------------------------------------------------------
+/// -----------------------------------------------------------------------------------------------
+/// -----------------------------------------------------------------------------------------------
+
+/// 路径不canonicalize，canonicalize会解析到目标上
+impl From<DumpData> for SymlinkNode {
+    fn from(data: DumpData) -> Self {
+        Self {
+            abspath: PathBuf::from(data.abspath),
+            target: PathBuf::from(data.target.unwrap_or_default()),
+            last_write_time: data.last_write_time,
+            size: data.size,
+            disk: data.disk.unwrap_or(data.size),
+        }
+    }
+}
+
+impl Into<DumpData> for SymlinkNode {
+    fn into(self) -> DumpData {
+        DumpData {
+            abspath: self.abspath.to_str().unwrap().to_string(),
+            last_write_time: self.last_write_time,
+            size: self.size,
+            is_dir: Some(false),
+            disk: Some(self.disk),
+            target: Some(self.target.to_str().unwrap().to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl std::fmt::Display for SymlinkNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let content = format!(
+            "****[Symlink]****\nname: {:?}\nabspath: {:?}\ntarget: {:?}\nmodify: {:?}",
+            self.abspath.file_name().unwrap(),
+            self.abspath,
+            self.target,
+            pretty_last_modified(self.last_write_time),
+        );
+        write!(f, "{}", content)
+    }
+}
+
+impl SymlinkNode {
+    /// metadata是lstat拿到的
+    fn with_metadata(abspath: PathBuf, metadata: &fs::Metadata) -> Self {
+        let target = fs::read_link(&abspath).unwrap_or_default();
+        Self {
+            abspath,
+            target,
+            last_write_time: metadata_last_modified(metadata),
+            size: metadata.len(),
+            disk: metadata_disk(metadata),
         }
     }
+    pub fn update(&mut self) {
+        let Ok(metadata) = fs::symlink_metadata(&self.abspath) else {
+            return;
+        };
+        self.last_write_time = metadata_last_modified(&metadata);
+        self.size = metadata.len();
+        self.disk = metadata_disk(&metadata);
+        self.target = fs::read_link(&self.abspath).unwrap_or_default();
+    }
 }
 
 /// -----------------------------------------------------------------------------------------------
@@ -398,16 +790,68 @@ impl Ord for DumpData {
 /// -----------------------------------------------------------------------------------------------
 
 #[inline]
+/// stat不了的是0
 pub fn get_last_modified(abspath: &PathBuf) -> u128 {
-    fs::metadata(abspath)
-        .unwrap()
+    fs::metadata(abspath).map(|m| metadata_last_modified(&m)).unwrap_or(0)
+}
+
+/// 拿不到修改时间、或者早于1970的是0
+#[inline]
+pub fn metadata_last_modified(metadata: &fs::Metadata) -> u128 {
+    metadata
         .modified()
-        .unwrap()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis()
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
 }
 
+/// (dev, ino, nlink)，只有一个链接的不记inode，返回(0, 0, 1)
+#[cfg(unix)]
+#[inline]
+pub fn metadata_inode(metadata: &fs::Metadata) -> (u64, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    match metadata.nlink() {
+        n if n > 1 => (metadata.dev(), metadata.ino(), n),
+        _ => (0, 0, 1),
+    }
+}
+
+/// 设备号，拿不到的平台上是0
+#[cfg(unix)]
+#[inline]
+pub fn metadata_dev(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.dev()
+}
+
+#[cfg(not(unix))]
+#[inline]
+pub fn metadata_dev(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// 分配的块数 * 512，和du一样
+#[cfg(unix)]
+#[inline]
+pub fn metadata_disk(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+/// 拿不到块数，当和表观大小一样
+#[cfg(not(unix))]
+#[inline]
+pub fn metadata_disk(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// 拿不到inode，当每个文件都只有一个链接
+#[cfg(not(unix))]
+#[inline]
+pub fn metadata_inode(_metadata: &fs::Metadata) -> (u64, u64, u64) {
+    (0, 0, 1)
+}
 
 #[inline]
 pub fn last_modify_systemtime(modify_time: u128) -> SystemTime {
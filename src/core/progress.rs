@@ -0,0 +1,132 @@
+//! 扫描进度和取消。
+//!
+//! 进度：scan_folder每合并完一个文件夹就回调一次观察者，带上累计的文件夹数、文件数、字节数和刚读完的路径；
+//! update_dirty重读时只更新路径，不重复计数。回调在调用update_node的线程上跑。
+//!
+//! 取消：CancelToken可以clone到别的线程（比如Ctrl-C的handler）里，cancel之后扫描在下一个文件夹前停下，
+//! update_node返回JError::Cancelled。停下时没读完的文件夹和它们的祖先都是脏的，
+//! 索引照样能dump，下次update_node接着算。
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::manager::JManager;
+use super::node::JNode;
+
+/// 这次update_node到目前为止的进度
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// 读过的文件夹
+    pub dirs: u64,
+    /// 见到的文件（包括符号链接、被过滤的文件夹里的）
+    pub files: u64,
+    /// 见到的文件的表观大小
+    pub bytes: u64,
+    /// 刚读完的文件夹
+    pub current: PathBuf,
+}
+
+#[derive(Debug, Default)]
+struct Flags {
+    cancelled: AtomicBool,
+    active: AtomicBool,
+}
+
+/// 取消扫描用，clone出来的都是同一个
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<Flags>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 正在跑的update_node会尽快停下；没在扫描时什么都不做
+    pub fn cancel(&self) {
+        if self.is_active() {
+            self.0.cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 有没有update_node在跑
+    pub fn is_active(&self) -> bool {
+        self.0.active.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn start(&self) {
+        self.0.cancelled.store(false, Ordering::SeqCst);
+        self.0.active.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.0.active.store(false, Ordering::SeqCst);
+        self.0.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+/// 要Sync，manager才能放进python的类里
+type Callback = Box<dyn FnMut(&Progress) + Send + Sync>;
+
+/// 观察者加上累计的进度；回调不能Debug，单独包一层
+#[derive(Default)]
+pub(crate) struct Observer {
+    callback: Option<Callback>,
+    progress: Progress,
+}
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observer")
+            .field("callback", &self.callback.is_some())
+            .field("progress", &self.progress)
+            .finish()
+    }
+}
+
+impl Observer {
+    pub(crate) fn reset(&mut self) {
+        self.progress = Progress::default();
+    }
+
+    /// 读完了一个文件夹；files、bytes是它直接含有的
+    pub(crate) fn visit(&mut self, dir: &Path, dirs: u64, files: u64, bytes: u64) {
+        self.progress.dirs += dirs;
+        self.progress.files += files;
+        self.progress.bytes += bytes;
+        self.touch(dir);
+    }
+
+    /// 只换当前路径
+    pub(crate) fn touch(&mut self, dir: &Path) {
+        self.progress.current = dir.to_path_buf();
+        if let Some(callback) = self.callback.as_mut() {
+            callback(&self.progress);
+        }
+    }
+}
+
+impl JManager<u64, JNode> {
+    /// 扫描时每读完一个文件夹调一次f，换掉之前的
+    pub fn set_observer(&mut self, f: impl FnMut(&Progress) + Send + Sync + 'static) {
+        self.observer.callback = Some(Box::new(f));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer.callback = None;
+    }
+
+    /// 最近一次update_node的进度
+    pub fn progress(&self) -> &Progress {
+        &self.observer.progress
+    }
+
+    /// 和manager共用的token，拿去别的线程cancel
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+}
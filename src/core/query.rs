@@ -0,0 +1,473 @@
+//! 查询语言：在一棵子树里按条件挑节点。
+//!
+//! ```text
+//! size > 1GiB and ext in (mp4, mkv) and modified < 90d under /data sort by size desc limit 20
+//! ```
+//! - 条件用`and`、`or`、`not`和括号组合，`and`比`or`先结合；条件可以不写，就是全部
+//! - 字段：`name`、`path`、`ext`、`kind`（file/dir/symlink）、`size`、`disk`、
+//!   `count_dir`、`count_file`、`modified`（别名`age`）
+//! - 比较：`= != < <= > >=`，文字字段只有`= != ~`（glob，和规则里的一样），都可以`in (a, b)`/`not in (a, b)`
+//! - 大小带单位B/K/M/G/T（KiB、MB都当1024进）；`modified`的值是时长s/m/h/d/w/y，
+//!   比的是距上次修改多久：`modified < 90d`是90天之内改过的
+//! - 文字不分大小写，有空格或者符号的用引号括起来
+//! - `under <path>`换查询的根，相对路径接在调用时给的节点后面；`sort by <field> [asc|desc]`默认asc，
+//!   不写时按路径排；`limit N`只要前N个
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use regex::Regex;
+
+use super::action::JNodeAction;
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::JNode;
+use super::rules::{glob_to_regex, parse_age, parse_size};
+use super::types::ext_of;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    Name,
+    Path,
+    Ext,
+    Kind,
+    Size,
+    Disk,
+    CountDir,
+    CountFile,
+    /// 比较时是距上次修改的毫秒数，排序时按修改时间
+    Modified,
+}
+
+impl QueryField {
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_lowercase().as_str() {
+            "name" => Some(Self::Name),
+            "path" => Some(Self::Path),
+            "ext" => Some(Self::Ext),
+            "kind" | "type" => Some(Self::Kind),
+            "size" => Some(Self::Size),
+            "disk" => Some(Self::Disk),
+            "count_dir" | "dirs" => Some(Self::CountDir),
+            "count_file" | "files" => Some(Self::CountFile),
+            "modified" | "last_modified" | "mtime" | "age" => Some(Self::Modified),
+            _ => None,
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, Self::Name | Self::Path | Self::Ext | Self::Kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn test(&self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ne => ord != Ordering::Equal,
+            Self::Lt => ord == Ordering::Less,
+            Self::Le => ord != Ordering::Greater,
+            Self::Gt => ord == Ordering::Greater,
+            Self::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+/// 文字已经转成小写，数字已经换算好
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Text(String),
+    Num(u128),
+}
+
+#[derive(Debug, Clone)]
+pub enum QueryExpr {
+    /// 没写条件
+    All,
+    And(Box<QueryExpr>, Box<QueryExpr>),
+    Or(Box<QueryExpr>, Box<QueryExpr>),
+    Not(Box<QueryExpr>),
+    Cmp(QueryField, CmpOp, Literal),
+    In(QueryField, Vec<Literal>),
+    /// 文字字段的glob
+    Glob(QueryField, Regex),
+}
+
+/// 解析好的一条查询
+#[derive(Debug, Clone)]
+pub struct Query {
+    src: String,
+    pub expr: QueryExpr,
+    pub under: Option<PathBuf>,
+    /// (字段, 是否从大到小)
+    pub sort: Option<(QueryField, bool)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// 引号括起来的，不当关键字
+    Quoted(String),
+    Op(CmpOp),
+    Tilde,
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            ',' => tokens.push(Token::Comma),
+            '~' => tokens.push(Token::Tilde),
+            '<' | '>' | '=' | '!' => {
+                let op = match (c, next) {
+                    ('<', Some('=')) => CmpOp::Le,
+                    ('>', Some('=')) => CmpOp::Ge,
+                    ('!', Some('=')) => CmpOp::Ne,
+                    ('=', Some('=')) => CmpOp::Eq,
+                    ('<', _) => CmpOp::Lt,
+                    ('>', _) => CmpOp::Gt,
+                    ('=', _) => CmpOp::Eq,
+                    _ => return Err("expect != after !".into()),
+                };
+                if next == Some('=') {
+                    i += 1;
+                }
+                tokens.push(Token::Op(op));
+            }
+            '"' | '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&q| q == c)
+                    .ok_or_else(|| format!("unclosed {c}"))?;
+                tokens.push(Token::Quoted(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 1;
+            }
+            _ => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| c.is_whitespace() || "()<>=!,~\"'".contains(*c))
+                    .unwrap_or(chars.len() - i);
+                tokens.push(Token::Word(chars[i..i + end].iter().collect()));
+                i += end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// 下一个是不是这个关键字（不分大小写，引号括起来的不算）
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let yes = self.is_keyword(keyword);
+        if yes {
+            self.pos += 1;
+        }
+        yes
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            other => Err(format!("expect {token:?}, found {other:?}")),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(w) | Token::Quoted(w)) => Ok(w),
+            other => Err(format!("expect {what}, found {other:?}")),
+        }
+    }
+
+    fn field(&mut self) -> Result<QueryField, String> {
+        let word = self.word("a field")?;
+        QueryField::parse(&word).ok_or_else(|| format!("unknown field: {word}"))
+    }
+
+    /// 条件在子句关键字或者结尾前停下
+    fn at_clause(&self) -> bool {
+        self.peek().is_none() || ["under", "sort", "limit"].iter().any(|k| self.is_keyword(k))
+    }
+
+    fn or(&mut self) -> Result<QueryExpr, String> {
+        let mut left = self.and()?;
+        while self.eat_keyword("or") {
+            left = QueryExpr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<QueryExpr, String> {
+        let mut left = self.not()?;
+        while self.eat_keyword("and") {
+            left = QueryExpr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<QueryExpr, String> {
+        if self.eat_keyword("not") {
+            return Ok(QueryExpr::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.or()?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+        self.cmp()
+    }
+
+    fn cmp(&mut self) -> Result<QueryExpr, String> {
+        let field = self.field()?;
+        let negate = self.eat_keyword("not");
+        if self.eat_keyword("in") {
+            self.expect(Token::LParen)?;
+            let mut values = vec![];
+            loop {
+                values.push(value(field, &self.word("a value")?)?);
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    other => return Err(format!("expect , or ), found {other:?}")),
+                }
+            }
+            let expr = QueryExpr::In(field, values);
+            return Ok(if negate { QueryExpr::Not(Box::new(expr)) } else { expr });
+        }
+        if negate {
+            return Err("expect in after not".into());
+        }
+        match self.next() {
+            Some(Token::Tilde) if field.is_text() => {
+                let glob = self.word("a glob")?;
+                let re = Regex::new(&format!("(?i){}", glob_to_regex(&glob))).map_err(|e| e.to_string())?;
+                Ok(QueryExpr::Glob(field, re))
+            }
+            Some(Token::Tilde) => Err(format!("~ only works on text fields, not {field:?}")),
+            Some(Token::Op(op)) => {
+                if field.is_text() && !matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    return Err(format!("{field:?} only supports =, != and ~"));
+                }
+                Ok(QueryExpr::Cmp(field, op, value(field, &self.word("a value")?)?))
+            }
+            other => Err(format!("expect an operator after {field:?}, found {other:?}")),
+        }
+    }
+}
+
+/// 按字段把值换算好
+fn value(field: QueryField, word: &str) -> Result<Literal, String> {
+    let bad = || format!("bad value for {field:?}: {word}");
+    match field {
+        QueryField::Name | QueryField::Path => Ok(Literal::Text(word.to_lowercase())),
+        QueryField::Ext => Ok(Literal::Text(word.trim_start_matches('.').to_lowercase())),
+        QueryField::Kind => match word.to_ascii_lowercase().as_str() {
+            k @ ("file" | "dir" | "symlink") => Ok(Literal::Text(k.to_string())),
+            _ => Err(bad()),
+        },
+        QueryField::Size | QueryField::Disk => parse_size(word).map(|n| Literal::Num(n as u128)).ok_or_else(bad),
+        QueryField::CountDir | QueryField::CountFile => word.parse::<u128>().map(Literal::Num).map_err(|_| bad()),
+        QueryField::Modified => parse_age(word).map(|d| Literal::Num(d.as_millis())).ok_or_else(bad),
+    }
+}
+
+impl Query {
+    pub fn parse(src: &str) -> Result<Self, JError> {
+        let invalid = |why: String| JError::InvalidQuery(src.to_string(), why);
+        let mut parser = Parser {
+            tokens: tokenize(src).map_err(invalid)?,
+            pos: 0,
+        };
+        let expr = if parser.at_clause() { QueryExpr::All } else { parser.or().map_err(invalid)? };
+        let mut query = Self {
+            src: src.to_string(),
+            expr,
+            under: None,
+            sort: None,
+            limit: None,
+        };
+        while parser.peek().is_some() {
+            if parser.eat_keyword("under") {
+                query.under = Some(PathBuf::from(parser.word("a path").map_err(invalid)?));
+            } else if parser.eat_keyword("sort") {
+                if !parser.eat_keyword("by") {
+                    return Err(invalid("expect by after sort".into()));
+                }
+                let field = parser.field().map_err(invalid)?;
+                let desc = parser.eat_keyword("desc");
+                if !desc {
+                    parser.eat_keyword("asc");
+                }
+                query.sort = Some((field, desc));
+            } else if parser.eat_keyword("limit") {
+                let n = parser.word("a number").map_err(invalid)?;
+                query.limit = Some(n.parse().map_err(|_| invalid(format!("bad limit: {n}")))?);
+            } else {
+                return Err(invalid(format!("unexpected {:?}", parser.peek().unwrap())));
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+
+    /// now是毫秒时间戳，算modified用
+    pub fn matches(&self, node: &JNode, now: u128) -> bool {
+        eval(&self.expr, node, now)
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = JError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn kind_of(node: &JNode) -> &'static str {
+    match node {
+        JNode::Dir(_) => "dir",
+        JNode::Symlink(_) => "symlink",
+        JNode::File(_) => "file",
+    }
+}
+
+/// 比较用的值；文字转小写，modified是距今多久
+fn field_value(field: QueryField, node: &JNode, now: u128) -> Literal {
+    match field {
+        QueryField::Name => Literal::Text(node.name().to_lowercase()),
+        QueryField::Path => Literal::Text(node.path().to_string_lossy().to_lowercase()),
+        QueryField::Ext => Literal::Text(ext_of(node.path())),
+        QueryField::Kind => Literal::Text(kind_of(node).to_string()),
+        QueryField::Size => Literal::Num(node.size() as u128),
+        QueryField::Disk => Literal::Num(node.disk_size() as u128),
+        QueryField::CountDir => Literal::Num(node.count_dir().unwrap_or(0) as u128),
+        QueryField::CountFile => Literal::Num(node.count_file().unwrap_or(0) as u128),
+        QueryField::Modified => Literal::Num(now.saturating_sub(node.last_modified())),
+    }
+}
+
+fn cmp_value(a: &Literal, b: &Literal) -> Ordering {
+    match (a, b) {
+        (Literal::Num(a), Literal::Num(b)) => a.cmp(b),
+        (Literal::Text(a), Literal::Text(b)) => a.cmp(b),
+        (Literal::Num(_), Literal::Text(_)) => Ordering::Less,
+        (Literal::Text(_), Literal::Num(_)) => Ordering::Greater,
+    }
+}
+
+fn eval(expr: &QueryExpr, node: &JNode, now: u128) -> bool {
+    match expr {
+        QueryExpr::All => true,
+        QueryExpr::And(a, b) => eval(a, node, now) && eval(b, node, now),
+        QueryExpr::Or(a, b) => eval(a, node, now) || eval(b, node, now),
+        QueryExpr::Not(a) => !eval(a, node, now),
+        QueryExpr::Cmp(field, op, v) => op.test(cmp_value(&field_value(*field, node, now), v)),
+        QueryExpr::In(field, values) => {
+            let actual = field_value(*field, node, now);
+            values.contains(&actual)
+        }
+        QueryExpr::Glob(field, re) => match field_value(*field, node, now) {
+            Literal::Text(text) => re.is_match(&text),
+            Literal::Num(_) => false,
+        },
+    }
+}
+
+/// 排序用的值；modified按修改时间，新的大
+fn sort_value(field: QueryField, node: &JNode) -> Literal {
+    match field {
+        QueryField::Modified => Literal::Num(node.last_modified()),
+        _ => field_value(field, node, 0),
+    }
+}
+
+impl JManager<u64, JNode> {
+    /// h下面（不含h自己）满足q的节点；q带under时换成那个路径，没展开的块先展开
+    pub fn query(&mut self, h: &u64, q: &Query) -> Result<Vec<(u64, JNode)>, Box<dyn std::error::Error>> {
+        let Some(base) = self.nodes.path(h) else {
+            return Err(JError::NotExistingNode(line!(), *h).into());
+        };
+        let root = match &q.under {
+            Some(under) => {
+                let path = base.join(under);
+                self.find_node(&path)?.ok_or(JError::NotExistingPath(path))?
+            }
+            None => *h,
+        };
+        self.materialize(&root)?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let mut result = vec![];
+        let mut stack = self.nodes.children(&root);
+        while let Some(ch) = stack.pop() {
+            let Some(node) = self.nodes.get(&ch) else {
+                continue;
+            };
+            stack.extend(self.nodes.children(&ch));
+            if q.matches(&node, now) {
+                result.push((ch, node));
+            }
+        }
+        match q.sort {
+            Some((field, desc)) => result.sort_by(|(_, a), (_, b)| {
+                let ord = cmp_value(&sort_value(field, a), &sort_value(field, b));
+                if desc { ord.reverse() } else { ord }.then_with(|| a.path().cmp(b.path()))
+            }),
+            None => result.sort_by(|(_, a), (_, b)| a.path().cmp(b.path())),
+        }
+        if let Some(limit) = q.limit {
+            result.truncate(limit);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,364 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+
+use super::action::JNodeAction;
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::{metadata_last_modified, JNode};
+
+/// 没有配置时的默认规则，和以前写死的IGNORE_DIR一样
+pub const DEFAULT_RULES: [&str; 2] = ["node_modules/", ".git/"];
+/// 每个文件夹下都可以放一个，规则只管这个文件夹下面
+pub const IGNORE_FILE: &str = ".jobsignore";
+
+/// 一条规则，写法：
+/// - `*.log`、`build/`、`/target`、`docs/**/*.tmp` gitignore风格的glob，`/`结尾只匹配文件夹；
+///   带`/`的相对规则所在的文件夹匹配，全局规则相对扫描的根
+/// - `re:<regex>` 正则，匹配整个路径（分隔符统一成`/`）
+/// - `size>100M`、`size<1K` 文件大小，单位B/K/M/G/T，只对文件生效
+/// - `age>90d`、`age<1h` 距上次修改的时间，单位s/m/h/d/w/y
+/// - 任何规则前加`!`表示排除，多条规则命中时后面的说了算
+#[derive(Debug, Clone)]
+pub struct Rule {
+    src: String,
+    negate: bool,
+    dir_only: bool,
+    kind: RuleKind,
+}
+
+#[derive(Debug, Clone)]
+enum RuleKind {
+    /// 不带`/`的glob只匹配名字
+    Glob { re: Regex, anchored: bool },
+    Regex(Regex),
+    Size(Ordering, u64),
+    /// 毫秒
+    Age(Ordering, u128),
+}
+
+impl Rule {
+    pub fn parse(src: &str) -> Result<Self, JError> {
+        let src = src.trim();
+        let invalid = |why: &str| JError::InvalidRule(src.to_string(), why.to_string());
+        let (negate, body) = match src.strip_prefix('!') {
+            Some(body) => (true, body),
+            None => (false, src),
+        };
+        if body.is_empty() {
+            return Err(invalid("empty rule"));
+        }
+        let mut dir_only = false;
+        let kind = if let Some(re) = body.strip_prefix("re:") {
+            RuleKind::Regex(Regex::new(re).map_err(|e| invalid(&e.to_string()))?)
+        } else if let Some(cond) = body.strip_prefix("size").filter(|c| is_cmp(c)) {
+            let (ord, num) = parse_cmp(cond).ok_or_else(|| invalid("expect size>N or size<N"))?;
+            RuleKind::Size(ord, parse_size(num).ok_or_else(|| invalid("bad size"))?)
+        } else if let Some(cond) = body.strip_prefix("age").filter(|c| is_cmp(c)) {
+            let (ord, num) = parse_cmp(cond).ok_or_else(|| invalid("expect age>N or age<N"))?;
+            RuleKind::Age(ord, parse_age(num).ok_or_else(|| invalid("bad age"))?.as_millis())
+        } else {
+            let mut glob = body;
+            if let Some(g) = glob.strip_suffix('/') {
+                dir_only = true;
+                glob = g;
+            }
+            let anchored = glob.contains('/');
+            let glob = glob.trim_start_matches('/');
+            let re = glob_to_regex(glob);
+            RuleKind::Glob {
+                re: Regex::new(&re).map_err(|e| invalid(&e.to_string()))?,
+                anchored,
+            }
+        };
+        Ok(Self {
+            src: src.to_string(),
+            negate,
+            dir_only,
+            kind,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+
+    /// rel是相对规则所在文件夹的路径，全局规则是相对扫描的根的
+    fn matches(&self, path: &Path, rel: &str, metadata: &Metadata) -> bool {
+        if self.dir_only && !metadata.is_dir() {
+            return false;
+        }
+        match &self.kind {
+            RuleKind::Glob { re, anchored } => {
+                if *anchored {
+                    re.is_match(rel)
+                } else {
+                    path.file_name()
+                        .map(|name| re.is_match(&name.to_string_lossy()))
+                        .unwrap_or(false)
+                }
+            }
+            RuleKind::Regex(re) => re.is_match(&slash_path(path)),
+            RuleKind::Size(ord, size) => metadata.is_file() && metadata.len().cmp(size) == *ord,
+            RuleKind::Age(ord, age) => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis();
+                now.saturating_sub(metadata_last_modified(metadata)).cmp(age) == *ord
+            }
+        }
+    }
+}
+
+/// 全局规则 + 扫描时读到的各个.jobsignore
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    global: Vec<Rule>,
+    /// .jobsignore所在的文件夹 -> 里面的规则
+    local: HashMap<PathBuf, Vec<Rule>>,
+    /// update_node扫过的根，互相不包含；后来扫里面的文件夹时还按外面的根算，结果不随扫描顺序变
+    roots: Vec<PathBuf>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            global: DEFAULT_RULES.iter().map(|r| Rule::parse(r).unwrap()).collect(),
+            local: HashMap::new(),
+            roots: vec![],
+        }
+    }
+}
+
+impl RuleSet {
+    /// 被排除的文件不进索引，被排除的文件夹只留总数
+    pub fn is_excluded(&self, path: &Path, metadata: &Metadata) -> bool {
+        let mut excluded = false;
+        // 不在任何扫过的根下面时退回整个路径
+        let rel = match self.roots.iter().find(|root| path.starts_with(root)) {
+            Some(root) => slash_path(path.strip_prefix(root).unwrap()),
+            None => slash_path(path).trim_start_matches('/').to_string(),
+        };
+        for rule in self.global.iter() {
+            if rule.matches(path, &rel, metadata) {
+                excluded = !rule.negate;
+            }
+        }
+        // 外层的先看，里层的.jobsignore可以覆盖外层
+        let mut bases = path
+            .ancestors()
+            .skip(1)
+            .filter(|base| self.local.contains_key(*base))
+            .collect::<Vec<_>>();
+        bases.reverse();
+        for base in bases {
+            let rel = slash_path(path.strip_prefix(base).unwrap());
+            for rule in self.local[base].iter() {
+                if rule.matches(path, &rel, metadata) {
+                    excluded = !rule.negate;
+                }
+            }
+        }
+        excluded
+    }
+
+    pub fn global(&self) -> Vec<String> {
+        self.global.iter().map(|r| r.src.clone()).collect()
+    }
+
+    pub(crate) fn add(&mut self, rule: Rule) {
+        self.global.retain(|r| r.src != rule.src);
+        self.global.push(rule);
+    }
+
+    pub(crate) fn remove(&mut self, src: &str) -> bool {
+        let len = self.global.len();
+        self.global.retain(|r| r.src != src.trim());
+        len != self.global.len()
+    }
+
+    pub(crate) fn set_global(&mut self, rules: Vec<Rule>) {
+        self.global = rules;
+    }
+
+    /// 记下扫描的根，已经在某个根下面的不算；盖住了原来的根时返回true，里面的结果可能变了
+    pub(crate) fn add_root(&mut self, root: &Path) -> bool {
+        if self.roots.iter().any(|r| root.starts_with(r)) {
+            return false;
+        }
+        let len = self.roots.len();
+        self.roots.retain(|r| !r.starts_with(root));
+        self.roots.push(root.to_path_buf());
+        len != self.roots.len() - 1
+    }
+
+    /// 扫到dir时重新读它的.jobsignore，没有就清掉
+    pub(crate) fn refresh_local(&mut self, dir: &Path) {
+        match fs::read_to_string(dir.join(IGNORE_FILE)) {
+            Ok(text) => {
+                // 写错的行直接跳过，不影响扫描
+                let rules = parse_lines(&text).filter_map(|r| r.ok()).collect::<Vec<_>>();
+                self.local.insert(dir.to_path_buf(), rules);
+            }
+            Err(_) => {
+                self.local.remove(dir);
+            }
+        }
+    }
+}
+
+/// 一行一条，空行和#开头的跳过
+pub(crate) fn parse_lines(text: &str) -> impl Iterator<Item = Result<Rule, JError>> + '_ {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Rule::parse)
+}
+
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// `size_report.txt`这种还是当glob
+fn is_cmp(cond: &str) -> bool {
+    cond.trim_start().starts_with(['>', '<'])
+}
+
+fn parse_cmp(cond: &str) -> Option<(Ordering, &str)> {
+    let cond = cond.trim();
+    if let Some(num) = cond.strip_prefix('>') {
+        Some((Ordering::Greater, num.trim()))
+    } else if let Some(num) = cond.strip_prefix('<') {
+        Some((Ordering::Less, num.trim()))
+    } else {
+        None
+    }
+}
+
+fn split_unit(num: &str) -> Option<(u64, &str)> {
+    let pos = num.find(|c: char| !c.is_ascii_digit()).unwrap_or(num.len());
+    Some((num[..pos].parse().ok()?, &num[pos..]))
+}
+
+/// KiB、MB都当1024进
+pub(crate) fn parse_size(num: &str) -> Option<u64> {
+    let (n, unit) = split_unit(num)?;
+    let scale: u64 = match unit.to_ascii_uppercase().trim_end_matches('B').trim_end_matches('I') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    n.checked_mul(scale)
+}
+
+pub(crate) fn parse_age(num: &str) -> Option<Duration> {
+    let (n, unit) = split_unit(num)?;
+    let secs: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "" | "d" => 86400,
+        "w" => 7 * 86400,
+        "y" => 365 * 86400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(n.checked_mul(secs)?))
+}
+
+/// `**/`任意层，`*`和`?`不跨`/`，`[...]`原样保留
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    let chars = glob.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i..].iter().position(|&c| c == ']') {
+                Some(end) => {
+                    let class = chars[i + 1..i + end].iter().collect::<String>();
+                    let class = class.strip_prefix('!').map(|c| format!("^{c}")).unwrap_or(class);
+                    re.push('[');
+                    re.push_str(&class.replace('\\', "\\\\"));
+                    re.push(']');
+                    i += end + 1;
+                    continue;
+                }
+                None => re.push_str("\\["),
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re.push('$');
+    re
+}
+
+/// 索引旁边的规则文件：example.csv -> example.jobsignore，格式和.jobsignore一样
+fn rules_path(index_path: &Path) -> PathBuf {
+    index_path.with_extension("jobsignore")
+}
+
+impl JManager<u64, JNode> {
+    /// 全局规则（不含各处的.jobsignore）
+    pub fn rules(&self) -> Vec<String> {
+        self.rules.global()
+    }
+
+    pub fn add_rule(&mut self, rule: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.rules.add(Rule::parse(rule)?);
+        self.invalidate_dirs();
+        Ok(())
+    }
+
+    /// 没有这条规则时返回false
+    pub fn remove_rule(&mut self, rule: &str) -> bool {
+        let removed = self.rules.remove(rule);
+        if removed {
+            self.invalidate_dirs();
+        }
+        removed
+    }
+
+    /// 规则变了，所有文件夹标脏，下次update_node按新规则重读
+    pub(crate) fn invalidate_dirs(&mut self) {
+        for h in self.nodes.ids().collect::<Vec<_>>() {
+            self.nodes.set_dirty(&h);
+        }
+    }
+
+    pub(crate) fn dump_rules(&self, index_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut text = self.rules.global().join("\n");
+        text.push('\n');
+        fs::write(rules_path(index_path), text)?;
+        Ok(())
+    }
+
+    /// 没有规则文件就保持现在的规则
+    pub(crate) fn load_rules(&mut self, index_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let Ok(text) = fs::read_to_string(rules_path(index_path)) else {
+            return Ok(());
+        };
+        let rules = parse_lines(&text).collect::<Result<Vec<_>, _>>()?;
+        self.rules.set_global(rules);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::action::JNodeAction;
+use super::manager::JManager;
+use super::node::JNode;
+use super::utils::segment_name;
+
+/// 名字索引，跟着create_node/delete_node一起改
+///
+/// 名字统一转小写，前缀查正着的trie，后缀查反过来的trie，
+/// 模糊查询直接在去重后的名字上算编辑距离
+#[derive(Debug, Default)]
+pub(crate) struct NameIndex {
+    prefix: Trie,
+    suffix: Trie,
+    /// 小写名字 -> 节点
+    names: HashMap<String, HashSet<u64>>,
+}
+
+#[derive(Debug, Default)]
+struct Trie {
+    children: BTreeMap<char, Trie>,
+    terminal: bool,
+}
+
+impl NameIndex {
+    pub(crate) fn insert(&mut self, name: &str, h: u64) {
+        let key = name.to_lowercase();
+        let set = self.names.entry(key.clone()).or_default();
+        if set.is_empty() {
+            self.prefix.insert(key.chars());
+            self.suffix.insert(key.chars().rev());
+        }
+        set.insert(h);
+    }
+
+    pub(crate) fn remove(&mut self, name: &str, h: &u64) {
+        let key = name.to_lowercase();
+        let Some(set) = self.names.get_mut(&key) else {
+            return;
+        };
+        set.remove(h);
+        if set.is_empty() {
+            self.names.remove(&key);
+            self.prefix.remove(&mut key.chars());
+            self.suffix.remove(&mut key.chars().rev());
+        }
+    }
+
+    /// (节点, 名字比前缀多出来的字符数)
+    fn prefix(&self, prefix: &str) -> Vec<(u64, usize)> {
+        let key = prefix.to_lowercase();
+        let mut result = vec![];
+        for name in self.prefix.collect(key.chars()) {
+            let score = name.chars().count() - key.chars().count();
+            for h in self.names.get(&name).into_iter().flatten() {
+                result.push((*h, score));
+            }
+        }
+        result
+    }
+
+    fn suffix(&self, suffix: &str) -> Vec<(u64, usize)> {
+        let key = suffix.to_lowercase();
+        let mut result = vec![];
+        for name in self.suffix.collect(key.chars().rev()) {
+            let name = name.chars().rev().collect::<String>();
+            let score = name.chars().count() - key.chars().count();
+            for h in self.names.get(&name).into_iter().flatten() {
+                result.push((*h, score));
+            }
+        }
+        result
+    }
+
+    /// (节点, 编辑距离)
+    fn fuzzy(&self, query: &str, max_dist: usize) -> Vec<(u64, usize)> {
+        let key = query.to_lowercase().chars().collect::<Vec<_>>();
+        let mut result = vec![];
+        for (name, set) in self.names.iter() {
+            let name = name.chars().collect::<Vec<_>>();
+            if let Some(dist) = edit_distance(&key, &name, max_dist) {
+                result.extend(set.iter().map(|h| (*h, dist)));
+            }
+        }
+        result
+    }
+}
+
+impl Trie {
+    fn insert(&mut self, key: impl Iterator<Item = char>) {
+        let mut node = self;
+        for c in key {
+            node = node.children.entry(c).or_default();
+        }
+        node.terminal = true;
+    }
+
+    /// 返回这个节点是否已经空了，空了就让上一层删掉
+    fn remove(&mut self, key: &mut impl Iterator<Item = char>) -> bool {
+        match key.next() {
+            None => self.terminal = false,
+            Some(c) => {
+                if let Some(child) = self.children.get_mut(&c) {
+                    if child.remove(key) {
+                        self.children.remove(&c);
+                    }
+                }
+            }
+        }
+        !self.terminal && self.children.is_empty()
+    }
+
+    /// 以key开头的所有完整名字
+    fn collect(&self, key: impl Iterator<Item = char>) -> Vec<String> {
+        let mut node = self;
+        let mut word = String::new();
+        for c in key {
+            match node.children.get(&c) {
+                Some(child) => node = child,
+                None => return vec![],
+            }
+            word.push(c);
+        }
+        let mut result = vec![];
+        let mut stack = vec![(node, word)];
+        while let Some((node, word)) = stack.pop() {
+            if node.terminal {
+                result.push(word.clone());
+            }
+            for (c, child) in node.children.iter() {
+                let mut w = word.clone();
+                w.push(*c);
+                stack.push((child, w));
+            }
+        }
+        result
+    }
+}
+
+/// 超过max_dist就提前放弃
+fn edit_distance(a: &[char], b: &[char], max_dist: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max_dist {
+        return None;
+    }
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        let mut row_min = cur[0];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            row_min = row_min.min(cur[j]);
+        }
+        if row_min > max_dist {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    let dist = prev[b.len()];
+    (dist <= max_dist).then_some(dist)
+}
+
+/// 查询的结果：(节点, 分数)
+type Found = Result<Vec<(u64, usize)>, Box<dyn std::error::Error>>;
+
+impl JManager<u64, JNode> {
+    /// 名字以prefix开头的节点，多出来的字符越少越靠前
+    pub fn search_prefix(&mut self, prefix: &str) -> Found {
+        // 名字索引只有表里的节点，没展开的块先展开
+        self.materialize_all()?;
+        Ok(self.rank(self.names.prefix(prefix)))
+    }
+
+    /// 名字以suffix结尾的节点，多出来的字符越少越靠前
+    pub fn search_suffix(&mut self, suffix: &str) -> Found {
+        self.materialize_all()?;
+        Ok(self.rank(self.names.suffix(suffix)))
+    }
+
+    /// 编辑距离不超过max_dist的节点，距离越小越靠前
+    pub fn search_fuzzy(&mut self, query: &str, max_dist: usize) -> Found {
+        self.materialize_all()?;
+        Ok(self.rank(self.names.fuzzy(query, max_dist)))
+    }
+
+    /// 分数一样的按路径排，路径要顺着父节点拼，每个节点只拼一次
+    fn rank(&self, mut result: Vec<(u64, usize)>) -> Vec<(u64, usize)> {
+        result.sort_by_cached_key(|(h, score)| (*score, self.nodes.path(h)));
+        result
+    }
+
+    pub(crate) fn index_name(&mut self, h: u64) {
+        let name = segment_name(&self.nodes.path(&h).unwrap());
+        self.names.insert(&name, h);
+    }
+
+    /// 节点已经从表里拿出来了，名字从node上取
+    pub(crate) fn unindex_name(&mut self, h: &u64, node: &JNode) {
+        let name = segment_name(node.path());
+        self.names.remove(&name, h);
+    }
+}
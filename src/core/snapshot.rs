@@ -0,0 +1,284 @@
+//! 快照历史：打开set_keep_history后，每次dump在索引旁边的.history里追加一条，记下每个文件夹的size/count_file/count_dir，
+//! 用来回答"上周以来哪里变大了"。
+//!
+//! example.csv -> example.history，每行一个快照（JSON）：
+//! ```text
+//! {"id":1,"time":..,"full":true,"changed":[["/data",1024,3,1],...],"removed":[]}
+//! {"id":2,"time":..,"full":false,"changed":[["/data",2048,4,1]],"removed":["/data/old"]}
+//! ```
+//! 每KEYFRAME_EVERY条存一次全量，中间的只存和上一条比变了的、没了的文件夹；
+//! 读某一条时从它前面最近的全量往后重放。time是毫秒时间戳。
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::action::JNodeAction;
+use super::manager::JManager;
+use super::node::JNode;
+
+/// 隔多少条存一次全量
+const KEYFRAME_EVERY: u32 = 32;
+
+/// 一个文件夹在某个快照里的数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirStat {
+    pub size: u64,
+    pub count_file: u64,
+    pub count_dir: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub id: u32,
+    /// 毫秒时间戳
+    pub time: u128,
+    /// 这一条是不是全量
+    pub full: bool,
+    /// 这一条里记了几个文件夹（全量是全部，增量是变了的加上没了的）
+    pub entries: usize,
+}
+
+/// 一个文件夹在两个快照之间的变化，before/after是None的是新建的/删掉的
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Growth {
+    pub path: PathBuf,
+    pub before: Option<DirStat>,
+    pub after: Option<DirStat>,
+}
+
+impl Growth {
+    pub fn size_delta(&self) -> i64 {
+        self.after.unwrap_or_default().size as i64 - self.before.unwrap_or_default().size as i64
+    }
+
+    pub fn files_delta(&self) -> i64 {
+        self.after.unwrap_or_default().count_file as i64 - self.before.unwrap_or_default().count_file as i64
+    }
+
+    pub fn dirs_delta(&self) -> i64 {
+        self.after.unwrap_or_default().count_dir as i64 - self.before.unwrap_or_default().count_dir as i64
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Line {
+    id: u32,
+    time: u128,
+    full: bool,
+    /// [path, size, count_file, count_dir]
+    #[serde(default)]
+    changed: Vec<(String, u64, u64, u64)>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+impl Line {
+    fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            id: self.id,
+            time: self.time,
+            full: self.full,
+            entries: self.changed.len() + self.removed.len(),
+        }
+    }
+
+    fn apply(&self, state: &mut HashMap<PathBuf, DirStat>) {
+        if self.full {
+            state.clear();
+        }
+        for (path, size, count_file, count_dir) in self.changed.iter() {
+            let stat = DirStat {
+                size: *size,
+                count_file: *count_file,
+                count_dir: *count_dir,
+            };
+            state.insert(PathBuf::from(path), stat);
+        }
+        for path in self.removed.iter() {
+            state.remove(Path::new(path));
+        }
+    }
+}
+
+/// 一个.history文件，open时整个读进来
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    lines: Vec<Line>,
+}
+
+impl History {
+    /// 索引旁边的历史文件：example.csv -> example.history
+    pub fn path_for(index_path: &Path) -> PathBuf {
+        index_path.with_extension("history")
+    }
+
+    /// 文件不存在时是空的，别的读错误照样报，不然append会从1重新编号
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = vec![];
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            lines.push(serde_json::from_str::<Line>(line)?);
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            lines,
+        })
+    }
+
+    pub fn list(&self) -> Vec<SnapshotInfo> {
+        self.lines.iter().map(Line::info).collect()
+    }
+
+    pub fn latest(&self) -> Option<SnapshotInfo> {
+        self.lines.last().map(Line::info)
+    }
+
+    /// 第id条快照时所有文件夹的数，没有这一条时是None
+    pub fn state(&self, id: u32) -> Option<HashMap<PathBuf, DirStat>> {
+        let k = self.lines.iter().position(|l| l.id == id)?;
+        let start = self.lines[..=k].iter().rposition(|l| l.full).unwrap_or(0);
+        let mut state = HashMap::new();
+        for line in self.lines[start..=k].iter() {
+            line.apply(&mut state);
+        }
+        Some(state)
+    }
+
+    /// a到b之间变了的文件夹，size变化大的在前
+    pub fn compare(&self, a: u32, b: u32) -> Option<Vec<Growth>> {
+        let before = self.state(a)?;
+        let mut after = self.state(b)?;
+        let mut result = vec![];
+        for (path, stat) in before.into_iter() {
+            let now = after.remove(&path);
+            if now != Some(stat) {
+                result.push(Growth {
+                    path,
+                    before: Some(stat),
+                    after: now,
+                });
+            }
+        }
+        result.extend(after.into_iter().map(|(path, stat)| Growth {
+            path,
+            before: None,
+            after: Some(stat),
+        }));
+        result.sort_by(|x, y| {
+            y.size_delta()
+                .unsigned_abs()
+                .cmp(&x.size_delta().unsigned_abs())
+                .then_with(|| x.path.cmp(&y.path))
+        });
+        Some(result)
+    }
+
+    /// path在每条快照里的数，按时间排；那时还没有或者已经删了的是None
+    pub fn series(&self, path: &Path) -> Vec<(SnapshotInfo, Option<DirStat>)> {
+        let key = path.to_str().unwrap_or_default();
+        let mut current = None;
+        let mut result = Vec::with_capacity(self.lines.len());
+        for line in self.lines.iter() {
+            if line.full {
+                current = None;
+            }
+            if let Some((_, size, count_file, count_dir)) = line.changed.iter().find(|(p, ..)| p == key) {
+                current = Some(DirStat {
+                    size: *size,
+                    count_file: *count_file,
+                    count_dir: *count_dir,
+                });
+            }
+            if line.removed.iter().any(|p| p == key) {
+                current = None;
+            }
+            result.push((line.info(), current));
+        }
+        result
+    }
+
+    /// 追加一条，和最后一条比只存变了的；到了KEYFRAME_EVERY存全量
+    fn append(&mut self, dirs: HashMap<PathBuf, DirStat>) -> Result<SnapshotInfo, Box<dyn std::error::Error>> {
+        let id = self.lines.last().map(|l| l.id + 1).unwrap_or(1);
+        let full = self.lines.iter().rev().take_while(|l| !l.full).count() as u32 + 1 >= KEYFRAME_EVERY
+            || self.lines.is_empty();
+        let mut changed = vec![];
+        let mut removed = vec![];
+        if full {
+            changed = dirs.iter().map(|(p, s)| entry(p, s)).collect();
+        } else {
+            let mut last = self.state(id - 1).unwrap_or_default();
+            for (path, stat) in dirs.iter() {
+                if last.remove(path) != Some(*stat) {
+                    changed.push(entry(path, stat));
+                }
+            }
+            removed = last.into_keys().map(|p| p.to_str().unwrap().to_string()).collect();
+        }
+        changed.sort();
+        removed.sort();
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let line = Line {
+            id,
+            time,
+            full,
+            changed,
+            removed,
+        };
+        let mut text = serde_json::to_string(&line)?;
+        text.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(text.as_bytes())?;
+        let info = line.info();
+        self.lines.push(line);
+        Ok(info)
+    }
+}
+
+fn entry(path: &Path, stat: &DirStat) -> (String, u64, u64, u64) {
+    (path.to_str().unwrap().to_string(), stat.size, stat.count_file, stat.count_dir)
+}
+
+impl JManager<u64, JNode> {
+    /// dump时要不要顺便记一条快照，默认不记
+    pub fn set_keep_history(&mut self, keep: bool) {
+        self.keep_history = keep;
+    }
+
+    pub fn keep_history(&self) -> bool {
+        self.keep_history
+    }
+
+    /// 现在所有文件夹（包括还没展开的块）的数，记进index_path旁边的.history
+    pub fn snapshot(&self, index_path: &Path) -> Result<SnapshotInfo, Box<dyn std::error::Error>> {
+        let mut dirs = HashMap::new();
+        let pending = self.pending_nodes()?.into_iter().map(|(_, _, node)| node);
+        for node in self.nodes.iter().map(|(_, node)| node).chain(pending) {
+            if let JNode::Dir(dir) = &node {
+                let stat = DirStat {
+                    size: node.size(),
+                    count_file: dir.count_file as u64,
+                    count_dir: dir.count_dir as u64,
+                };
+                dirs.insert(dir.abspath.clone(), stat);
+            }
+        }
+        History::open(&History::path_for(index_path))?.append(dirs)
+    }
+}
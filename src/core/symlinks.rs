@@ -0,0 +1,56 @@
+//! 符号链接：扫描时按SymlinkPolicy决定跳过、记成叶子还是跟进去。
+//!
+//! 跟进去时节点还挂在链接自己的路径下面，子项的路径也是链接路径拼出来的。
+//! 防绕圈：目标（canonicalize之后）是这条路径上某一层祖先实际所在位置的祖先或者自己，就不跟，记成叶子。
+//! 祖先要按逻辑路径一层层看，不然两个互相指的链接能绕出无限深的路径。
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::action::SymlinkPolicy;
+use super::manager::JManager;
+use super::node::JNode;
+
+impl JManager<u64, JNode> {
+    pub fn symlink_policy(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+
+    /// 换策略后所有文件夹标脏，下次update_node重扫
+    pub fn set_symlink_policy(&mut self, policy: SymlinkPolicy) {
+        if policy != self.symlinks {
+            self.symlinks = policy;
+            self.invalidate_dirs();
+        }
+    }
+}
+
+/// 扫描时用的metadata：不是链接的原样返回；Skip返回None；
+/// Leaf、以及Follow时悬空或者绕圈的，返回链接自己的（lstat）
+pub(crate) fn resolve_entry(path: &Path, policy: SymlinkPolicy) -> io::Result<Option<fs::Metadata>> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.file_type().is_symlink() {
+        return Ok(Some(metadata));
+    }
+    match policy {
+        SymlinkPolicy::Skip => Ok(None),
+        SymlinkPolicy::Leaf => Ok(Some(metadata)),
+        SymlinkPolicy::Follow => match fs::metadata(path) {
+            Ok(target) if target.is_dir() && is_cycle(path) => Ok(Some(metadata)),
+            Ok(target) => Ok(Some(target)),
+            Err(_) => Ok(Some(metadata)),
+        },
+    }
+}
+
+/// 目标是不是路径上某一层的祖先（或者就是那一层）
+fn is_cycle(path: &Path) -> bool {
+    let Ok(target) = path.canonicalize() else {
+        return true;
+    };
+    path.ancestors()
+        .skip(1)
+        .filter_map(|a| a.canonicalize().ok())
+        .any(|a| a.starts_with(&target))
+}
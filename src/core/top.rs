@@ -0,0 +1,109 @@
+//! 最大的文件/文件夹。
+//!
+//! NodeStore在insert/update/remove时顺手维护几棵按(值, slot)排的BTreeSet，
+//! top_n从大往小走，按先序区间O(1)判断在不在子树里，跳过不在的，够n个就停，
+//! 不用把子树整个走一遍；区间在树的结构变了之后的第一次top_n时重新编。
+//! 文件夹的数是上次扫描算好的，脏的文件夹可能是旧的。
+
+use std::collections::BTreeSet;
+use std::iter::Peekable;
+
+use super::action::{TopBy, TopKind};
+use super::errors::JError;
+use super::manager::JManager;
+use super::node::JNode;
+
+/// (值, slot)，文件和文件夹分开放
+#[derive(Debug, Default)]
+pub(crate) struct Ranking {
+    /// [文件, 文件夹]
+    size: [BTreeSet<(u64, u32)>; 2],
+    disk: [BTreeSet<(u64, u32)>; 2],
+    /// 文件的count_file总是0，只放文件夹
+    count_file: BTreeSet<(u64, u32)>,
+}
+
+/// 一个slot在排名里的几个值
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RankKey {
+    pub(crate) is_dir: bool,
+    pub(crate) size: u64,
+    pub(crate) disk: u64,
+    pub(crate) count_file: u64,
+}
+
+impl Ranking {
+    pub(crate) fn insert(&mut self, k: u32, key: RankKey) {
+        let i = key.is_dir as usize;
+        self.size[i].insert((key.size, k));
+        self.disk[i].insert((key.disk, k));
+        if key.is_dir {
+            self.count_file.insert((key.count_file, k));
+        }
+    }
+
+    pub(crate) fn remove(&mut self, k: u32, key: RankKey) {
+        let i = key.is_dir as usize;
+        self.size[i].remove(&(key.size, k));
+        self.disk[i].remove(&(key.disk, k));
+        if key.is_dir {
+            self.count_file.remove(&(key.count_file, k));
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.size[0].len() + self.size[1].len() + self.disk[0].len() + self.disk[1].len() + self.count_file.len()
+    }
+
+    /// 从大到小的slot
+    pub(crate) fn iter(&self, kind: TopKind, by: TopBy) -> Box<dyn Iterator<Item = u32> + '_> {
+        let [files, dirs] = match by {
+            TopBy::Size => &self.size,
+            TopBy::Disk => &self.disk,
+            TopBy::FileCount => {
+                return match kind {
+                    TopKind::Files => Box::new(std::iter::empty()),
+                    _ => Box::new(self.count_file.iter().rev().map(|(_, k)| *k)),
+                }
+            }
+        };
+        match kind {
+            TopKind::Files => Box::new(files.iter().rev().map(|(_, k)| *k)),
+            TopKind::Dirs => Box::new(dirs.iter().rev().map(|(_, k)| *k)),
+            TopKind::All => Box::new(merge_desc(files.iter().rev().peekable(), dirs.iter().rev().peekable())),
+        }
+    }
+}
+
+/// 两个从大到小的序列合成一个
+fn merge_desc<'a>(
+    mut a: Peekable<impl Iterator<Item = &'a (u64, u32)>>,
+    mut b: Peekable<impl Iterator<Item = &'a (u64, u32)>>,
+) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || match (a.peek(), b.peek()) {
+        (Some(x), Some(y)) if x >= y => a.next().map(|(_, k)| *k),
+        (_, Some(_)) => b.next().map(|(_, k)| *k),
+        _ => a.next().map(|(_, k)| *k),
+    })
+}
+
+impl JManager<u64, JNode> {
+    /// h下面（不含h自己）按by排最大的n个，没展开的块先展开
+    pub fn top_n(&mut self, h: &u64, kind: TopKind, by: TopBy, n: usize) -> Result<Vec<(u64, JNode)>, Box<dyn std::error::Error>> {
+        if !self.nodes.contains_key(h) {
+            return Err(JError::NotExistingNode(line!(), *h).into());
+        }
+        self.materialize(h)?;
+        Ok(self
+            .nodes
+            .top(h, kind, by, n)
+            .into_iter()
+            .filter_map(|ch| self.nodes.get(&ch).map(|node| (ch, node)))
+            .collect())
+    }
+
+    /// 上一次top_n在排名里看了几条
+    pub fn top_visits(&self) -> usize {
+        self.nodes.top_visits()
+    }
+}
@@ -0,0 +1,169 @@
+//! 按扩展名汇总：每个文件夹记下面（所有层）每种扩展名的字节数和文件数。
+//!
+//! update_node在aggregate时和size一起往上加，被规则过滤的文件夹在扫描线程里顺便算好。
+//! 扩展名一律小写、不带点，没有扩展名的是空串；字节数是表观大小，硬链接每个都算（和文件数一样）。
+//! 分类（视频、日志、压缩包、编译产物……）不落盘，要的时候从扩展名换算。
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::manager::JManager;
+use super::node::JNode;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TypeStat {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+/// 扩展名 -> 汇总
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TypeStats(BTreeMap<String, TypeStat>);
+
+impl TypeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, ext: &str, bytes: u64, files: u64) {
+        let stat = self.0.entry(ext.to_string()).or_default();
+        stat.bytes += bytes;
+        stat.files += files;
+    }
+
+    /// 一个文件
+    pub fn add_file(&mut self, path: &Path, bytes: u64) {
+        self.add(&ext_of(path), bytes, 1);
+    }
+
+    pub fn merge(&mut self, other: &TypeStats) {
+        for (ext, stat) in other.0.iter() {
+            self.add(ext, stat.bytes, stat.files);
+        }
+    }
+
+    pub fn get(&self, ext: &str) -> Option<TypeStat> {
+        self.0.get(&ext.trim_start_matches('.').to_lowercase()).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, TypeStat)> + '_ {
+        self.0.iter().map(|(ext, stat)| (ext.as_str(), *stat))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// 字节数大的在前
+    pub fn sorted(&self) -> Vec<(&str, TypeStat)> {
+        let mut list = self.iter().collect::<Vec<_>>();
+        list.sort_by(|(a, x), (b, y)| y.bytes.cmp(&x.bytes).then_with(|| a.cmp(b)));
+        list
+    }
+
+    /// 按分类再汇总一次，字节数大的在前
+    pub fn by_category(&self) -> Vec<(Category, TypeStat)> {
+        let mut map: BTreeMap<Category, TypeStat> = BTreeMap::new();
+        for (ext, stat) in self.iter() {
+            let entry = map.entry(Category::of(ext)).or_default();
+            entry.bytes += stat.bytes;
+            entry.files += stat.files;
+        }
+        let mut list = map.into_iter().collect::<Vec<_>>();
+        list.sort_by(|(a, x), (b, y)| y.bytes.cmp(&x.bytes).then_with(|| a.cmp(b)));
+        list
+    }
+}
+
+/// 小写、不带点，没有扩展名的是空串
+pub fn ext_of(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Video,
+    Audio,
+    Image,
+    Archive,
+    Document,
+    Code,
+    Log,
+    /// 编译产物：目标文件、库、字节码
+    Build,
+    Other,
+}
+
+impl Category {
+    pub fn of(ext: &str) -> Self {
+        match ext {
+            "mp4" | "mkv" | "avi" | "mov" | "wmv" | "flv" | "webm" | "m4v" | "mpg" | "mpeg" | "m2ts" => Self::Video,
+            "mp3" | "wav" | "flac" | "aac" | "ogg" | "m4a" | "wma" | "opus" => Self::Audio,
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" | "webp" | "tif" | "tiff" | "heic" | "ico" | "psd" => {
+                Self::Image
+            }
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "zst" | "iso" | "dmg" => Self::Archive,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "txt" | "md" | "csv" | "rtf" | "epub" => {
+                Self::Document
+            }
+            "rs" | "py" | "c" | "h" | "cc" | "cpp" | "hpp" | "js" | "ts" | "jsx" | "tsx" | "go" | "java" | "kt" | "rb"
+            | "php" | "sh" | "cs" | "swift" | "html" | "css" | "vue" | "json" | "toml" | "yaml" | "yml" => Self::Code,
+            "log" | "out" | "err" => Self::Log,
+            "o" | "obj" | "a" | "so" | "dll" | "lib" | "exe" | "class" | "jar" | "pyc" | "rlib" | "rmeta" | "d"
+            | "pdb" | "wasm" | "whl" => Self::Build,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Image => "image",
+            Self::Archive => "archive",
+            Self::Document => "document",
+            Self::Code => "code",
+            Self::Log => "log",
+            Self::Build => "build",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// CSV里是一列JSON，空的是空串；旧缓存没有这一列
+pub(crate) mod column {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::TypeStats;
+
+    pub(crate) fn serialize<S: Serializer>(types: &TypeStats, s: S) -> Result<S::Ok, S::Error> {
+        if types.is_empty() {
+            return s.serialize_str("");
+        }
+        serde_json::to_string(types).map_err(serde::ser::Error::custom)?.serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<TypeStats, D::Error> {
+        let text = String::deserialize(d)?;
+        if text.is_empty() {
+            return Ok(TypeStats::default());
+        }
+        serde_json::from_str(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+impl JManager<u64, JNode> {
+    /// h这个文件夹按扩展名的汇总，文件是None
+    pub fn types(&mut self, h: &u64) -> Result<Option<TypeStats>, Box<dyn std::error::Error>> {
+        Ok(self.get_info(h)?.types().cloned())
+    }
+}
@@ -1,9 +1,14 @@
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
     fs,
-    path::{Component, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
-use super::manager::is_excluded;
+use super::action::SymlinkPolicy;
+use super::errors::JError;
+use super::node::{metadata_disk, metadata_inode};
+use super::types::TypeStats;
 
 /// -------------------------------------------------------------------------
 /// 获取父路径
@@ -20,6 +25,18 @@ pub fn is_root(path: &PathBuf) -> bool {
     path.parent().is_none()
 }
 
+/// 路径的最后一段，根节点就是整个路径；不是UTF-8的按to_string_lossy换掉
+#[inline]
+pub fn segment_name(path: &Path) -> String {
+    segment_os(path).to_string_lossy().into_owned()
+}
+
+/// 同segment_name，原样不转
+#[inline]
+pub fn segment_os(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
 pub fn read_dir_recursive(path: &PathBuf) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let mut paths = Vec::new();
     for entry in fs::read_dir(path)? {
@@ -36,21 +53,72 @@ pub fn read_dir_recursive(path: &PathBuf) -> Result<Vec<PathBuf>, Box<dyn std::e
     Ok(paths)
 }
 
-/// size, fc, dc
-pub fn read_dir_recursive_(path: &PathBuf) -> Result<(u64, u64, u64), Box<dyn std::error::Error>> {
-    let mut result: (u64, u64, u64) = (0, 0, 0);
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
+/// size, disk, fc, dc；只算文件，文件夹自己的大小不算；links_once时同一个inode只算一次大小。
+/// 符号链接不跟进去，除了Skip都当一个文件算它自己的大小。
+/// 只有path自己读不了才返回错误，里面读不了的跳过、记进errors。
+/// 每个文件按扩展名记进types，硬链接每个都算
+pub fn read_dir_recursive_(
+    path: &PathBuf,
+    links_once: bool,
+    symlinks: SymlinkPolicy,
+    types: &mut TypeStats,
+    errors: &mut Vec<JError>,
+) -> Result<(u64, u64, u64, u64), JError> {
+    read_dir_total(path, links_once.then(HashSet::new).as_mut(), symlinks, types, errors)
+}
+
+fn read_dir_total(
+    path: &PathBuf,
+    mut seen: Option<&mut HashSet<(u64, u64)>>,
+    symlinks: SymlinkPolicy,
+    types: &mut TypeStats,
+    errors: &mut Vec<JError>,
+) -> Result<(u64, u64, u64, u64), JError> {
+    let mut result: (u64, u64, u64, u64) = (0, 0, 0, 0);
+    for entry in fs::read_dir(path).map_err(|e| JError::from_io(path, e))? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(JError::from_io(path, e));
+                continue;
+            }
+        };
         let path = entry.path();
-        result.0 += fs::metadata(&path)?.len();
-        if path.is_dir() {
-            let t = read_dir_recursive_(&path)?;
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                errors.push(JError::from_io(&path, e));
+                continue;
+            }
+        };
+        if metadata.file_type().is_symlink() && symlinks == SymlinkPolicy::Skip {
+            continue;
+        }
+        if metadata.is_dir() {
+            let t = match read_dir_total(&path, seen.as_deref_mut(), symlinks, types, errors) {
+                Ok(t) => t,
+                Err(e) => {
+                    errors.push(e);
+                    (0, 0, 0, 0)
+                }
+            };
             result.0 += t.0;
             result.1 += t.1;
-            result.2 += t.2 + 1;
-        } else {
-            result.1 += 1;
+            result.2 += t.2;
+            result.3 += t.3 + 1;
+            continue;
+        }
+        let (dev, ino, nlink) = metadata_inode(&metadata);
+        let counted = match seen.as_deref_mut() {
+            Some(seen) if nlink > 1 => seen.insert((dev, ino)),
+            _ => true,
+        };
+        if counted {
+            result.0 += metadata.len();
+            result.1 += metadata_disk(&metadata);
         }
+        types.add_file(&path, metadata.len());
+        result.2 += 1;
     }
     Ok(result)
 }
@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::action::{JNodeAction, ManagerAction};
+use super::errors::JError;
+use super::manager::{JManager, ROOT_PARENT};
+use super::node::JNode;
+use super::symlinks::resolve_entry;
+use super::utils::get_parent_pathbuf;
+
+/// 订阅了inotify的根，事件先攒在rx里，apply_events时再改表；
+/// Receiver不是Sync，包一层Mutex，manager才能放进python的类里
+pub(crate) struct Watch {
+    watcher: RecommendedWatcher,
+    rx: Mutex<Receiver<notify::Result<Event>>>,
+    roots: HashSet<PathBuf>,
+}
+
+impl std::fmt::Debug for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watch").field("roots", &self.roots).finish()
+    }
+}
+
+impl Watch {
+    fn new() -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+        })?;
+        Ok(Self {
+            watcher,
+            rx: Mutex::new(rx),
+            roots: HashSet::new(),
+        })
+    }
+
+    /// path在某个根下面（含根自己）
+    fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+/// 一批事件改完表之后要做的事
+#[derive(Default)]
+struct Pending {
+    /// 子节点变了，要从下往上重算的文件夹
+    touched: HashSet<u64>,
+    /// 被过滤的文件夹只有总数，里面有动静就整个重读
+    rescan: HashSet<u64>,
+}
+
+impl JManager<u64, JNode> {
+    /// 先扫一遍node，之后文件系统的改动由apply_events直接改表，不用再scan
+    pub fn watch(&mut self, node_h: &u64) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match self.nodes.get(node_h) {
+            None => return Err(JError::NotExistingNode(line!(), *node_h).into()),
+            Some(node) if !node.is_dir() => return Err(JError::NotDirectory(node.path().clone()).into()),
+            Some(node) => node.path().clone(),
+        };
+        if self.watch.is_none() {
+            self.watch = Some(Watch::new()?);
+        }
+        if self.watch.as_ref().unwrap().covers(&path) {
+            return Ok(());
+        }
+        self.update_node(node_h)?;
+
+        let watch = self.watch.as_mut().unwrap();
+        watch.watcher.watch(&path, RecursiveMode::Recursive)?;
+        // 新根盖住的旧根不用再单独订阅了
+        let covered = watch
+            .roots
+            .iter()
+            .filter(|root| root.starts_with(&path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for root in covered {
+            let _ = watch.watcher.unwatch(&root);
+            watch.roots.remove(&root);
+        }
+        watch.roots.insert(path);
+        Ok(())
+    }
+
+    /// 只能取消watch时给的根
+    pub fn unwatch(&mut self, node_h: &u64) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(node) = self.nodes.get(node_h) else {
+            return Err(JError::NotExistingNode(line!(), *node_h).into());
+        };
+        let path = node.path().clone();
+        if let Some(watch) = self.watch.as_mut() {
+            if watch.roots.remove(&path) {
+                // 底下已经删掉的子目录退订会报EINVAL，不影响
+                let _ = watch.watcher.unwatch(&path);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn watched(&self) -> Vec<PathBuf> {
+        let mut roots = self
+            .watch
+            .as_ref()
+            .map(|w| w.roots.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        roots.sort();
+        roots
+    }
+
+    /// 把攒下的事件落到表里：
+    /// create -> 挂新节点（文件夹整个扫一遍，可能是mv进来的），
+    /// remove -> delete_node，modify -> 文件重新stat，
+    /// 最后把受影响的文件夹从下往上重算到watch的根。
+    /// 返回处理了多少个事件
+    pub fn apply_events(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let Some(watch) = self.watch.as_ref() else {
+            return Ok(0);
+        };
+        let mut events = vec![];
+        let rx = watch.rx.lock().unwrap();
+        loop {
+            match rx.try_recv() {
+                Ok(Ok(event)) => events.push(event),
+                // 多半是刚删掉的子目录加不上订阅，删除事件本身还会到
+                Ok(Err(_)) => continue,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        drop(rx);
+
+        let mut pending = Pending::default();
+        for event in events.iter() {
+            if event.need_rescan() {
+                // 内核队列溢出，丢了事件，只能整个根重扫
+                for root in self.watched() {
+                    if let Some(h) = self.lookup(&root) {
+                        self.nodes.set_dirty(&h);
+                        self.update_node(&h)?;
+                    }
+                }
+                continue;
+            }
+            match event.kind {
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                    for path in event.paths.iter() {
+                        self.on_create(path, &mut pending)?;
+                    }
+                }
+                EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                    for path in event.paths.iter() {
+                        self.on_remove(path, &mut pending)?;
+                    }
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                    self.on_remove(&event.paths[0], &mut pending)?;
+                    self.on_create(&event.paths[1], &mut pending)?;
+                }
+                EventKind::Modify(ModifyKind::Name(_)) => {
+                    // 不知道是from还是to，看文件还在不在
+                    for path in event.paths.iter() {
+                        if path.exists() {
+                            self.on_create(path, &mut pending)?;
+                        } else {
+                            self.on_remove(path, &mut pending)?;
+                        }
+                    }
+                }
+                EventKind::Modify(_) => {
+                    for path in event.paths.iter() {
+                        self.on_modify(path, &mut pending)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.settle(pending)?;
+        Ok(events.len())
+    }
+
+    fn on_create(&mut self, path: &PathBuf, pending: &mut Pending) -> Result<(), Box<dyn std::error::Error>> {
+        if self.redirect_excluded(path, pending) {
+            return Ok(());
+        }
+        if self.lookup(path).is_some() {
+            return self.on_modify(path, pending);
+        }
+        let Some(ph) = self.lookup(&get_parent_pathbuf(path)) else {
+            return Ok(());
+        };
+        // 事件到的时候文件可能已经又没了；符号链接按策略来，Skip的不建
+        let Ok(Some(metadata)) = resolve_entry(path, self.symlinks) else {
+            return Ok(());
+        };
+        if !metadata.is_dir() && self.rules.is_excluded(path, &metadata) {
+            return Ok(());
+        }
+        let h = self.attach(JNode::with_metadata(path.clone(), &metadata), ph);
+        if metadata.is_dir() {
+            self.update_node(&h)?;
+        }
+        pending.touched.insert(ph);
+        Ok(())
+    }
+
+    fn on_remove(&mut self, path: &Path, pending: &mut Pending) -> Result<(), Box<dyn std::error::Error>> {
+        if self.redirect_excluded(path, pending) {
+            return Ok(());
+        }
+        let Some(h) = self.lookup(path) else {
+            return Ok(());
+        };
+        let ph = self.get_parent(&h);
+        self.delete_node(&h)?;
+        if ph != ROOT_PARENT {
+            pending.touched.insert(ph);
+        }
+        Ok(())
+    }
+
+    fn on_modify(&mut self, path: &Path, pending: &mut Pending) -> Result<(), Box<dyn std::error::Error>> {
+        if self.redirect_excluded(path, pending) {
+            return Ok(());
+        }
+        let Some(h) = self.lookup(path) else {
+            return Ok(());
+        };
+        if self.nodes.is_dir(&h) {
+            // 文件夹的mtime变化总是跟着子项的create/remove，一起在settle里重算
+            pending.touched.insert(h);
+        } else {
+            if !path.exists() {
+                return self.on_remove(path, pending);
+            }
+            // stat不了的会标成inaccessible，下次扫描时再报
+            self.nodes.update(&h, |v| {
+                let _ = v.update();
+            });
+            pending.touched.insert(self.get_parent(&h));
+        }
+        Ok(())
+    }
+
+    /// path在被过滤的文件夹里面时，改成重读那个文件夹
+    fn redirect_excluded(&self, path: &Path, pending: &mut Pending) -> bool {
+        let watch = self.watch.as_ref().unwrap();
+        for ancestor in path.ancestors().skip(1) {
+            let ancestor = ancestor.to_path_buf();
+            if !watch.covers(&ancestor) {
+                break;
+            }
+            let Some(h) = self.lookup(&ancestor) else {
+                continue;
+            };
+            match fs::metadata(&ancestor) {
+                Ok(metadata) if self.rules.is_excluded(&ancestor, &metadata) => {
+                    pending.rescan.insert(h);
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// 深的先算，一直算到watch的根，根以上只传脏标
+    fn settle(&mut self, mut pending: Pending) -> Result<(), Box<dyn std::error::Error>> {
+        for h in pending.rescan.iter() {
+            if !self.nodes.contains_key(h) {
+                continue;
+            }
+            self.nodes.set_dirty(h);
+            self.update_node(h)?;
+            pending.touched.insert(self.get_parent(h));
+        }
+
+        let watch = self.watch.as_ref().unwrap();
+        let mut chain = HashSet::new();
+        for h in pending.touched.iter() {
+            let mut h = *h;
+            while let Some(path) = self.nodes.path(&h) {
+                if !watch.covers(&path) || !chain.insert(h) {
+                    break;
+                }
+                h = self.get_parent(&h);
+            }
+        }
+        let mut chain = chain
+            .into_iter()
+            .map(|h| (self.nodes.path(&h).unwrap().components().count(), h))
+            .collect::<Vec<_>>();
+        chain.sort_by(|a, b| b.cmp(a));
+        for (_, h) in chain.iter() {
+            self.aggregate(h);
+        }
+        for root in self.watched() {
+            let Some(h) = self.lookup(&root) else {
+                continue;
+            };
+            if chain.iter().any(|(_, v)| *v == h) {
+                self.propagate_dirty(&h)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,19 @@
 #![allow(non_snake_case)]
 mod core;
 mod console;
+mod sync;
+pub mod cli;
+#[cfg(feature = "python")]
+mod python;
 
 pub use core::*;
+pub use sync::{SyncClient, SyncRow, SyncServer};
+pub use cli::run_cli;
 
 #[cfg(feature = "console")]
 pub use console::run;
 #[cfg(feature = "console")]
 pub use console::Console;
+
+#[cfg(feature = "python")]
+pub use python::jobs;
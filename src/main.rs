@@ -1,7 +1,12 @@
 use std::error::Error;
-use Jobs::run;
+use Jobs::{run, run_cli};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // 带参数就是非交互模式，见cli.rs
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        std::process::exit(run_cli(&args));
+    }
     run()?;
     Ok(())
 }
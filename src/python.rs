@@ -0,0 +1,263 @@
+//! Python接口，`cargo build --features python` 或者 `maturin develop`
+//!
+//! ```python
+//! import jobs
+//! mng = jobs.open("~/example.csv")
+//! h = mng.locate_node("/data")
+//! mng.update_node(h)
+//! pandas.DataFrame(mng.records(h))
+//! ```
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::{JError, JManager, JNode, JNodeAction, LoadMode, ManagerAction, ManagerStorage};
+
+create_exception!(jobs, JobsError, PyException);
+create_exception!(jobs, NoAuthorizationError, JobsError);
+create_exception!(jobs, NotExistingPathError, JobsError);
+create_exception!(jobs, NotDirectoryError, JobsError);
+create_exception!(jobs, NotExistingNodeError, JobsError);
+create_exception!(jobs, CacheError, JobsError);
+create_exception!(jobs, InvalidRuleError, JobsError);
+
+/// JError的每个变体对应一个异常类，都继承jobs.JobsError
+fn to_py_err(e: Box<dyn Error>) -> PyErr {
+    let msg = e.to_string();
+    if let Some(e) = e.downcast_ref::<JError>() {
+        return match e {
+            JError::NoAuthorization(_) => NoAuthorizationError::new_err(msg),
+            JError::NotExistingPath(_) => NotExistingPathError::new_err(msg),
+            JError::NotDirectory(_) => NotDirectoryError::new_err(msg),
+            JError::NotExistingNode(_, _) => NotExistingNodeError::new_err(msg),
+            JError::CacheError | JError::CorruptedCache(_, _) => CacheError::new_err(msg),
+            JError::InvalidRule(_, _) => InvalidRuleError::new_err(msg),
+            JError::InvalidQuery(_, _) => PyValueError::new_err(msg),
+            JError::Io(_, _) => PyOSError::new_err(msg),
+            JError::Cancelled | JError::IntegrityFailed(_) => JobsError::new_err(msg),
+        };
+    }
+    if e.is::<std::io::Error>() {
+        return PyOSError::new_err(msg);
+    }
+    JobsError::new_err(msg)
+}
+
+/// JNode的快照，拿到之后就和manager没关系了
+#[pyclass(name = "Node", module = "jobs", frozen)]
+pub struct PyNode {
+    node: JNode,
+    handle: u64,
+}
+
+#[pymethods]
+impl PyNode {
+    #[getter]
+    fn handle(&self) -> u64 {
+        self.handle
+    }
+    #[getter]
+    fn name(&self) -> String {
+        self.node.name()
+    }
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.node.path().clone()
+    }
+    #[getter]
+    fn size(&self) -> u64 {
+        self.node.size()
+    }
+    /// 占的磁盘空间
+    #[getter]
+    fn disk(&self) -> u64 {
+        self.node.disk_size()
+    }
+    /// 毫秒时间戳
+    #[getter]
+    fn last_modified(&self) -> u128 {
+        self.node.last_modified()
+    }
+    #[getter]
+    fn count_dir(&self) -> Option<u64> {
+        self.node.count_dir()
+    }
+    #[getter]
+    fn count_file(&self) -> Option<u64> {
+        self.node.count_file()
+    }
+    #[getter]
+    fn is_dir(&self) -> bool {
+        self.node.is_dir()
+    }
+    /// 符号链接指向哪，别的节点是None
+    #[getter]
+    fn target(&self) -> Option<PathBuf> {
+        self.node.target().cloned()
+    }
+    /// 上次扫描时读不了
+    #[getter]
+    fn inaccessible(&self) -> bool {
+        self.node.is_inaccessible()
+    }
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        record(py, self.handle, &self.node)
+    }
+    fn __repr__(&self) -> String {
+        format!("<jobs.Node {} size={}>", self.node.path().display(), self.node.size())
+    }
+}
+
+#[pyclass(name = "Manager", module = "jobs")]
+pub struct PyManager {
+    manager: JManager,
+}
+
+#[pymethods]
+impl PyManager {
+    /// threads为0时按CPU核数
+    #[new]
+    #[pyo3(signature = (threads = 0))]
+    fn new(threads: usize) -> Self {
+        Self {
+            manager: JManager::with_threads(threads),
+        }
+    }
+
+    fn locate_node(&mut self, path: PathBuf) -> PyResult<u64> {
+        self.manager.locate_node(&path).map_err(to_py_err)
+    }
+
+    fn delete_node(&mut self, node: u64) -> PyResult<()> {
+        self.manager.delete_node(&node).map_err(to_py_err)
+    }
+
+    /// 扫描时释放GIL
+    fn update_node(&mut self, py: Python<'_>, node: u64) -> PyResult<()> {
+        let manager = &mut self.manager;
+        // PyErr是Send的，在释放GIL的闭包里直接转，不丢异常类型
+        py.allow_threads(|| manager.update_node(&node).map_err(to_py_err))
+    }
+
+    /// 和update_node一样，返回读不了的路径：[(path, 错误信息)]
+    fn scan(&mut self, py: Python<'_>, node: u64) -> PyResult<Vec<(Option<PathBuf>, String)>> {
+        let manager = &mut self.manager;
+        let report = py
+            .allow_threads(|| manager.scan(&node).map_err(to_py_err))?;
+        Ok(report
+            .errors
+            .iter()
+            .map(|e| (e.path().map(|p| p.to_path_buf()), e.to_string()))
+            .collect())
+    }
+
+    fn get_info(&mut self, node: u64) -> PyResult<PyNode> {
+        let info = self.manager.get_info(&node).map_err(to_py_err)?;
+        Ok(PyNode { node: info, handle: node })
+    }
+
+    fn get_parent(&self, node: u64) -> u64 {
+        self.manager.get_parent(&node)
+    }
+
+    fn get_children(&self, node: u64) -> Vec<u64> {
+        self.manager.get_children(&node)
+    }
+
+    fn children(&mut self, node: u64) -> PyResult<Vec<PyNode>> {
+        self.manager.materialize(&node).map_err(to_py_err)?;
+        Ok(self
+            .manager
+            .get_children_node(&node)
+            .into_iter()
+            .map(|(v, h)| PyNode {
+                node: v.clone(),
+                handle: h,
+            })
+            .collect())
+    }
+
+    /// node子树（含自己）的所有节点，每个节点一个dict，可以直接喂给pandas.DataFrame
+    fn records<'py>(&mut self, py: Python<'py>, node: u64) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.manager.materialize(&node).map_err(to_py_err)?;
+        let mut result = vec![];
+        let mut stack = vec![node];
+        while let Some(h) = stack.pop() {
+            let info = self.manager.get_info(&h).map_err(to_py_err)?;
+            result.push(record(py, h, &info)?);
+            stack.extend(self.manager.get_children(&h));
+        }
+        Ok(result)
+    }
+
+    fn search_prefix(&mut self, prefix: &str) -> PyResult<Vec<(u64, usize)>> {
+        self.manager.search_prefix(prefix).map_err(to_py_err)
+    }
+
+    fn search_suffix(&mut self, suffix: &str) -> PyResult<Vec<(u64, usize)>> {
+        self.manager.search_suffix(suffix).map_err(to_py_err)
+    }
+
+    #[pyo3(signature = (query, max_dist = 2))]
+    fn search_fuzzy(&mut self, query: &str, max_dist: usize) -> PyResult<Vec<(u64, usize)>> {
+        self.manager.search_fuzzy(query, max_dist).map_err(to_py_err)
+    }
+
+    fn dump(&self, path: PathBuf) -> PyResult<()> {
+        self.manager.dump(&path).map_err(to_py_err)
+    }
+
+    #[pyo3(signature = (path, trust = false))]
+    fn load(&mut self, path: PathBuf, trust: bool) -> PyResult<()> {
+        let mode = if trust { LoadMode::Trust } else { LoadMode::Verify };
+        self.manager.set_load_mode(mode);
+        self.manager.load(&path).map_err(to_py_err)
+    }
+}
+
+fn record<'py>(py: Python<'py>, h: u64, node: &JNode) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("handle", h)?;
+    dict.set_item("name", node.name())?;
+    dict.set_item("path", node.path().clone())?;
+    dict.set_item("is_dir", node.is_dir())?;
+    dict.set_item("target", node.target().cloned())?;
+    dict.set_item("inaccessible", node.is_inaccessible())?;
+    dict.set_item("size", node.size())?;
+    dict.set_item("disk", node.disk_size())?;
+    dict.set_item("last_modified", node.last_modified())?;
+    dict.set_item("count_dir", node.count_dir())?;
+    dict.set_item("count_file", node.count_file())?;
+    Ok(dict)
+}
+
+/// 新建manager并load索引，文件不存在时就是空的
+#[pyfunction]
+#[pyo3(signature = (path, trust = false, threads = 0))]
+fn open(path: PathBuf, trust: bool, threads: usize) -> PyResult<PyManager> {
+    let mut manager = PyManager::new(threads);
+    manager.load(path, trust)?;
+    Ok(manager)
+}
+
+#[pymodule]
+#[pyo3(name = "jobs")]
+pub fn jobs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyManager>()?;
+    m.add_class::<PyNode>()?;
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    m.add("JobsError", py.get_type::<JobsError>())?;
+    m.add("NoAuthorizationError", py.get_type::<NoAuthorizationError>())?;
+    m.add("NotExistingPathError", py.get_type::<NotExistingPathError>())?;
+    m.add("NotDirectoryError", py.get_type::<NotDirectoryError>())?;
+    m.add("NotExistingNodeError", py.get_type::<NotExistingNodeError>())?;
+    m.add("CacheError", py.get_type::<CacheError>())?;
+    m.add("InvalidRuleError", py.get_type::<InvalidRuleError>())?;
+    Ok(())
+}
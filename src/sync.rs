@@ -0,0 +1,241 @@
+//! 多台机器之间同步索引
+//!
+//! TCP上一问一答，每条消息一行JSON：
+//! ```text
+//! -> {"Push":{"host":"pc1","rows":[{"data":{...DumpData},"deleted":false}, ...]}}
+//! <- {"Pushed":{"accepted":3,"rejected":0}}
+//! -> {"Pull":{"host":"pc1"}}
+//! <- {"Rows":{"rows":[...]}}
+//! -> "Hosts"
+//! <- {"Hosts":{"hosts":["pc1","pc2"]}}
+//! ```
+//! 服务端按host分开存，同一路径冲突时留last_write_time大的那条，
+//! 删除也是一条记录（deleted = true，时间取删除时刻）。
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DumpData, JManager};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncRow {
+    pub data: DumpData,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    Push { host: String, rows: Vec<SyncRow> },
+    Pull { host: String },
+    Hosts,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    Pushed { accepted: usize, rejected: usize },
+    Rows { rows: Vec<SyncRow> },
+    Hosts { hosts: Vec<String> },
+    Error { message: String },
+}
+
+/// host -> (abspath -> row)
+type Store = Arc<Mutex<HashMap<String, HashMap<String, SyncRow>>>>;
+
+pub struct SyncServer {
+    listener: TcpListener,
+    store: Store,
+}
+
+impl SyncServer {
+    /// 端口给0就随便挑一个，用local_addr拿
+    pub fn bind(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            store: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 一直阻塞，每个连接一个线程
+    pub fn serve(self) -> std::io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let store = self.store.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle(stream, store) {
+                    eprintln!("[Jobs sync] {e}");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle(stream: TcpStream, store: Store) -> Result<(), Box<dyn Error>> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => respond(request, &store),
+            Err(e) => Response::Error {
+                message: e.to_string(),
+            },
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn respond(request: Request, store: &Store) -> Response {
+    let mut store = store.lock().unwrap();
+    match request {
+        Request::Push { host, rows } => {
+            let tree = store.entry(host).or_default();
+            let (mut accepted, mut rejected) = (0, 0);
+            for row in rows {
+                match tree.get(&row.data.abspath) {
+                    Some(old) if old.data.last_write_time > row.data.last_write_time => rejected += 1,
+                    _ => {
+                        tree.insert(row.data.abspath.clone(), row);
+                        accepted += 1;
+                    }
+                }
+            }
+            Response::Pushed { accepted, rejected }
+        }
+        Request::Pull { host } => {
+            let mut rows = store
+                .get(&host)
+                .map(|tree| tree.values().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            rows.sort_by(|a, b| a.data.cmp(&b.data));
+            Response::Rows { rows }
+        }
+        Request::Hosts => {
+            let mut hosts = store.keys().cloned().collect::<Vec<_>>();
+            hosts.sort();
+            Response::Hosts { hosts }
+        }
+    }
+}
+
+/// 记着上次推了什么，下次只推变化的部分
+pub struct SyncClient {
+    addr: SocketAddr,
+    host: String,
+    pushed: HashMap<String, DumpData>,
+}
+
+impl SyncClient {
+    pub fn new(addr: SocketAddr, host: &str) -> Self {
+        Self {
+            addr,
+            host: host.to_string(),
+            pushed: HashMap::new(),
+        }
+    }
+
+    /// 推这台机器的增量，返回服务端接受的行数
+    pub fn push(&mut self, mng: &JManager) -> Result<usize, Box<dyn Error>> {
+        let rows = mng.dump_rows()?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let current = rows
+            .into_iter()
+            .map(|data| (data.abspath.clone(), data))
+            .collect::<HashMap<_, _>>();
+
+        let mut delta = vec![];
+        for (path, data) in current.iter() {
+            if self.pushed.get(path) != Some(data) {
+                delta.push(SyncRow {
+                    data: data.clone(),
+                    deleted: false,
+                });
+            }
+        }
+        for (path, data) in self.pushed.iter() {
+            if !current.contains_key(path) {
+                let mut data = data.clone();
+                data.last_write_time = now;
+                delta.push(SyncRow { data, deleted: true });
+            }
+        }
+        delta.sort_by(|a, b| a.data.cmp(&b.data));
+        if delta.is_empty() {
+            return Ok(0);
+        }
+
+        let accepted = self.push_rows(delta)?;
+        self.pushed = current;
+        Ok(accepted)
+    }
+
+    /// 直接推行，不管增量
+    pub fn push_rows(&self, rows: Vec<SyncRow>) -> Result<usize, Box<dyn Error>> {
+        let request = Request::Push {
+            host: self.host.clone(),
+            rows,
+        };
+        match self.call(&request)? {
+            Response::Pushed { accepted, .. } => Ok(accepted),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 某台机器的索引，不含已删除的行，按路径排好序，可以直接喂给JManager::load_rows
+    pub fn pull(&self, host: &str) -> Result<Vec<DumpData>, Box<dyn Error>> {
+        let request = Request::Pull {
+            host: host.to_string(),
+        };
+        match self.call(&request)? {
+            Response::Rows { rows } => Ok(rows
+                .into_iter()
+                .filter(|row| !row.deleted)
+                .map(|row| row.data)
+                .collect()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn hosts(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        match self.call(&Request::Hosts)? {
+            Response::Hosts { hosts } => Ok(hosts),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn call(&self, request: &Request) -> Result<Response, Box<dyn Error>> {
+        let mut stream = TcpStream::connect(self.addr)?;
+        serde_json::to_writer(&mut stream, request)?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+fn unexpected(response: Response) -> Box<dyn Error> {
+    match response {
+        Response::Error { message } => format!("[Jobs sync] server error: {message}").into(),
+        other => format!("[Jobs sync] unexpected response: {other:?}").into(),
+    }
+}
@@ -0,0 +1,237 @@
+use std::{error::Error, fs, path::PathBuf, process::Command};
+
+use Jobs::cli::{execute, CliError, EXIT_FAILURE, EXIT_NOT_FOUND, EXIT_OK, EXIT_USAGE};
+
+// cargo test --test test_cli -- --nocapture
+
+#[allow(non_snake_case)]
+fn init_test_dir(name: &str) -> PathBuf {
+    let TEMP_DIR = env!("TEMP");
+    let TEMP_DIR = PathBuf::from(format!("{TEMP_DIR}/Jobs_test_cli/{name}"));
+    if TEMP_DIR.exists() {
+        fs::remove_dir_all(&TEMP_DIR).unwrap();
+    }
+    fs::create_dir_all(TEMP_DIR.join("A/B")).unwrap();
+    fs::write(TEMP_DIR.join("A/file_a.txt"), b"hellow word78787878").unwrap();
+    fs::write(TEMP_DIR.join("A/B/file_b.txt"), b"hellow word78787878").unwrap();
+    fs::write(TEMP_DIR.join("A/B/file_c.txt"), b"hellow").unwrap();
+    TEMP_DIR
+}
+
+fn run(args: &[&str]) -> Result<String, CliError> {
+    let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    let mut out = vec![];
+    execute(&args, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+/// scan写索引，show/tree只读索引
+#[allow(non_snake_case)]
+#[test]
+fn test_cli1() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("1");
+    let index = dir.join("index.csv");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    let out = run(&["--index", index, "--json", "scan", A])?;
+    let node: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(node["size"], 44);
+    assert_eq!(node["count_file"], 3);
+    assert_eq!(node["count_dir"], 1);
+
+    let out = run(&["show", A, "--index", index, "--json"])?;
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&out)?, node);
+
+    let out = run(&["--index", index, "tree", A, "--depth", "1"])?;
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("A/"));
+    assert!(lines[1].starts_with("|   B/"));
+    assert!(lines[2].starts_with("|   file_a.txt"));
+
+    let out = run(&["--index", index, "--json", "tree", A, "--depth", "5"])?;
+    let tree: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(tree["children"][0]["name"], "B");
+    assert_eq!(tree["children"][0]["children"].as_array().unwrap().len(), 2);
+    Ok(())
+}
+
+/// du按大小排，dump/load换格式
+#[allow(non_snake_case)]
+#[test]
+fn test_cli2() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("2");
+    let index = dir.join("index.csv");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    let out = run(&["--index", index, "--json", "du", A])?;
+    let rows: serde_json::Value = serde_json::from_str(&out)?;
+    let names = rows
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["B", "file_a.txt"]);
+
+    let jidx = dir.join("index.jidx");
+    let jidx = jidx.to_str().unwrap();
+    run(&["--index", index, "dump", jidx])?;
+    let out = run(&["--index", jidx, "--json", "load"])?;
+    let info: serde_json::Value = serde_json::from_str(&out)?;
+    let total = run(&["--index", index, "--json", "load", "--trust"])?;
+    let total: serde_json::Value = serde_json::from_str(&total)?;
+    assert_eq!(info["nodes"], total["nodes"]);
+
+    let txt = dir.join("index.txt");
+    let txt = txt.to_str().unwrap();
+    run(&["--index", index, "dump", "--format", "ndjson", txt])?;
+    assert!(fs::read_to_string(txt)?.lines().all(|l| l.starts_with("{\"id\"")));
+    assert!(matches!(run(&["--index", index, "dump", txt, "--format", "xml"]), Err(CliError::Usage(_))));
+    Ok(())
+}
+
+/// 退出码
+#[allow(non_snake_case)]
+#[test]
+fn test_cli3() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("3");
+    let index = dir.join("index.csv");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    let code = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_Jobs"))
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .code()
+            .unwrap()
+    };
+    assert_eq!(code(&["--index", index, "frobnicate"]), EXIT_USAGE);
+    assert_eq!(code(&["--index", index, "tree", A, "--depth", "x"]), EXIT_USAGE);
+    assert_eq!(code(&["--index", index, "show", A]), EXIT_NOT_FOUND);
+    assert_eq!(code(&["--index", index, "scan", "/no/such/dir"]), EXIT_NOT_FOUND);
+    assert_eq!(code(&["--index", index, "load"]), EXIT_NOT_FOUND);
+    assert_eq!(code(&["--index", index, "scan", A]), EXIT_OK);
+    assert_eq!(code(&["--index", index, "show", A]), EXIT_OK);
+    Ok(())
+}
+
+/// stats报告节点表的内存
+#[allow(non_snake_case)]
+#[test]
+fn test_cli4() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("4");
+    let index = dir.join("index.jidx");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    run(&["--index", index, "scan", A])?;
+    let out = run(&["--index", index, "--json", "stats"])?;
+    let stats: serde_json::Value = serde_json::from_str(&out)?;
+    let nodes = stats["nodes"].as_u64().unwrap();
+    // A以上的祖先 + A、B、三个文件
+    assert_eq!(nodes, dir.canonicalize()?.ancestors().count() as u64 + 5);
+    assert_eq!(stats["free_slots"], 0);
+    assert!(stats["bytes"].as_u64().unwrap() > stats["name_bytes"].as_u64().unwrap());
+
+    let out = run(&["--index", index, "stats"])?;
+    assert!(out.starts_with(&format!("nodes: {nodes}\n")));
+    Ok(())
+}
+
+/// --disk按占的磁盘空间算，稀疏文件几乎不占
+#[cfg(unix)]
+#[allow(non_snake_case)]
+#[test]
+fn test_cli5() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("5");
+    let index = dir.join("index.csv");
+    let index = index.to_str().unwrap();
+    fs::File::create(dir.join("A/sparse.bin"))?.set_len(1 << 20)?;
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    let out = run(&["--index", index, "--json", "scan", A])?;
+    let node: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(node["size"], 44 + (1 << 20));
+    assert!(node["disk"].as_u64().unwrap() < 1 << 20);
+
+    let out = run(&["--index", index, "du", A])?;
+    assert!(out.lines().next().unwrap().ends_with("sparse.bin"));
+    let out = run(&["--index", index, "--disk", "du", A])?;
+    assert!(out.lines().next().unwrap().ends_with("B"));
+    Ok(())
+}
+
+/// diff比较两份索引，不给new时用--index
+#[allow(non_snake_case)]
+#[test]
+fn test_cli6() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("6");
+    let old = dir.join("old.csv");
+    let old = old.to_str().unwrap();
+    let index = dir.join("index.csv");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    run(&["--index", old, "scan", A])?;
+    fs::write(dir.join("A/B/file_d.txt"), b"1234")?;
+    run(&["--index", index, "scan", A])?;
+
+    let out = run(&["--index", index, "diff", old])?;
+    let file_d = dir.join("A/B/file_d.txt").canonicalize()?;
+    assert!(out.lines().any(|l| l == format!("A\t+4\t{}", file_d.display())));
+    assert_eq!(out, run(&["diff", old, index])?);
+
+    let out = run(&["--index", index, "--json", "diff", old])?;
+    let diff: serde_json::Value = serde_json::from_str(&out)?;
+    let A = PathBuf::from(A).canonicalize()?;
+    let dir_A = diff["dirs"].as_array().unwrap().iter().find(|d| d["abspath"] == A.to_str().unwrap()).unwrap();
+    assert_eq!(dir_A["size_delta"], 4);
+    assert_eq!(dir_A["added"], 1);
+
+    let err = run(&["--index", index, "diff"]).unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_USAGE);
+    let err = run(&["--index", index, "diff", &format!("{old}.missing")]).unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_NOT_FOUND);
+    Ok(())
+}
+
+/// --digest让scan存摘要，verify发现大小、时间没变但内容变了的文件，退出码是1
+#[allow(non_snake_case)]
+#[test]
+fn test_cli7() -> Result<(), Box<dyn Error>> {
+    let dir = init_test_dir("7");
+    let index = dir.join("index.jidx");
+    let index = index.to_str().unwrap();
+    let A = dir.join("A");
+    let A = A.to_str().unwrap();
+
+    run(&["--index", index, "--digest", "blake3", "scan", A])?;
+    assert_eq!(run(&["--index", index, "verify", A])?, "");
+    let out = run(&["--index", index, "--json", "verify", A])?;
+    let report: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(report["checked"], 3);
+    assert_eq!(report["unhashed"], 0);
+
+    let file_c = dir.join("A/B/file_c.txt").canonicalize()?;
+    let old = fs::metadata(&file_c)?.modified()?;
+    fs::write(&file_c, b"HELLOW")?;
+    fs::File::options().write(true).open(&file_c)?.set_modified(old)?;
+    let err = run(&["--index", index, "verify", A]).unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_FAILURE);
+
+    let err = run(&["--index", index, "--digest", "md5", "scan", A]).unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_USAGE);
+    Ok(())
+}
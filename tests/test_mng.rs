@@ -81,6 +81,11 @@ fn init_test_dir(serial_number: i32) -> String {
     TEMP_DIR
 }
 
+/// 扫描后马上改文件的话，mtime可能还落在同一个时钟粒度里，先等一下
+fn wait_mtime_tick() {
+    std::thread::sleep(std::time::Duration::from_millis(50));
+}
+
 /// 1 ***文件夹级控制***
 /// case 1: 扫描功能
 /// 直接扫描A
@@ -323,6 +328,19 @@ mod file_level {
 /// 创建文件夹B/node_modules，B/node_modules/inside
 /// 创建文件B/node_modules/file.txt，B/node_modules/inside/file.txt
 /// 扫描A, 计算总文件夹数，总文件数
+///
+/// case 2: glob和大小规则
+/// 加规则*.log、size>1K，创建B/x.log、big.bin，扫描A；删掉*.log再扫描A
+///
+/// case 3: .jobsignore
+/// B2/.jobsignore里写C2/和file_b2*.txt，扫描A
+///
+/// case 4: 规则跟着索引一起存
+/// 加规则，dump，新manager load，规则还在；写错的规则报错
+///
+/// case 5: 带`/`的全局规则
+/// 加规则/B2、B/C/，创建B/B2/file_x.txt，扫描A：A/B2、A/B/C只留总数，B/B2照常；
+/// 再单独扫描B，还是按A算
 mod filter_sys {
     use super::*;
 
@@ -364,6 +382,132 @@ mod filter_sys {
         assert_eq!(root.count_dir().unwrap(), DEFAULT_DIR_CNT + 2);
         Ok(())
     }
+
+    /// case 2: glob和大小规则
+    /// 加规则*.log、size>1K，创建B/x.log、big.bin，扫描A；删掉*.log再扫描A
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(32);
+        let mut mng = JManager::new();
+        mng.add_rule("*.log")?;
+        mng.add_rule("size>1K")?;
+
+        let log: PathBuf = [&path, "A", "B", "x.log"].iter().collect();
+        fs::write(log, b"log")?;
+        let big: PathBuf = [&path, "A", "big.bin"].iter().collect();
+        fs::write(big, vec![0u8; 2048])?;
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file().unwrap(), DEFAULT_FILE_CNT);
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT);
+        assert!(mng.search_prefix("x.log")?.is_empty());
+
+        assert!(mng.remove_rule("*.log"));
+        assert!(!mng.remove_rule("*.log"));
+        mng.update_node(&node_h)?;
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file().unwrap(), DEFAULT_FILE_CNT + 1);
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT + 3);
+        assert_eq!(mng.search_prefix("x.log")?.len(), 1);
+        Ok(())
+    }
+
+    /// case 3: .jobsignore
+    /// B2/.jobsignore里写C2/和file_b2*.txt，扫描A
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(33);
+        let mut mng = JManager::new();
+
+        let ignore: PathBuf = [&path, "A", "B2", ".jobsignore"].iter().collect();
+        fs::write(ignore, b"# comment\nC2/\nfile_b2*.txt\n")?;
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let root = mng.get_info(&node_h)?;
+        // 少了file_b21、file_b22，多了.jobsignore
+        assert_eq!(root.count_file().unwrap(), DEFAULT_FILE_CNT - 1);
+        assert_eq!(root.count_dir().unwrap(), DEFAULT_DIR_CNT);
+        assert!(mng.search_prefix("file_b2")?.is_empty());
+
+        // C2只留总数
+        let C2: PathBuf = [&path, "A", "B2", "C2"].iter().collect();
+        let C2_h = mng.locate_node(&C2)?;
+        assert!(mng.get_children(&C2_h).is_empty());
+        assert_eq!(mng.get_info(&C2_h)?.count_file(), Some(1));
+        assert_eq!(mng.get_info(&C2_h)?.size(), 19);
+        // 只管B2下面
+        assert_eq!(mng.search_prefix("file_b.txt")?.len(), 1);
+        Ok(())
+    }
+
+    /// case 4: 规则跟着索引一起存
+    /// 加规则，dump，新manager load，规则还在；写错的规则报错
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_4() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(34);
+        let mut mng = JManager::new();
+        assert_eq!(mng.rules(), Jobs::DEFAULT_RULES.to_vec());
+        mng.add_rule("!.git/")?;
+        mng.add_rule("age>30d")?;
+        assert!(mng.add_rule("re:([").is_err());
+        assert!(mng.add_rule("size>12Q").is_err());
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let cache: PathBuf = [&path, "cache.csv"].iter().collect();
+        mng.dump(&cache)?;
+
+        let mut mng2 = JManager::new();
+        mng2.load(&cache)?;
+        assert_eq!(mng2.rules(), vec!["node_modules/", ".git/", "!.git/", "age>30d"]);
+        Ok(())
+    }
+
+    /// case 5: 带`/`的全局规则
+    /// 加规则/B2、B/C/，创建B/B2/file_x.txt，扫描A：A/B2、A/B/C只留总数，B/B2照常；
+    /// 再单独扫描B，还是按A算
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_5() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(35);
+        let mut mng = JManager::new();
+        mng.add_rule("/B2")?;
+        mng.add_rule("B/C/")?;
+        let inner: PathBuf = [&path, "A", "B", "B2"].iter().collect();
+        fs::create_dir_all(&inner)?;
+        fs::write(inner.join("file_x.txt"), b"x")?;
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let B2: PathBuf = [&path, "A", "B2"].iter().collect();
+        let B2_h = mng.locate_node(&B2)?;
+        assert!(mng.get_children(&B2_h).is_empty());
+        assert_eq!(mng.get_info(&B2_h)?.count_file(), Some(3));
+        let C: PathBuf = [&path, "A", "B", "C"].iter().collect();
+        let C_h = mng.locate_node(&C)?;
+        assert!(mng.get_children(&C_h).is_empty());
+        let inner_h = mng.locate_node(&inner)?;
+        assert_eq!(mng.get_children(&inner_h).len(), 1);
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 1));
+
+        let B: PathBuf = [&path, "A", "B"].iter().collect();
+        let B_h = mng.locate_node(&B)?;
+        mng.add_rule("*.tmp")?;
+        mng.update_node(&B_h)?;
+        assert!(mng.get_children(&C_h).is_empty());
+        assert_eq!(mng.get_children(&inner_h).len(), 1);
+        Ok(())
+    }
 }
 
 /// 4 ***持久化***
@@ -541,3 +685,2351 @@ mod persistent {
         Ok(())
     }
 }
+
+/// 5 ***并行扫描***
+/// case 1: 线程数
+/// 1个线程和8个线程分别扫描A，结果应与默认一致
+///
+/// case 2: 并行复用
+/// 4个线程，先扫描B，修改B2，再扫描A
+mod parallel {
+    use super::*;
+
+    /// case 1: 线程数
+    /// 1个线程和8个线程分别扫描A，结果应与默认一致
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(51);
+        let A: PathBuf = [&path, "A"].iter().collect();
+
+        for threads in [1, 8] {
+            let mut mng = JManager::with_threads(threads);
+            assert_eq!(mng.get_threads(), threads);
+
+            let node_h = mng.locate_node(&A)?;
+            mng.update_node(&node_h)?;
+            let root = mng.get_info(&node_h)?;
+
+            assert_eq!(root.size(), 133);
+            assert_eq!(root.count_file().unwrap(), DEFAULT_FILE_CNT);
+            assert_eq!(root.count_dir().unwrap(), DEFAULT_DIR_CNT);
+        }
+        Ok(())
+    }
+
+    /// case 2: 并行复用
+    /// 4个线程，先扫描B，修改B2，再扫描A
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(52);
+        let mut mng = JManager::with_threads(4);
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let B: PathBuf = [&path, "A", "B"].iter().collect();
+        let node_h = mng.locate_node(&B)?;
+        mng.update_node(&node_h)?;
+
+        let C3: PathBuf = [&path, "A", "B2", "C3"].iter().collect();
+        fs::create_dir_all(&C3)?;
+        fs::write(C3.join("file_c3.txt"), b"new file")?;
+        let file: PathBuf = [&path, "A", "B2", "file_b21.txt"].iter().collect();
+        fs::remove_file(file)?;
+
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let root = mng.get_info(&node_h)?;
+
+        assert_eq!(root.size(), 133 - 19 + 8);
+        assert_eq!(root.count_file().unwrap(), DEFAULT_FILE_CNT);
+        assert_eq!(root.count_dir().unwrap(), DEFAULT_DIR_CNT + 1);
+        Ok(())
+    }
+}
+
+/// 6 ***二进制分块索引***
+/// case 1: 功能测试
+/// 扫描A，dump成jidx
+/// 创建新mng，load，查看A，应无变化
+///
+/// case 2: 删除节点+文件测试
+/// 扫描A，dump，删除B2/C2，删除B/file_b.txt
+/// 创建新mng，load，查看A，应无变化
+/// 扫描A，查看A，应有变化
+///
+/// case 3: 按需展开
+/// A下面放三个大文件夹，dump
+/// 创建新mng，load，只有spine在表里，locate一个深处的文件才展开对应的块
+///
+/// case 4: 校验和
+/// dump之后改坏meta里一个字节，load应报错
+/// 改坏子树块里一个字节，展开时应报错
+///
+/// case 5: 平铺的大文件夹
+/// A/F下面直接放10000个文件，dump
+/// 创建新mng，load，文件不在spine里；locate其中一个只展开它所在的块
+///
+/// case 6: 长度不对
+/// header里的meta_len改成很大、文件截掉一半，load应报错，不能按读出来的长度分配内存
+mod binary_index {
+    use super::*;
+
+    /// case 1: 功能测试
+    /// 扫描A，dump成jidx
+    /// 创建新mng，load，查看A，应无变化
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(61);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let node1 = mng.get_info(&node_h)?;
+        mng.dump(&dump_path)?;
+
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A)?;
+        let node2 = mng.get_info(&node_h)?;
+
+        assert_eq!(node1.size(), node2.size());
+        assert_eq!(node1.count_dir(), node2.count_dir());
+        assert_eq!(node1.count_file(), node2.count_file());
+        Ok(())
+    }
+
+    /// case 2: 删除节点+文件测试
+    /// 扫描A，dump，删除B2/C2，删除B/file_b.txt
+    /// 创建新mng，load，查看A，应无变化
+    /// 扫描A，查看A，应有变化
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(62);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let node1 = mng.get_info(&node_h)?;
+        mng.dump(&dump_path)?;
+        wait_mtime_tick();
+
+        let C2: PathBuf = [&path, "A", "B2", "C2"].iter().collect();
+        fs::remove_dir_all(C2)?;
+        let file: PathBuf = [&path, "A", "B", "file_b.txt"].iter().collect();
+        fs::remove_file(file)?;
+
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A)?;
+        let node2 = mng.get_info(&node_h)?;
+
+        assert_eq!(node1.size(), node2.size());
+        assert_eq!(node1.count_dir(), node2.count_dir());
+        assert_eq!(node1.count_file(), node2.count_file());
+
+        mng.update_node(&node_h)?;
+        let node3 = mng.get_info(&node_h)?;
+
+        assert_eq!(node3.size(), 133 - 19 * 2);
+        assert_eq!(node3.count_dir().unwrap(), DEFAULT_DIR_CNT - 1);
+        assert_eq!(node3.count_file().unwrap(), DEFAULT_FILE_CNT - 2);
+        Ok(())
+    }
+
+    /// case 3: 按需展开
+    /// A下面放三个大文件夹，dump
+    /// 创建新mng，load，只有spine在表里，locate一个深处的文件才展开对应的块
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(63);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        for d in ["D1", "D2", "D3"] {
+            let dir: PathBuf = [&path, "A", d].iter().collect();
+            fs::create_dir_all(&dir)?;
+            for i in 0..2500 {
+                fs::write(dir.join(format!("file_{i}.txt")), b"x")?;
+            }
+        }
+        let mut mng = JManager::new();
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let node1 = mng.get_info(&node_h)?;
+        let full = mng.get_node_cnt();
+        mng.dump(&dump_path)?;
+
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let spine = mng.get_node_cnt();
+        assert!(spine < 2500);
+
+        let deep: PathBuf = [&path, "A", "D3", "file_42.txt"].iter().collect();
+        let deep_h = mng.locate_node(&deep)?;
+        assert_eq!(mng.get_info(&deep_h)?.size(), 1);
+        assert!(mng.get_node_cnt() > spine);
+        assert!(mng.get_node_cnt() < full);
+
+        // 没展开的块dump时也要写回去
+        let dump_path2: PathBuf = [&path, "dump2.jidx"].iter().collect();
+        mng.dump(&dump_path2)?;
+        let mut mng = JManager::new();
+        mng.load(&dump_path2)?;
+        let node_h = mng.locate_node(&A)?;
+        mng.materialize(&node_h)?;
+        assert_eq!(mng.get_node_cnt(), full);
+
+        mng.update_node(&node_h)?;
+        let node2 = mng.get_info(&node_h)?;
+        assert_eq!(node1.size(), node2.size());
+        assert_eq!(node1.count_dir(), node2.count_dir());
+        assert_eq!(node1.count_file(), node2.count_file());
+        Ok(())
+    }
+
+    /// case 4: 校验和
+    /// dump之后改坏meta里一个字节，load应报错
+    /// 改坏子树块里一个字节，展开时应报错
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_4() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(64);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        mng.dump(&dump_path)?;
+        let bytes = fs::read(&dump_path)?;
+
+        let mut broken = bytes.clone();
+        broken[40] ^= 0xff;
+        fs::write(&dump_path, broken)?;
+        let mut mng = JManager::new();
+        assert!(mng.load(&dump_path).is_err());
+
+        let mut broken = bytes.clone();
+        let last = broken.len() - 1;
+        broken[last] ^= 0xff;
+        fs::write(&dump_path, broken)?;
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A);
+        assert!(node_h.is_err() || mng.materialize(&node_h?).is_err());
+        Ok(())
+    }
+
+    /// case 5: 平铺的大文件夹
+    /// A/F下面直接放10000个文件，dump
+    /// 创建新mng，load，文件不在spine里；locate其中一个只展开它所在的块
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_5() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(65);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let F: PathBuf = [&path, "A", "F"].iter().collect();
+        fs::create_dir_all(&F)?;
+        for i in 0..10000 {
+            fs::write(F.join(format!("file_{i}.txt")), b"x")?;
+        }
+        let mut mng = JManager::new();
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let full = mng.get_node_cnt();
+        mng.dump(&dump_path)?;
+
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let spine = mng.get_node_cnt();
+        assert!(spine < 100);
+
+        let file_h = mng.locate_node(&F.join("file_1000.txt"))?;
+        assert_eq!(mng.get_info(&file_h)?.size(), 1);
+        assert!(mng.get_node_cnt() > spine);
+        assert!(mng.get_node_cnt() < full - 4096);
+
+        let F_h = mng.locate_node(&F)?;
+        mng.materialize(&F_h)?;
+        assert_eq!(mng.get_node_cnt(), spine + 10000);
+        let node_h = mng.locate_node(&A)?;
+        mng.materialize(&node_h)?;
+        assert_eq!(mng.get_node_cnt(), full);
+        Ok(())
+    }
+
+    /// case 6: 长度不对
+    /// header里的meta_len改成很大、文件截掉一半，load应报错，不能按读出来的长度分配内存
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_6() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(66);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        mng.dump(&dump_path)?;
+        let bytes = fs::read(&dump_path)?;
+
+        let mut broken = bytes.clone();
+        broken[20..28].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        fs::write(&dump_path, broken)?;
+        assert!(JManager::new().load(&dump_path).is_err());
+
+        fs::write(&dump_path, &bytes[..bytes.len() / 2])?;
+        assert!(JManager::new().load(&dump_path).is_err());
+        Ok(())
+    }
+}
+
+/// 7 ***信任缓存的load***
+/// case 1: 功能测试
+/// 扫描A，dump，删除B2/C2，删除B/file_b.txt
+/// 创建新mng，Trust模式load，查看A，应无变化
+/// 扫描A，查看A，应有变化
+///
+/// case 2: 按需校验
+/// 扫描A，dump，删除B2/C2
+/// Trust模式load，C2还在表里，get_info时才发现不存在并删掉
+///
+/// case 3: 旧缓存
+/// 没有is_dir列的CSV也能load
+mod trusted_load {
+    use super::*;
+    use Jobs::LoadMode;
+
+    /// case 1: 功能测试
+    /// 扫描A，dump，删除B2/C2，删除B/file_b.txt
+    /// 创建新mng，Trust模式load，查看A，应无变化
+    /// 扫描A，查看A，应有变化
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(71);
+        let dump_path: PathBuf = [&path, "dump.csv"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let node1 = mng.get_info(&node_h)?;
+        mng.dump(&dump_path)?;
+        wait_mtime_tick();
+
+        let C2: PathBuf = [&path, "A", "B2", "C2"].iter().collect();
+        fs::remove_dir_all(C2)?;
+        let file: PathBuf = [&path, "A", "B", "file_b.txt"].iter().collect();
+        fs::remove_file(file)?;
+
+        let mut mng = JManager::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A)?;
+        let node2 = mng.get_info(&node_h)?;
+
+        assert_eq!(node1.size(), node2.size());
+        assert_eq!(node1.count_dir(), node2.count_dir());
+        assert_eq!(node1.count_file(), node2.count_file());
+
+        mng.update_node(&node_h)?;
+        let node3 = mng.get_info(&node_h)?;
+
+        assert_eq!(node3.size(), 133 - 19 * 2);
+        assert_eq!(node3.count_dir().unwrap(), DEFAULT_DIR_CNT - 1);
+        assert_eq!(node3.count_file().unwrap(), DEFAULT_FILE_CNT - 2);
+        Ok(())
+    }
+
+    /// case 2: 按需校验
+    /// 扫描A，dump，删除B2/C2
+    /// Trust模式load，C2还在表里，get_info时才发现不存在并删掉
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(72);
+        let dump_path: PathBuf = [&path, "dump.csv"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        mng.dump(&dump_path)?;
+        let node_cnt = mng.get_node_cnt();
+
+        let C2: PathBuf = [&path, "A", "B2", "C2"].iter().collect();
+        fs::remove_dir_all(C2)?;
+
+        let mut mng = JManager::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load(&dump_path)?;
+        assert_eq!(mng.get_node_cnt(), node_cnt);
+
+        let B2: PathBuf = [&path, "A", "B2"].iter().collect();
+        let b2_h = mng.locate_node(&B2)?;
+        let (_, c2_h) = mng
+            .get_children_node(&b2_h)
+            .into_iter()
+            .find(|(v, _)| v.name() == "C2")
+            .unwrap();
+        assert!(mng.get_info(&c2_h).is_err());
+        assert_eq!(mng.get_node_cnt(), node_cnt - 2);
+        assert!(!mng.get_children(&b2_h).contains(&c2_h));
+        Ok(())
+    }
+
+    /// case 3: 旧缓存
+    /// 没有is_dir列的CSV也能load
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(73);
+        let dump_path: PathBuf = [&path, "dump.csv"].iter().collect();
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let node1 = mng.get_info(&node_h)?;
+        mng.dump(&dump_path)?;
+
+        // 去掉最后一列（types是带逗号的JSON，按CSV拆）
+        let mut rdr = csv::ReaderBuilder::new().has_headers(false).from_path(&dump_path)?;
+        let rows = rdr.records().collect::<Result<Vec<_>, _>>()?;
+        let mut wtr = csv::Writer::from_path(&dump_path)?;
+        for row in rows {
+            wtr.write_record(row.iter().take(row.len() - 1))?;
+        }
+        wtr.flush()?;
+
+        let mut mng = JManager::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A)?;
+        let node2 = mng.get_info(&node_h)?;
+
+        assert!(node2.count_dir().is_some());
+        assert_eq!(node1.size(), node2.size());
+        assert_eq!(node1.count_dir(), node2.count_dir());
+        assert_eq!(node1.count_file(), node2.count_file());
+        Ok(())
+    }
+}
+
+/// 8 ***名字搜索***
+/// case 1: 前缀/后缀/模糊
+/// 扫描A，按名字查
+///
+/// case 2: 索引跟着树变
+/// 扫描A，删除C，增加B2/file_new.txt，扫描A，再查
+///
+/// case 3: load之后
+/// A下面放两个大文件夹，dump成jidx，新mng load，没展开的块里的也能查到
+///
+/// case 4: 不是UTF-8的文件名（unix）
+/// B下放bad\xffname.txt，扫描A不panic，按换掉的名字能查到；dump成jidx再load，路径还是原来的字节
+mod name_search {
+    use super::*;
+
+    /// case 1: 前缀/后缀/模糊
+    /// 扫描A，按名字查
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(81);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        // file_b.txt, file_b21.txt, file_b22.txt
+        let found = mng.search_prefix("FILE_B")?;
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].1, 4);
+        assert_eq!(mng.get_info(&found[0].0)?.name(), "file_b.txt");
+
+        // file_0.txt * 2
+        let found = mng.search_suffix("_0.txt")?;
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|(_, score)| *score == 4));
+
+        let found = mng.search_fuzzy("B3", 1)?;
+        let names = found
+            .iter()
+            .map(|(h, _)| mng.get_info(h).unwrap().name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["B", "B2"]);
+        assert_eq!(found.iter().map(|(_, d)| *d).collect::<Vec<_>>(), vec![1, 1]);
+        Ok(())
+    }
+
+    /// case 2: 索引跟着树变
+    /// 扫描A，删除C，增加B2/file_new.txt，扫描A，再查
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(82);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        assert_eq!(mng.search_prefix("file_")?.len(), DEFAULT_FILE_CNT as usize);
+        wait_mtime_tick();
+
+        let C: PathBuf = [&path, "A", "B", "C"].iter().collect();
+        fs::remove_dir_all(C)?;
+        let file: PathBuf = [&path, "A", "B2", "file_new.txt"].iter().collect();
+        fs::write(file, b"new file")?;
+        mng.update_node(&node_h)?;
+
+        assert_eq!(mng.search_prefix("file_")?.len(), DEFAULT_FILE_CNT as usize - 1);
+        assert_eq!(mng.search_prefix("file_0")?.len(), 1);
+        assert!(mng.search_suffix("c")?.is_empty());
+        assert_eq!(mng.search_fuzzy("file_nwe.txt", 2)?.len(), 1);
+        Ok(())
+    }
+
+    /// case 3: load之后
+    /// A下面放两个大文件夹，dump成jidx，新mng load，没展开的块里的也能查到
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(83);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        for d in ["D1", "D2"] {
+            let dir: PathBuf = [&path, "A", d].iter().collect();
+            fs::create_dir_all(&dir)?;
+            for i in 0..5000 {
+                fs::write(dir.join(format!("file_{i}.log")), b"x")?;
+            }
+        }
+        let mut mng = JManager::new();
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        mng.dump(&dump_path)?;
+
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        assert!(mng.get_node_cnt() < 5000);
+        let found = mng.search_prefix("file_4999.")?;
+        assert_eq!(found.len(), 2);
+        assert!(mng.get_info(&found[0].0)?.path().ends_with("D1/file_4999.log"));
+        assert_eq!(mng.search_suffix(".log")?.len(), 10000);
+        Ok(())
+    }
+
+    /// case 4: 不是UTF-8的文件名（unix）
+    #[cfg(unix)]
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_4() -> Result<(), Box<dyn Error>> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use Jobs::LoadMode;
+
+        let path = init_test_dir(84);
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        let bad: PathBuf = [&path, "A", "B"].iter().collect::<PathBuf>().join(OsStr::from_bytes(b"bad\xffname.txt"));
+        fs::write(&bad, b"12345")?;
+        let mut mng = JManager::new();
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 1));
+
+        let found = mng.search_prefix("bad\u{fffd}")?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(mng.get_info(&found[0].0)?.path(), &bad);
+        mng.dump(&dump_path)?;
+
+        let mut mng = JManager::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load(&dump_path)?;
+        let h = mng.find_node(&bad)?.unwrap();
+        assert_eq!(mng.get_info(&h)?.size(), 5);
+        assert_eq!(mng.search_suffix("name.txt")?.len(), 1);
+        Ok(())
+    }
+}
+
+/// 9 ***watch***
+/// case 1: 增加、修改文件
+/// watch A，增加B/file_new.txt，追加写file_a.txt，不扫描直接看A
+///
+/// case 2: 删除、重命名
+/// watch A，删除C，B2改名成B3，不扫描直接看A；unwatch之后的改动不再生效
+#[cfg(feature = "watch")]
+mod watch {
+    use super::*;
+
+    /// 事件是异步到的，轮询到满足条件为止
+    fn wait_until(mng: &mut JManager, mut cond: impl FnMut(&mut JManager) -> bool) -> bool {
+        for _ in 0..40 {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            mng.apply_events().unwrap();
+            if cond(mng) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// case 1: 增加、修改文件
+    /// watch A，增加B/file_new.txt，追加写file_a.txt，不扫描直接看A
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(91);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.watch(&node_h)?;
+        assert_eq!(mng.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT);
+        assert_eq!(mng.watched(), vec![A.canonicalize()?]);
+
+        let file: PathBuf = [&path, "A", "B", "file_new.txt"].iter().collect();
+        fs::write(&file, b"new file")?;
+        let file_a: PathBuf = [&path, "A", "file_a.txt"].iter().collect();
+        fs::OpenOptions::new().append(true).open(&file_a)?.write_all(b"+1")?;
+
+        let expected = 19 * DEFAULT_FILE_CNT + 8 + 2;
+        assert!(wait_until(&mut mng, |m| m.get_info(&node_h).unwrap().size() == expected));
+        let info = mng.get_info(&node_h)?;
+        assert_eq!(info.count_file(), Some(DEFAULT_FILE_CNT + 1));
+        assert_eq!(info.count_dir(), Some(DEFAULT_DIR_CNT));
+        assert!(format!("{info}").find("[dirty]").is_none());
+        assert_eq!(mng.search_prefix("file_new")?.len(), 1);
+        Ok(())
+    }
+
+    /// case 2: 删除、重命名
+    /// watch A，删除C，B2改名成B3，不扫描直接看A；unwatch之后的改动不再生效
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(92);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.watch(&node_h)?;
+
+        let C: PathBuf = [&path, "A", "B", "C"].iter().collect();
+        fs::remove_dir_all(C)?;
+        let B2: PathBuf = [&path, "A", "B2"].iter().collect();
+        let B3: PathBuf = [&path, "A", "B3"].iter().collect();
+        fs::rename(&B2, &B3)?;
+
+        assert!(wait_until(&mut mng, |m| {
+            m.get_info(&node_h).unwrap().count_file() == Some(DEFAULT_FILE_CNT - 2)
+                && !m.search_prefix("B3").unwrap().is_empty()
+        }));
+        let info = mng.get_info(&node_h)?;
+        assert_eq!(info.size(), 19 * (DEFAULT_FILE_CNT - 2));
+        assert_eq!(info.count_dir(), Some(DEFAULT_DIR_CNT - 1));
+        assert!(mng.search_prefix("B2")?.is_empty());
+        let B3_h = mng.locate_node(&B3)?;
+        assert_eq!(mng.get_info(&B3_h)?.count_file(), Some(3));
+
+        mng.unwatch(&node_h)?;
+        assert!(mng.watched().is_empty());
+        let file: PathBuf = [&path, "A", "file_late.txt"].iter().collect();
+        fs::write(&file, b"late")?;
+        assert!(!wait_until(&mut mng, |m| !m.search_prefix("file_late").unwrap().is_empty()));
+        Ok(())
+    }
+}
+
+/// 10 ***JSON / NDJSON***
+/// case 1: 往返
+/// 扫描A，dump成.json和.ndjson，分别load进新的manager，nodes/chash/phash一模一样
+///
+/// case 2: 指定格式、文件内容
+/// dump_as写成NDJSON但扩展名是.txt，父节点在子节点前面；.json是嵌套的；坏行报错
+///
+/// case 3: 很深的树
+/// A下面一路套200层文件夹，最里面放一个文件，dump成.json，新manager load，和原来一样
+mod json_export {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use Jobs::{DumpData, LoadMode, StorageFormat};
+
+    fn tree_of(mng: &JManager) -> (Vec<(u64, String, u64)>, HashMap<u64, HashSet<u64>>) {
+        let mut nodes = mng
+            .nodes
+            .iter()
+            .map(|(h, v)| (h, format!("{:?}", Into::<DumpData>::into(v)), mng.nodes.parent(&h)))
+            .collect::<Vec<_>>();
+        nodes.sort();
+        let chash = mng
+            .nodes
+            .ids()
+            .map(|h| (h, mng.nodes.children(&h).into_iter().collect::<HashSet<_>>()))
+            .filter(|(_, chs)| !chs.is_empty())
+            .collect();
+        (nodes, chash)
+    }
+
+    /// case 1: 往返
+    /// 扫描A，dump成.json和.ndjson，分别load进新的manager，nodes/chash/phash一模一样
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(101);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let expected = tree_of(&mng);
+
+        for name in ["cache.json", "cache.ndjson"] {
+            let cache: PathBuf = [&path, name].iter().collect();
+            mng.dump(&cache)?;
+            for mode in [LoadMode::Verify, LoadMode::Trust] {
+                let mut mng2 = JManager::new();
+                mng2.set_load_mode(mode);
+                mng2.load(&cache)?;
+                assert_eq!(tree_of(&mng2), expected);
+                assert_eq!(mng2.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT);
+            }
+        }
+        Ok(())
+    }
+
+    /// case 2: 指定格式、文件内容
+    /// dump_as写成NDJSON但扩展名是.txt，父节点在子节点前面；.json是嵌套的；坏行报错
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(102);
+        let mut mng = JManager::new();
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let cache: PathBuf = [&path, "cache.txt"].iter().collect();
+        mng.dump_as(&cache, StorageFormat::NdJson)?;
+        let text = fs::read_to_string(&cache)?;
+        let mut seen = HashSet::from([0u64]);
+        for line in text.lines() {
+            let v: serde_json::Value = serde_json::from_str(line)?;
+            assert!(seen.contains(&v["parent"].as_u64().unwrap()));
+            seen.insert(v["id"].as_u64().unwrap());
+        }
+        assert_eq!(seen.len(), mng.nodes.len() + 1);
+
+        let mut mng2 = JManager::new();
+        mng2.load_as(&cache, StorageFormat::NdJson)?;
+        assert_eq!(mng2.nodes.len(), mng.nodes.len());
+
+        let cache: PathBuf = [&path, "cache.json"].iter().collect();
+        mng.dump(&cache)?;
+        let doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(&cache)?)?;
+        assert_eq!(doc["version"], 1);
+        // 根一路到A，A下面是B、B2、file_a.txt
+        let mut node = &doc["roots"][0];
+        while node["path"].as_str().unwrap() != A.canonicalize()?.to_str().unwrap() {
+            node = &node["children"][0];
+        }
+        let names = node["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["path"].as_str().unwrap().rsplit('/').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["B", "B2", "file_a.txt"]);
+        assert_eq!(node["count_file"], DEFAULT_FILE_CNT);
+
+        let bad: PathBuf = [&path, "bad.ndjson"].iter().collect();
+        fs::write(&bad, "{\"id\": 1\n")?;
+        assert!(JManager::new().load(&bad).is_err());
+        Ok(())
+    }
+
+    /// case 3: 很深的树
+    /// A下面一路套200层文件夹，最里面放一个文件，dump成.json，新manager load，和原来一样
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(103);
+        let mut deep: PathBuf = [&path, "A"].iter().collect();
+        for _ in 0..200 {
+            deep.push("d");
+        }
+        fs::create_dir_all(&deep)?;
+        fs::write(deep.join("file.txt"), b"deep")?;
+
+        let mut mng = JManager::new();
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let expected = tree_of(&mng);
+
+        let cache: PathBuf = [&path, "cache.json"].iter().collect();
+        mng.dump(&cache)?;
+        let mut mng2 = JManager::new();
+        mng2.set_load_mode(LoadMode::Trust);
+        mng2.load(&cache)?;
+        assert_eq!(tree_of(&mng2), expected);
+        assert_eq!(mng2.get_info(&node_h)?.count_dir(), Some(DEFAULT_DIR_CNT + 200));
+        Ok(())
+    }
+}
+
+/// 11 ***稳定的节点id***
+/// case 1: id只看路径
+/// 固定路径的id是定值；两个manager扫同一棵树，id一样，都等于stable_id(path)
+///
+/// case 2: 旧缓存迁移
+/// id是别的算法算的.json能load，load完id换成stable_id
+///
+/// case 3: 碰撞
+/// B的自然id被别的节点占了，B换一个id，按路径还能找到；B删掉后冲突记录也清掉
+mod stable_ids {
+    use std::path::Path;
+
+    use super::*;
+    use Jobs::stable_id;
+
+    /// case 1: id只看路径
+    /// 固定路径的id是定值；两个manager扫同一棵树，id一样，都等于stable_id(path)
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        assert_eq!(stable_id(Path::new("/data/photos/a.jpg")), 0xc7485f28564db1af);
+        assert_eq!(
+            stable_id(Path::new("/data/photos/a.jpg")),
+            stable_id(Path::new("\\data\\photos\\a.jpg"))
+        );
+
+        let path = init_test_dir(111);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let mut mng2 = JManager::with_threads(1);
+        let node_h2 = mng2.locate_node(&A)?;
+        mng2.update_node(&node_h2)?;
+
+        assert_eq!(node_h, node_h2);
+        assert_eq!(node_h, stable_id(&A.canonicalize()?));
+        for (h, node) in mng.nodes.iter() {
+            assert_eq!(h, stable_id(node.path()));
+            assert_eq!(mng2.nodes.path(&h).as_ref(), Some(node.path()));
+            assert_eq!(mng2.nodes.parent(&h), mng.nodes.parent(&h));
+        }
+        Ok(())
+    }
+
+    /// case 2: 旧缓存迁移
+    /// id是别的算法算的.json能load，load完id换成stable_id
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(112);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let cache: PathBuf = [&path, "cache.json"].iter().collect();
+        mng.dump(&cache)?;
+
+        // id全换掉
+        let mut doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(&cache)?)?;
+        let mut next = 1u64;
+        let mut stack = doc["roots"].as_array_mut().unwrap().iter_mut().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            node["id"] = next.into();
+            next += 1;
+            if let Some(children) = node.get_mut("children").and_then(|v| v.as_array_mut()) {
+                stack.extend(children.iter_mut());
+            }
+        }
+        let old: PathBuf = [&path, "old.json"].iter().collect();
+        fs::write(&old, doc.to_string())?;
+
+        let mut mng2 = JManager::new();
+        mng2.load(&old)?;
+        assert_eq!(mng2.nodes.len(), mng.nodes.len());
+        for h in mng.nodes.ids() {
+            assert_eq!(mng2.nodes.parent(&h), mng.nodes.parent(&h));
+        }
+        assert_eq!(mng2.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT));
+
+        mng2.dump(&old)?;
+        let doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(&old)?)?;
+        assert_eq!(doc["version"], 1);
+        Ok(())
+    }
+
+    /// case 3: 碰撞
+    /// B的自然id被别的节点占了，B换一个id，按路径还能找到；B删掉后冲突记录也清掉
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(113);
+        let B: PathBuf = [&path, "A", "B"].iter().collect();
+        let B = B.canonicalize()?;
+        let C = B.join("C");
+
+        let mut mng = JManager::new();
+        let c_h = mng.locate_node(&C)?;
+        let c_node = mng.get_info(&c_h)?;
+        // 假装C和B撞了：用B的自然id存一个C
+        let mut mng = JManager::new();
+        mng.nodes.insert(stable_id(&B), c_node, 0);
+
+        let b_h = mng.locate_node(&B)?;
+        assert_ne!(b_h, stable_id(&B));
+        assert_eq!(mng.id_of(&B), b_h);
+        assert_eq!(mng.locate_node(&B)?, b_h);
+        assert_eq!(mng.find_node(&B)?, Some(b_h));
+        mng.update_node(&b_h)?;
+        assert_eq!(mng.get_info(&b_h)?.path(), &B);
+        assert_eq!(mng.get_info(&b_h)?.count_file(), Some(3));
+        let c_h = mng.locate_node(&C)?;
+        assert_eq!(mng.get_parent(&c_h), b_h);
+
+        mng.delete_node(&b_h)?;
+        assert_eq!(mng.id_of(&B), stable_id(&B));
+        Ok(())
+    }
+}
+
+/// 12 ***节点表（slab）***
+/// case 1: 名字驻留
+/// 扫描A，节点表里同名的只存一份（两个file_0.txt），stats的节点数和表一致，路径拼得回来
+///
+/// case 2: 删除回收
+/// 删掉B2，它的slot进空闲表、只有它用的名字回收；再扫描A，slot复用，总数不变
+///
+/// case 3: 标脏
+/// 扫描A，set_dirty只对干净的文件夹返回true；删掉B2/C2/file_0.txt，C2、B2、A变脏，B还是干净的；重新扫描后都干净
+mod node_store {
+    use super::*;
+
+    /// case 1: 名字驻留
+    /// 扫描A，节点表里同名的只存一份（两个file_0.txt），stats的节点数和表一致，路径拼得回来
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(121);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let stats = mng.stats();
+        assert_eq!(stats.nodes, mng.get_node_cnt());
+        assert_eq!(stats.slots, stats.nodes);
+        assert_eq!(stats.free_slots, 0);
+        // 只有file_0.txt重名，扩展名txt也驻留在名字表里
+        assert_eq!(stats.names, stats.nodes);
+
+        let file_0: PathBuf = [&path, "A", "B2", "C2", "file_0.txt"].iter().collect();
+        let file_0 = file_0.canonicalize()?;
+        let h = mng.locate_node(&file_0)?;
+        assert_eq!(mng.nodes.path(&h), Some(file_0.clone()));
+        assert_eq!(mng.nodes.name(&h), file_0.file_name());
+        let c2_h = mng.get_parent(&h);
+        assert_eq!(mng.nodes.path(&c2_h).as_deref(), file_0.parent());
+        assert_eq!(mng.get_children_node(&c2_h).len(), 1);
+        Ok(())
+    }
+
+    /// case 2: 删除回收
+    /// 删掉B2，它的slot进空闲表、只有它用的名字回收；再扫描A，slot复用，总数不变
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(122);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let B2: PathBuf = [&path, "A", "B2"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let before = mng.stats();
+
+        // B2、C2、C2/file_0.txt、file_b21.txt、file_b22.txt
+        let b2_h = mng.locate_node(&B2)?;
+        mng.delete_node(&b2_h)?;
+        let stats = mng.stats();
+        assert_eq!(stats.nodes, before.nodes - 5);
+        assert_eq!(stats.free_slots, 5);
+        // file_0.txt还有B/C在用
+        assert_eq!(stats.names, before.names - 4);
+        assert!(mng.get_info(&node_h)?.count_dir().is_some());
+
+        mng.update_node(&node_h)?;
+        let stats = mng.stats();
+        // bytes按容量估，删过一次会多一点
+        assert_eq!((stats.nodes, stats.slots, stats.free_slots), (before.nodes, before.slots, 0));
+        assert_eq!((stats.names, stats.name_bytes), (before.names, before.name_bytes));
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT));
+        Ok(())
+    }
+
+    /// case 3: 标脏
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(123);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let dirty = |mng: &mut JManager, p: PathBuf| -> Result<bool, Box<dyn Error>> {
+            let h = mng.locate_node(&p)?;
+            Ok(matches!(mng.get_info(&h)?, Jobs::JNode::Dir(dir) if dir._dirty))
+        };
+
+        let C: PathBuf = [&path, "A", "B", "C"].iter().collect();
+        let c_h = mng.locate_node(&C)?;
+        let file_h = mng.locate_node(&C.join("file_0.txt"))?;
+        assert!(!mng.nodes.set_dirty(&file_h));
+        assert!(mng.nodes.set_dirty(&c_h));
+        assert!(!mng.nodes.set_dirty(&c_h));
+        mng.update_node(&node_h)?;
+        assert!(!dirty(&mut mng, C.clone())?);
+
+        let C2: PathBuf = [&path, "A", "B2", "C2"].iter().collect();
+        let file_h = mng.locate_node(&C2.join("file_0.txt"))?;
+        fs::remove_file(C2.join("file_0.txt"))?;
+        mng.delete_node(&file_h)?;
+        assert!(dirty(&mut mng, C2.clone())?);
+        assert!(dirty(&mut mng, C2.parent().unwrap().to_path_buf())?);
+        assert!(dirty(&mut mng, A.clone())?);
+        assert!(!dirty(&mut mng, C.parent().unwrap().to_path_buf())?);
+
+        mng.update_node(&node_h)?;
+        assert!(!dirty(&mut mng, A.clone())?);
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT - 1));
+        Ok(())
+    }
+}
+
+/// 13 ***硬链接***
+/// 在A下面加两个C/file_0.txt的硬链接：C/link.txt、B2/link2.txt
+///
+/// case 1: 按inode去重
+/// 默认只算一次：C 38，B2里第一次见到这个inode所以是76，A在共同祖先处去重还是133；
+/// 文件数每个链接都算，A有9个。换成EachLink后A是171
+///
+/// case 2: dump/load之后
+/// csv和jidx往返后大小不变；再切换模式重新扫描，去重表从load进来的文件节点重建
+mod hard_links {
+    use super::*;
+    use Jobs::LinkMode;
+
+    fn init_links(serial_number: i32) -> String {
+        let path = init_test_dir(serial_number);
+        let file_0: PathBuf = [&path, "A", "B", "C", "file_0.txt"].iter().collect();
+        let link: PathBuf = [&path, "A", "B", "C", "link.txt"].iter().collect();
+        let link2: PathBuf = [&path, "A", "B2", "link2.txt"].iter().collect();
+        fs::hard_link(&file_0, link).unwrap();
+        fs::hard_link(&file_0, link2).unwrap();
+        path
+    }
+
+    /// case 1: 按inode去重
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_links(131);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let C: PathBuf = [&path, "A", "B", "C"].iter().collect();
+        let B2: PathBuf = [&path, "A", "B2"].iter().collect();
+        let mut mng = JManager::new();
+        assert_eq!(mng.link_mode(), LinkMode::Once);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let c_h = mng.locate_node(&C)?;
+        let b2_h = mng.locate_node(&B2)?;
+        assert_eq!(mng.get_info(&c_h)?.size(), 19 * 2);
+        assert_eq!(mng.get_info(&c_h)?.count_file(), Some(3));
+        assert_eq!(mng.get_info(&b2_h)?.size(), 19 * 4);
+        assert_eq!(mng.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT);
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 2));
+
+        mng.set_link_mode(LinkMode::EachLink);
+        mng.update_node(&node_h)?;
+        assert_eq!(mng.get_info(&c_h)?.size(), 19 * 3);
+        assert_eq!(mng.get_info(&node_h)?.size(), 19 * (DEFAULT_FILE_CNT + 2));
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 2));
+        Ok(())
+    }
+
+    /// case 2: dump/load之后
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_links(132);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        for name in ["dump.csv", "dump.jidx"] {
+            let dump_path: PathBuf = [&path, name].iter().collect();
+            mng.dump(&dump_path)?;
+            let mut mng2 = JManager::new();
+            mng2.load(&dump_path)?;
+            let node_h = mng2.locate_node(&A)?;
+            assert_eq!(mng2.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT);
+
+            mng2.set_link_mode(LinkMode::EachLink);
+            mng2.update_node(&node_h)?;
+            assert_eq!(mng2.get_info(&node_h)?.size(), 19 * (DEFAULT_FILE_CNT + 2));
+
+            mng2.set_link_mode(LinkMode::Once);
+            mng2.update_node(&node_h)?;
+            assert_eq!(mng2.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT);
+            assert_eq!(mng2.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 2));
+        }
+        Ok(())
+    }
+}
+
+/// 14 ***磁盘占用***
+/// case 1: 稀疏文件
+/// A下面加一个1M的稀疏文件，扫描A，size算上1M，disk是子节点disk的和、比size小；jidx往返后不变
+///
+/// case 2: 被过滤的文件夹
+/// B/node_modules里放两个文件，扫描A，node_modules的size只算文件，不算文件夹自己；disk是两个文件占的块
+#[cfg(unix)]
+mod disk_usage {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    /// case 1: 稀疏文件
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(141);
+        let sparse: PathBuf = [&path, "A", "sparse.bin"].iter().collect();
+        fs::File::create(&sparse)?.set_len(1 << 20)?;
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT + (1 << 20));
+        let disk = mng.get_children_node(&node_h).iter().map(|(v, _)| v.disk_size()).sum::<u64>();
+        assert_eq!(root.disk_size(), disk);
+        assert!(root.disk_size() < root.size());
+
+        let dump_path: PathBuf = [&path, "dump.jidx"].iter().collect();
+        mng.dump(&dump_path)?;
+        let mut mng = JManager::new();
+        mng.load(&dump_path)?;
+        let node_h = mng.locate_node(&A)?;
+        let loaded = mng.get_info(&node_h)?;
+        assert_eq!((loaded.size(), loaded.disk_size()), (root.size(), root.disk_size()));
+        Ok(())
+    }
+
+    /// case 2: 被过滤的文件夹
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(142);
+        let inside: PathBuf = [&path, "A", "B", "node_modules", "inside"].iter().collect();
+        fs::create_dir_all(&inside)?;
+        let file_1: PathBuf = [&path, "A", "B", "node_modules", "file.txt"].iter().collect();
+        let file_2: PathBuf = [&path, "A", "B", "node_modules", "inside", "file.txt"].iter().collect();
+        fs::write(&file_1, b"new file")?;
+        fs::write(&file_2, b"new file")?;
+
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let node_modules: PathBuf = [&path, "A", "B", "node_modules"].iter().collect();
+        let h = mng.locate_node(&node_modules)?;
+        let info = mng.get_info(&h)?;
+        assert_eq!(info.size(), 16);
+        assert_eq!(info.count_file(), Some(2));
+        assert_eq!(info.count_dir(), Some(1));
+        let disk = fs::metadata(&file_1)?.blocks() * 512 + fs::metadata(&file_2)?.blocks() * 512;
+        assert_eq!(info.disk_size(), disk);
+        Ok(())
+    }
+}
+
+/// 15 ***符号链接***
+/// 在A下面建：link_up -> A（绕圈）、B/link_c -> ../B2/C2、dangling -> 不存在的路径
+///
+/// case 1: 默认记成叶子
+/// 扫描A不跟进去，三个链接都是Symlink、带target，文件数算上它们；jidx/csv/json往返后还是Symlink
+///
+/// case 2: 跟进去，防绕圈
+/// 再加P/to_q -> ../Q、Q/to_p -> ../P互相指；Follow扫描A能结束，
+/// B/link_c跟成文件夹，link_up、P/to_q/to_p这种指回祖先的和悬空的记成叶子
+///
+/// case 3: 换策略
+/// Leaf扫描，换Follow重扫B/link_c变成文件夹，再换Skip重扫链接都没了
+#[cfg(unix)]
+mod symlinks {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use Jobs::{LoadMode, SymlinkPolicy};
+
+    /// 返回canonicalize过的A
+    #[allow(non_snake_case)]
+    fn init_symlinks(serial_number: i32) -> PathBuf {
+        let path = init_test_dir(serial_number);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize().unwrap();
+        symlink(&A, A.join("link_up")).unwrap();
+        symlink("../B2/C2", A.join("B").join("link_c")).unwrap();
+        symlink(A.join("no_such_file"), A.join("dangling")).unwrap();
+        A
+    }
+
+    fn link_size(path: &PathBuf) -> u64 {
+        fs::symlink_metadata(path).unwrap().len()
+    }
+
+    /// case 1: 默认记成叶子
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init_symlinks(151);
+        let mut mng = JManager::new();
+        assert_eq!(mng.symlink_policy(), SymlinkPolicy::Leaf);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let links = [A.join("link_up"), A.join("B").join("link_c"), A.join("dangling")];
+        for link in links.iter() {
+            let node = mng.nodes.get(&mng.id_of(link)).unwrap();
+            assert!(node.is_symlink());
+            assert_eq!(node.target(), Some(&fs::read_link(link)?));
+        }
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT + 3));
+        assert_eq!(root.count_dir(), Some(DEFAULT_DIR_CNT));
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT + links.iter().map(link_size).sum::<u64>());
+
+        for name in ["dump.jidx", "dump.csv", "dump.json"] {
+            let dump_path = A.parent().unwrap().join(name);
+            mng.dump(&dump_path)?;
+            let mut mng2 = JManager::new();
+            mng2.set_load_mode(LoadMode::Trust);
+            mng2.load(&dump_path)?;
+            let node_h = mng2.locate_node(&A)?;
+            assert_eq!(mng2.get_info(&node_h)?.size(), root.size());
+            // jidx的子树块碰到才展开
+            mng2.find_node(&A.join("B"))?;
+            let h = mng2.id_of(&A.join("B").join("link_c"));
+            assert_eq!(mng2.get_info(&h)?.target(), Some(&PathBuf::from("../B2/C2")));
+        }
+        Ok(())
+    }
+
+    /// case 2: 跟进去，防绕圈
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let A = init_symlinks(152);
+        fs::create_dir_all(A.join("P"))?;
+        fs::create_dir_all(A.join("Q"))?;
+        symlink("../Q", A.join("P").join("to_q"))?;
+        symlink("../P", A.join("Q").join("to_p"))?;
+        let mut mng = JManager::new();
+        mng.set_symlink_policy(SymlinkPolicy::Follow);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let link_c = mng.id_of(&A.join("B").join("link_c"));
+        let info = mng.get_info(&link_c)?;
+        assert!(info.is_dir());
+        assert_eq!(info.count_file(), Some(1));
+        assert_eq!(info.size(), 19);
+        assert!(mng.nodes.contains_key(&mng.id_of(&A.join("B").join("link_c").join("file_0.txt"))));
+
+        for leaf in [A.join("link_up"), A.join("dangling"), A.join("P").join("to_q").join("to_p")] {
+            assert!(mng.nodes.get(&mng.id_of(&leaf)).unwrap().is_symlink());
+        }
+        // P/to_q跟成了Q，Q/to_p同理
+        assert!(mng.nodes.is_dir(&mng.id_of(&A.join("P").join("to_q"))));
+        assert!(mng.nodes.is_dir(&mng.id_of(&A.join("Q").join("to_p"))));
+        Ok(())
+    }
+
+    /// case 3: 换策略
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let A = init_symlinks(153);
+        let link_c = A.join("B").join("link_c");
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        assert!(mng.nodes.is_symlink(&mng.id_of(&link_c)));
+
+        mng.set_symlink_policy(SymlinkPolicy::Follow);
+        mng.update_node(&node_h)?;
+        assert!(mng.nodes.is_dir(&mng.id_of(&link_c)));
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 1 + 2));
+
+        mng.set_symlink_policy(SymlinkPolicy::Skip);
+        mng.update_node(&node_h)?;
+        assert!(!mng.nodes.contains_key(&mng.id_of(&link_c)));
+        assert!(!mng.nodes.contains_key(&mng.id_of(&link_c.join("file_0.txt"))));
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT));
+        assert_eq!(root.count_dir(), Some(DEFAULT_DIR_CNT));
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT);
+        Ok(())
+    }
+}
+
+/// 16 ***单一文件系统***
+/// 在/dev/shm（tmpfs，和测试目录不在一个设备上）下面建一个带文件的文件夹，A/shm链接过去，按Follow扫描；
+/// /dev/shm不存在或者和测试目录同一个设备时跳过
+///
+/// case 1: 挂载点不进去
+/// one_file_system打开扫描A，A/shm记成空文件夹、没有子节点，mounts列出它；jidx/json往返后还能列出来
+///
+/// case 2: 开关
+/// 先关着扫描A/shm有一个文件，打开重扫变空，再关上重扫又回来；直接从A/shm开始扫不受影响
+#[cfg(unix)]
+mod one_file_system {
+    use super::*;
+    use std::os::unix::fs::{symlink, MetadataExt};
+    use Jobs::{LoadMode, SymlinkPolicy};
+
+    /// 返回canonicalize过的A；建不出跨设备的目录时返回None
+    #[allow(non_snake_case)]
+    fn init_mount(serial_number: i32) -> Option<PathBuf> {
+        let path = init_test_dir(serial_number);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize().unwrap();
+        let shm = PathBuf::from(format!("/dev/shm/jobs_test_{serial_number}"));
+        let _ = fs::remove_dir_all(&shm);
+        if fs::create_dir_all(&shm).is_err() {
+            return None;
+        }
+        if fs::metadata(&shm).unwrap().dev() == fs::metadata(&A).unwrap().dev() {
+            return None;
+        }
+        // 别的用户留下的同名目录写不进去
+        if fs::write(shm.join("file_0.txt"), "0123456789").is_err() {
+            return None;
+        }
+        symlink(&shm, A.join("shm")).unwrap();
+        Some(A)
+    }
+
+    /// case 1: 挂载点不进去
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let Some(A) = init_mount(161) else {
+            return Ok(());
+        };
+        let shm = A.join("shm");
+        let mut mng = JManager::new();
+        mng.set_symlink_policy(SymlinkPolicy::Follow);
+        mng.set_one_file_system(true);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let shm_h = mng.id_of(&shm);
+        let info = mng.get_info(&shm_h)?;
+        assert!(info.is_dir());
+        assert_eq!(info.size(), 0);
+        assert_eq!(info.count_file(), Some(0));
+        assert!(mng.nodes.children(&shm_h).is_empty());
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT));
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT);
+        assert_eq!(mng.nodes.dev(&node_h), fs::metadata(&A)?.dev());
+        assert_eq!(mng.mounts(&node_h)?, vec![shm_h]);
+        assert!(mng.mounts(&mng.id_of(&A.join("B")))?.is_empty());
+
+        for name in ["dump.jidx", "dump.json"] {
+            let dump_path = A.parent().unwrap().join(name);
+            mng.dump(&dump_path)?;
+            let mut mng2 = JManager::new();
+            mng2.set_load_mode(LoadMode::Trust);
+            mng2.load(&dump_path)?;
+            let node_h = mng2.locate_node(&A)?;
+            assert_eq!(mng2.mounts(&node_h)?, vec![shm_h]);
+        }
+        Ok(())
+    }
+
+    /// case 2: 开关
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let Some(A) = init_mount(162) else {
+            return Ok(());
+        };
+        let shm = A.join("shm");
+        let mut mng = JManager::new();
+        mng.set_symlink_policy(SymlinkPolicy::Follow);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let shm_h = mng.id_of(&shm);
+        assert_eq!(mng.get_info(&shm_h)?.count_file(), Some(1));
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT + 1));
+        // 关着的时候也记设备号
+        assert_eq!(mng.mounts(&node_h)?, vec![shm_h]);
+
+        mng.set_one_file_system(true);
+        mng.update_node(&node_h)?;
+        assert_eq!(mng.get_info(&shm_h)?.count_file(), Some(0));
+        assert!(!mng.nodes.contains_key(&mng.id_of(&shm.join("file_0.txt"))));
+        assert_eq!(mng.get_info(&node_h)?.count_file(), Some(DEFAULT_FILE_CNT));
+
+        // 起点自己是挂载点时照样扫
+        let mut mng2 = JManager::new();
+        mng2.set_symlink_policy(SymlinkPolicy::Follow);
+        mng2.set_one_file_system(true);
+        let h = mng2.locate_node(&shm)?;
+        mng2.update_node(&h)?;
+        assert_eq!(mng2.get_info(&h)?.count_file(), Some(1));
+
+        mng.set_one_file_system(false);
+        mng.update_node(&node_h)?;
+        assert_eq!(mng.get_info(&shm_h)?.count_file(), Some(1));
+        assert_eq!(mng.get_info(&node_h)?.size(), 19 * DEFAULT_FILE_CNT + 10);
+        Ok(())
+    }
+}
+
+/// 17 ***读不了的路径***
+/// A/locked（000，里面有一个文件）整个读不了；A/B/noexec（600，能列不能stat）里的hidden.txt stat不了；
+/// root不受权限限制，读得了时跳过
+///
+/// case 1: 扫描照样做完
+/// scan返回的报告里正好是这两个路径、都是NoAuthorization；两个都记成inaccessible的空节点，
+/// 总数不算它们的内容；jidx/json往返后还是inaccessible
+///
+/// case 2: 权限恢复
+/// 改回755后再scan，报告是空的，两个节点恢复正常、算上里面的文件
+#[cfg(unix)]
+mod inaccessible {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use Jobs::{JError, LoadMode};
+
+    /// drop时把权限改回去，不然删不掉测试目录
+    struct Restore(Vec<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            for path in self.0.iter() {
+                let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o755));
+            }
+        }
+    }
+
+    /// 返回canonicalize过的A和权限守卫；root读得了时返回None
+    #[allow(non_snake_case)]
+    fn init_locked(serial_number: i32) -> Option<(PathBuf, Restore)> {
+        let path = init_test_dir(serial_number);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize().unwrap();
+        let locked = A.join("locked");
+        let noexec = A.join("B").join("noexec");
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(&noexec).unwrap();
+        fs::write(locked.join("file_l.txt"), "hellow word78787878").unwrap();
+        fs::write(noexec.join("hidden.txt"), "hellow word78787878").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        fs::set_permissions(&noexec, fs::Permissions::from_mode(0o600)).unwrap();
+        let guard = Restore(vec![locked.clone(), noexec]);
+        if fs::read_dir(&locked).is_ok() {
+            return None;
+        }
+        Some((A, guard))
+    }
+
+    /// case 1: 扫描照样做完
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let Some((A, _guard)) = init_locked(171) else {
+            return Ok(());
+        };
+        let locked = A.join("locked");
+        let hidden = A.join("B").join("noexec").join("hidden.txt");
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        let report = mng.scan(&node_h)?;
+
+        let mut failed = report.errors.iter().map(|e| e.path().unwrap().to_path_buf()).collect::<Vec<_>>();
+        failed.sort();
+        assert_eq!(failed, vec![A.join("B").join("noexec").join("hidden.txt"), locked.clone()]);
+        assert!(report.errors.iter().all(|e| matches!(e, JError::NoAuthorization(_))));
+
+        let info = mng.get_info(&mng.id_of(&locked))?;
+        assert!(info.is_dir() && info.is_inaccessible());
+        assert_eq!(info.count_file(), Some(0));
+        let info = mng.get_info(&mng.id_of(&hidden))?;
+        assert!(!info.is_dir() && info.is_inaccessible());
+        assert_eq!(info.size(), 0);
+        let root = mng.get_info(&node_h)?;
+        assert!(!root.is_inaccessible());
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT + 1));
+        assert_eq!(root.count_dir(), Some(DEFAULT_DIR_CNT + 2));
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT);
+
+        for name in ["dump.jidx", "dump.json"] {
+            let dump_path = A.parent().unwrap().join(name);
+            mng.dump(&dump_path)?;
+            let mut mng2 = JManager::new();
+            mng2.set_load_mode(LoadMode::Trust);
+            mng2.load(&dump_path)?;
+            mng2.find_node(&A.join("B").join("noexec"))?;
+            assert!(mng2.get_info(&mng2.id_of(&locked))?.is_inaccessible());
+            assert!(mng2.get_info(&mng2.id_of(&hidden))?.is_inaccessible());
+        }
+        Ok(())
+    }
+
+    /// case 2: 权限恢复
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let Some((A, guard)) = init_locked(172) else {
+            return Ok(());
+        };
+        let locked = A.join("locked");
+        let hidden = A.join("B").join("noexec").join("hidden.txt");
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        assert_eq!(mng.scan(&node_h)?.len(), 2);
+
+        drop(guard);
+        assert!(mng.scan(&node_h)?.is_empty());
+        let info = mng.get_info(&mng.id_of(&locked))?;
+        assert!(!info.is_inaccessible());
+        assert_eq!(info.count_file(), Some(1));
+        let info = mng.get_info(&mng.id_of(&hidden))?;
+        assert!(!info.is_inaccessible());
+        assert_eq!(info.size(), 19);
+        let root = mng.get_info(&node_h)?;
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT + 2));
+        assert_eq!(root.size(), 19 * (DEFAULT_FILE_CNT + 2));
+        Ok(())
+    }
+}
+
+/// 18 ***进度和取消***
+///
+/// case 1: 进度
+/// 扫描A时观察者收到的进度单调不减，最后一次是5个文件夹、7个文件、19 * 7字节，和progress()一样
+///
+/// case 2: 取消
+/// 没在扫描时cancel不影响下一次扫描；观察者第一次回调就cancel，update_node返回Cancelled，
+/// A和没扫完的B、B2都是脏的，能dump；再扫一遍结果和没取消过一样
+mod progress {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use Jobs::{JError, JNode, Progress};
+
+    fn is_dirty(node: &JNode) -> bool {
+        match node {
+            JNode::Dir(dir) => dir._dirty,
+            _ => false,
+        }
+    }
+
+    /// case 1: 进度
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(181);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let seen: Arc<Mutex<Vec<Progress>>> = Arc::default();
+        let mut mng = JManager::new();
+        let sink = seen.clone();
+        mng.set_observer(move |p| sink.lock().unwrap().push(p.clone()));
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let seen = seen.lock().unwrap();
+        // update_dirty重读时也会回调，只换路径
+        assert!(seen.len() as u64 >= DEFAULT_DIR_CNT + 1);
+        assert!(seen.windows(2).all(|w| w[0].dirs <= w[1].dirs && w[0].files <= w[1].files && w[0].bytes <= w[1].bytes));
+        assert!(seen.iter().all(|p| p.current.starts_with(&A)));
+        let last = seen.last().unwrap();
+        assert_eq!((last.dirs, last.files, last.bytes), (DEFAULT_DIR_CNT + 1, DEFAULT_FILE_CNT, 19 * DEFAULT_FILE_CNT));
+        assert_eq!(mng.progress(), last);
+        Ok(())
+    }
+
+    /// case 2: 取消
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(182);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let mut mng = JManager::new();
+        let token = mng.cancel_token();
+        token.cancel();
+        assert!(!token.is_cancelled());
+
+        let node_h = mng.locate_node(&A)?;
+        let stopper = token.clone();
+        mng.set_observer(move |_| stopper.cancel());
+        let err = mng.update_node(&node_h).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JError>(), Some(JError::Cancelled)));
+        assert!(!token.is_active() && !token.is_cancelled());
+        assert_eq!(mng.progress().dirs, 1);
+        for dir in [A.clone(), A.join("B"), A.join("B2")] {
+            assert!(is_dirty(&mng.get_info(&mng.id_of(&dir))?));
+        }
+        mng.dump(&PathBuf::from(&path).join("dump.jidx"))?;
+
+        mng.clear_observer();
+        mng.update_node(&node_h)?;
+        let root = mng.get_info(&node_h)?;
+        assert!(!is_dirty(&root));
+        assert_eq!(root.count_file(), Some(DEFAULT_FILE_CNT));
+        assert_eq!(root.count_dir(), Some(DEFAULT_DIR_CNT));
+        assert_eq!(root.size(), 19 * DEFAULT_FILE_CNT);
+        Ok(())
+    }
+}
+
+/// 19 ***快照历史***
+///
+/// case 1: 增量和比较
+/// 打开set_keep_history，扫描A后dump三次：第一次是全量；A/B下加一个文件后第二次只记A和A/B；删掉B2后第三次记下B2、C2没了。
+/// compare(1, 2)是A和A/B各多了一个文件，compare(2, 3)里B2、C2的after是None；series(A)三个点，大小和当时一样
+///
+/// case 2: 开关和没展开的块
+/// 默认不写.history；jidx按Trust读进来不展开，再dump的快照和之前一样，增量是空的；
+/// .history不存在时是空的，读不了（是个文件夹）时报错
+mod history {
+    use super::*;
+    use Jobs::{History, LoadMode};
+
+    /// case 1: 增量和比较
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(191);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let index = A.parent().unwrap().join("dump.csv");
+        let mut mng = JManager::new();
+        mng.set_keep_history(true);
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        mng.dump(&index)?;
+
+        fs::write(A.join("B").join("new.txt"), "12345")?;
+        mng.update_node(&node_h)?;
+        mng.dump(&index)?;
+
+        fs::remove_dir_all(A.join("B2"))?;
+        mng.update_node(&node_h)?;
+        mng.dump(&index)?;
+
+        let history = History::open(&History::path_for(&index))?;
+        let list = history.list();
+        assert_eq!(list.iter().map(|s| (s.id, s.full)).collect::<Vec<_>>(), vec![(1, true), (2, false), (3, false)]);
+        let under_A = |id| history.state(id).unwrap().into_keys().filter(|p| p.starts_with(&A)).count() as u64;
+        assert_eq!(under_A(1), DEFAULT_DIR_CNT + 1);
+        assert_eq!(list[1].entries, 2);
+
+        let growth = history.compare(1, 2).unwrap();
+        assert_eq!(growth.iter().map(|g| g.path.clone()).collect::<Vec<_>>(), vec![A.clone(), A.join("B")]);
+        assert!(growth.iter().all(|g| g.size_delta() == 5 && g.files_delta() == 1));
+
+        let growth = history.compare(2, 3).unwrap();
+        for removed in [A.join("B2"), A.join("B2").join("C2")] {
+            let g = growth.iter().find(|g| g.path == removed).unwrap();
+            assert!(g.before.is_some() && g.after.is_none());
+        }
+        assert_eq!(under_A(3), DEFAULT_DIR_CNT - 1);
+        assert!(history.compare(1, 4).is_none());
+
+        let series = history.series(&A);
+        let sizes = series.iter().map(|(_, s)| s.unwrap().size).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![19 * DEFAULT_FILE_CNT, 19 * DEFAULT_FILE_CNT + 5, 19 * (DEFAULT_FILE_CNT - 3) + 5]);
+        assert!(history.series(&A.join("B2")).last().unwrap().1.is_none());
+        Ok(())
+    }
+
+    /// case 2: 开关和没展开的块
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(192);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let off = A.parent().unwrap().join("off.csv");
+        assert!(!mng.keep_history());
+        mng.dump(&off)?;
+        assert!(!History::path_for(&off).exists());
+        assert!(History::open(&History::path_for(&off))?.list().is_empty());
+        fs::create_dir(History::path_for(&off))?;
+        assert!(History::open(&History::path_for(&off)).is_err());
+
+        let index = A.parent().unwrap().join("dump.jidx");
+        mng.set_keep_history(true);
+        mng.dump(&index)?;
+        let mut mng2 = JManager::new();
+        mng2.set_keep_history(true);
+        mng2.set_load_mode(LoadMode::Trust);
+        mng2.load(&index)?;
+        mng2.dump(&index)?;
+
+        let history = History::open(&History::path_for(&index))?;
+        let latest = history.latest().unwrap();
+        assert_eq!((latest.id, latest.full, latest.entries), (2, false, 0));
+        assert_eq!(history.state(1), history.state(2));
+        Ok(())
+    }
+}
+
+/// 20 ***比较两棵树***
+///
+/// case 1: 两个manager
+/// 扫描A后改四处：file_b.txt加5字节(R)，B2下新建3字节的文件(A)，删掉B/C(D，连同两个文件)，file_a.txt只改时间(M)；
+/// 重新扫一遍和旧的比，每个节点的类型对，A、A/B、A/B/C汇总的大小和计数对
+///
+/// case 2: dump文件
+/// diff_since(旧dump)、diff_files(旧dump, 新dump)和两个manager直接比一样；JSON能读回来；dump不存在时报错
+mod diff {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use Jobs::{ChangeKind, Diff};
+
+    #[allow(non_snake_case)]
+    fn change_tree(A: &PathBuf) -> Result<(), Box<dyn Error>> {
+        fs::OpenOptions::new().append(true).open(A.join("B").join("file_b.txt"))?.write_all(b"12345")?;
+        fs::write(A.join("B2").join("new.txt"), "123")?;
+        fs::remove_dir_all(A.join("B").join("C"))?;
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::options().write(true).open(A.join("file_a.txt"))?.set_modified(old)?;
+        Ok(())
+    }
+
+    /// case 1: 两个manager
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(201);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let mut before = JManager::new();
+        let node_h = before.locate_node(&A)?;
+        before.update_node(&node_h)?;
+
+        change_tree(&A)?;
+        let mut after = JManager::new();
+        let node_h = after.locate_node(&A)?;
+        after.update_node(&node_h)?;
+
+        let diff = before.diff(&after)?;
+        let kind = |p: PathBuf| diff.changes.iter().find(|c| c.path() == p).map(|c| c.kind);
+        assert_eq!(kind(A.join("B").join("file_b.txt")), Some(ChangeKind::Resized));
+        assert_eq!(kind(A.join("B2").join("new.txt")), Some(ChangeKind::Added));
+        assert_eq!(kind(A.join("B").join("C")), Some(ChangeKind::Removed));
+        assert_eq!(kind(A.join("B").join("C").join("file_0.txt")), Some(ChangeKind::Removed));
+        assert_eq!(kind(A.join("file_a.txt")), Some(ChangeKind::Modified));
+        assert_eq!(kind(A.join("B2").join("file_b21.txt")), None);
+        assert_eq!(diff.count(ChangeKind::Removed), 3);
+
+        let a = diff.dir(&A).unwrap();
+        assert_eq!((a.size_delta, a.added, a.removed, a.resized, a.modified), (5 + 3 - 38, 1, 2, 1, 1));
+        assert_eq!(diff.dir(&A.join("B")).unwrap().size_delta, 5 - 38);
+        assert_eq!(diff.dir(&A.join("B").join("C")).unwrap().removed, 2);
+        assert_eq!(diff.dir(&A.join("B2")).unwrap().size_delta, 3);
+        // 大的在前
+        assert_eq!(diff.dirs[0].abspath, A.join("B").join("C").to_str().unwrap());
+
+        assert!(after.diff(&after)?.is_empty());
+        let within = diff.within(&A.join("B2"));
+        assert_eq!(within.changes.len(), 2);
+        assert_eq!(within.dirs.len(), 1);
+        Ok(())
+    }
+
+    /// case 2: dump文件
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let path = init_test_dir(202);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        let A = A.canonicalize()?;
+        let old = A.parent().unwrap().join("old.csv");
+        let new = A.parent().unwrap().join("new.jidx");
+        let mut before = JManager::new();
+        let node_h = before.locate_node(&A)?;
+        before.update_node(&node_h)?;
+        before.dump(&old)?;
+
+        change_tree(&A)?;
+        let mut after = JManager::new();
+        let node_h = after.locate_node(&A)?;
+        after.update_node(&node_h)?;
+        after.dump(&new)?;
+
+        let direct = before.diff(&after)?;
+        assert_eq!(after.diff_since(&old)?, direct);
+        assert_eq!(JManager::diff_files(&old, &new)?, direct);
+
+        let text = serde_json::to_string(&direct)?;
+        assert_eq!(serde_json::from_str::<Diff>(&text)?, direct);
+
+        assert!(after.diff_since(&A.join("nothing.csv")).is_err());
+        Ok(())
+    }
+}
+
+/// 21 ***最大的N个***
+///
+/// case 1: 排名跟着扫描变
+/// B下放1000字节的big.bin，B2/C2下放500字节的mid.bin，扫描A：
+/// 最大的文件是big、mid，最大的文件夹是B、B2，混在一起是B、big、B2；只看B2时是mid；不含子树的根自己。
+/// B2下再放2000字节的huge.bin重新扫，huge排第一，按文件数B2排第一；删掉B2重新扫，排名里没有B2下面的了
+///
+/// case 2: 没展开的块
+/// jidx按Trust读进来，top_n先展开再排，和扫描完的一样；不在表里的句柄报错
+///
+/// case 3: 大树里的小子树
+/// B下放300个大小各不相同的文件，C下放5个：从A和从C看的最大的N个，都和把文件挨个排出来的一样
+///
+/// case 4: 不走整个子树
+/// 同case 3再在C下放一个最大的文件：从A取3个只看排名里的3条，从C取1个只看1条；删掉C之后重新编号，从B取1个还对
+mod top {
+    use super::*;
+    use Jobs::{LoadMode, TopBy, TopKind};
+
+    #[allow(non_snake_case)]
+    fn init(serial: i32) -> Result<PathBuf, Box<dyn Error>> {
+        let path = init_test_dir(serial);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        fs::write(A.join("B").join("big.bin"), vec![0u8; 1000])?;
+        fs::write(A.join("B2").join("C2").join("mid.bin"), vec![0u8; 500])?;
+        Ok(A.canonicalize()?)
+    }
+
+    fn names(top: Vec<(u64, Jobs::JNode)>) -> Vec<String> {
+        top.into_iter().map(|(_, node)| node.name()).collect()
+    }
+
+    /// case 1: 排名跟着扫描变
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init(211)?;
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Files, TopBy::Size, 2)?), vec!["big.bin", "mid.bin"]);
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Dirs, TopBy::Size, 2)?), vec!["B", "B2"]);
+        assert_eq!(names(mng.top_n(&node_h, TopKind::All, TopBy::Size, 3)?), vec!["B", "big.bin", "B2"]);
+        assert_eq!(mng.top_n(&node_h, TopKind::Dirs, TopBy::Size, 10)?.len() as u64, DEFAULT_DIR_CNT);
+        let B2_h = mng.locate_node(&A.join("B2"))?;
+        assert_eq!(names(mng.top_n(&B2_h, TopKind::Files, TopBy::Size, 1)?), vec!["mid.bin"]);
+        assert_eq!(names(mng.top_n(&B2_h, TopKind::Dirs, TopBy::Size, 10)?), vec!["C2"]);
+        assert!(mng.top_n(&B2_h, TopKind::Files, TopBy::FileCount, 10)?.is_empty());
+
+        fs::write(A.join("B2").join("huge.bin"), vec![0u8; 2000])?;
+        mng.update_node(&node_h)?;
+        assert_eq!(names(mng.top_n(&node_h, TopKind::All, TopBy::Size, 2)?), vec!["B2", "huge.bin"]);
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Dirs, TopBy::FileCount, 1)?), vec!["B2"]);
+
+        fs::remove_dir_all(A.join("B2"))?;
+        mng.update_node(&node_h)?;
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Files, TopBy::Size, 1)?), vec!["big.bin"]);
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Dirs, TopBy::Size, 10)?), vec!["B", "C"]);
+        Ok(())
+    }
+
+    /// case 2: 没展开的块
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let A = init(212)?;
+        let index = A.parent().unwrap().join("dump.jidx");
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+        let expected = names(mng.top_n(&node_h, TopKind::All, TopBy::Size, 5)?);
+        mng.dump(&index)?;
+
+        let mut mng = JManager::new();
+        mng.set_load_mode(LoadMode::Trust);
+        mng.load(&index)?;
+        let node_h = mng.find_node(&A)?.unwrap();
+        assert_eq!(names(mng.top_n(&node_h, TopKind::All, TopBy::Size, 5)?), expected);
+        assert!(mng.top_n(&u64::MAX, TopKind::All, TopBy::Size, 5).is_err());
+        Ok(())
+    }
+
+    /// case 3: 大树里的小子树
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_3() -> Result<(), Box<dyn Error>> {
+        let A = init(213)?;
+        let C = A.join("B").join("C");
+        let mut sizes = vec![("big.bin".to_string(), 1000), ("mid.bin".to_string(), 500)];
+        for i in 0..300 {
+            let name = format!("f{i}.bin");
+            fs::write(A.join("B").join(&name), vec![0u8; 1001 + i * 3])?;
+            sizes.push((name, 1001 + i * 3));
+        }
+        let mut small = vec![];
+        for i in 0..5 {
+            let name = format!("c{i}.bin");
+            fs::write(C.join(&name), vec![0u8; 100 + i * 200])?;
+            small.push((name.clone(), 100 + i * 200));
+            sizes.push((name, 100 + i * 200));
+        }
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        let expect = |mut all: Vec<(String, usize)>, n: usize| -> Vec<String> {
+            all.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+            all.into_iter().take(n).map(|(name, _)| name).collect()
+        };
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Files, TopBy::Size, 20)?), expect(sizes.clone(), 20));
+        let C_h = mng.locate_node(&C)?;
+        assert_eq!(names(mng.top_n(&C_h, TopKind::Files, TopBy::Size, 3)?), expect(small.clone(), 3));
+        assert_eq!(names(mng.top_n(&C_h, TopKind::All, TopBy::Size, 5)?), expect(small, 5));
+        // 还有原来的file_0、file_1
+        assert_eq!(mng.top_n(&C_h, TopKind::All, TopBy::Size, 10)?.len(), 7);
+        assert!(mng.top_n(&C_h, TopKind::Dirs, TopBy::Size, 10)?.is_empty());
+        Ok(())
+    }
+
+    /// case 4: 不走整个子树
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_4() -> Result<(), Box<dyn Error>> {
+        let A = init(214)?;
+        let C = A.join("B").join("C");
+        for i in 0..300 {
+            fs::write(A.join("B").join(format!("f{i}.bin")), vec![0u8; 1001 + i * 3])?;
+        }
+        fs::write(C.join("largest.bin"), vec![0u8; 5000])?;
+        let mut mng = JManager::new();
+        let node_h = mng.locate_node(&A)?;
+        mng.update_node(&node_h)?;
+
+        assert_eq!(names(mng.top_n(&node_h, TopKind::Files, TopBy::Size, 3)?), vec!["largest.bin", "f299.bin", "f298.bin"]);
+        assert_eq!(mng.top_visits(), 3);
+        let C_h = mng.locate_node(&C)?;
+        assert_eq!(names(mng.top_n(&C_h, TopKind::Files, TopBy::Size, 1)?), vec!["largest.bin"]);
+        assert_eq!(mng.top_visits(), 1);
+
+        fs::remove_dir_all(&C)?;
+        mng.update_node(&node_h)?;
+        let B_h = mng.locate_node(&A.join("B"))?;
+        assert_eq!(names(mng.top_n(&B_h, TopKind::Files, TopBy::Size, 1)?), vec!["f299.bin"]);
+        assert_eq!(mng.top_visits(), 1);
+        Ok(())
+    }
+}
+
+/// 22 ***查询语言***
+///
+/// case 1: 条件、排序、limit、under
+/// B下放2000字节的big.bin，B2下放1500字节的video.MP4，A下放1200字节、200天前改的old.mkv，扫描A后：
+/// 按大小和扩展名挑、再加上修改时间；按文件数挑文件夹并按大小排；glob和not；没有条件只排序取前两个；
+/// under换根；and比or先结合
+///
+/// case 2: 写错的查询
+/// 缺值、值不对、文字字段用<、不认识的字段、括号没关、sort后面没有by都是InvalidQuery；under的路径不在表里时报错
+mod query {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use Jobs::{JError, Query};
+
+    #[allow(non_snake_case)]
+    fn init(serial: i32) -> Result<PathBuf, Box<dyn Error>> {
+        let path = init_test_dir(serial);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        fs::write(A.join("B").join("big.bin"), vec![0u8; 2000])?;
+        fs::write(A.join("B2").join("video.MP4"), vec![0u8; 1500])?;
+        fs::write(A.join("old.mkv"), vec![0u8; 1200])?;
+        let old = SystemTime::now() - Duration::from_secs(200 * 86400);
+        fs::File::options().write(true).open(A.join("old.mkv"))?.set_modified(old)?;
+        Ok(A.canonicalize()?)
+    }
+
+    fn names(mng: &mut JManager, h: &u64, src: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let q = Query::parse(src)?;
+        Ok(mng.query(h, &q)?.into_iter().map(|(_, node)| node.name()).collect())
+    }
+
+    /// case 1: 条件、排序、limit、under
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init(221)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+
+        assert_eq!(names(&mut mng, &h, "size > 1K and ext in (mp4, MKV)")?, vec!["video.MP4", "old.mkv"]);
+        assert_eq!(names(&mut mng, &h, "size > 1KiB and ext in (mp4, mkv) and modified < 90d")?, vec!["video.MP4"]);
+        assert_eq!(names(&mut mng, &h, "kind = dir and count_file >= 3 sort by size desc")?, vec!["B", "B2"]);
+        assert_eq!(names(&mut mng, &h, "name ~ 'FILE_*' and not (path ~ \"**/c/**\")")?.len(), 5);
+        assert_eq!(names(&mut mng, &h, "sort by size desc limit 2")?, vec!["B", "big.bin"]);
+        assert_eq!(
+            names(&mut mng, &h, "kind = file under B2 sort by name")?,
+            vec!["file_0.txt", "file_b21.txt", "file_b22.txt", "video.MP4"]
+        );
+        assert_eq!(names(&mut mng, &h, "ext = mp4 or ext = mkv and size > 1300")?, vec!["video.MP4"]);
+        assert_eq!(names(&mut mng, &h, "(ext = mp4 or ext = mkv) and size >= 1200 sort by modified")?, vec!["old.mkv", "video.MP4"]);
+        assert_eq!(names(&mut mng, &h, "kind not in (file, dir)")?, Vec::<String>::new());
+        Ok(())
+    }
+
+    /// case 2: 写错的查询
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        for src in ["size >", "size > abc", "name < x", "owner = me", "ext in (mp4", "sort size", "size = 1 limit x", "kind = pipe"] {
+            assert!(matches!(Query::parse(src), Err(JError::InvalidQuery(_, _))), "{src}");
+        }
+        let q: Query = "modified >= 1w limit 3".parse()?;
+        assert_eq!(q.limit, Some(3));
+
+        let A = init(222)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+        assert!(mng.query(&h, &Query::parse("under nothing")?).is_err());
+        Ok(())
+    }
+}
+
+/// 23 ***按扩展名汇总***
+///
+/// case 1: 汇总
+/// B下放clip.mp4、B/node_modules（被过滤）下放lib.so和inside/a.log，B2下放没有扩展名的README，A下放app.LOG，扫描A：
+/// A、B的每种扩展名的字节数和文件数，被过滤的文件夹也算进去，扩展名不分大小写；按分类汇总；文件没有汇总；
+/// 删掉clip.mp4再扫描A，A和B里都没有mp4了
+///
+/// case 2: 跟着索引一起存
+/// 扫描A后dump成csv、jidx、json、ndjson，新manager按Trust load，A、B、node_modules的汇总不变
+mod types {
+    use super::*;
+    use Jobs::{Category, LoadMode, TypeStat};
+
+    #[allow(non_snake_case)]
+    fn init(serial: i32) -> Result<PathBuf, Box<dyn Error>> {
+        let path = init_test_dir(serial);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        fs::create_dir_all(A.join("B").join("node_modules").join("inside"))?;
+        fs::write(A.join("B").join("clip.mp4"), vec![0u8; 1000])?;
+        fs::write(A.join("B").join("node_modules").join("lib.so"), vec![0u8; 300])?;
+        fs::write(A.join("B").join("node_modules").join("inside").join("a.log"), vec![0u8; 50])?;
+        fs::write(A.join("B2").join("README"), vec![0u8; 10])?;
+        fs::write(A.join("app.LOG"), vec![0u8; 40])?;
+        Ok(A.canonicalize()?)
+    }
+
+    fn stat(bytes: u64, files: u64) -> Option<TypeStat> {
+        Some(TypeStat { bytes, files })
+    }
+
+    /// case 1: 汇总
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init(231)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+
+        let types = mng.types(&h)?.unwrap();
+        assert_eq!(types.len(), 5);
+        assert_eq!(types.get("txt"), stat(19 * DEFAULT_FILE_CNT, DEFAULT_FILE_CNT));
+        assert_eq!(types.get(".MP4"), stat(1000, 1));
+        assert_eq!(types.get("so"), stat(300, 1));
+        assert_eq!(types.get("log"), stat(90, 2));
+        assert_eq!(types.get(""), stat(10, 1));
+        let files = types.iter().map(|(_, s)| s.files).sum::<u64>();
+        assert_eq!(files, mng.get_info(&h)?.count_file().unwrap() as u64);
+        let categories = types.by_category().into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+        assert_eq!(
+            categories,
+            vec![Category::Video, Category::Build, Category::Document, Category::Log, Category::Other]
+        );
+
+        let b_h = mng.locate_node(&A.join("B"))?;
+        let types = mng.types(&b_h)?.unwrap();
+        assert_eq!(types.get("txt"), stat(19 * 3, 3));
+        assert_eq!(types.get("log"), stat(50, 1));
+        assert_eq!(types.get(""), None);
+        let file_h = mng.locate_node(&A.join("file_a.txt"))?;
+        assert_eq!(mng.types(&file_h)?, None);
+
+        fs::remove_file(A.join("B").join("clip.mp4"))?;
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+        assert_eq!(mng.types(&h)?.unwrap().get("mp4"), None);
+        assert_eq!(mng.types(&b_h)?.unwrap().get("mp4"), None);
+        assert_eq!(mng.types(&h)?.unwrap().get("so"), stat(300, 1));
+        Ok(())
+    }
+
+    /// case 2: 跟着索引一起存
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let A = init(232)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+        let dirs = [A.clone(), A.join("B"), A.join("B").join("node_modules")];
+        let mut expected = vec![];
+        for dir in dirs.iter() {
+            let h = mng.locate_node(dir)?;
+            expected.push(mng.types(&h)?.unwrap());
+        }
+        assert!(!expected[2].is_empty());
+
+        for name in ["cache.csv", "cache.jidx", "cache.json", "cache.ndjson"] {
+            let cache = A.parent().unwrap().join(name);
+            mng.dump(&cache)?;
+            let mut mng2 = JManager::new();
+            mng2.set_load_mode(LoadMode::Trust);
+            mng2.load(&cache)?;
+            for (dir, types) in dirs.iter().zip(expected.iter()) {
+                let h = mng2.locate_node(dir)?;
+                assert_eq!(mng2.types(&h)?.as_ref(), Some(types), "{name} {}", dir.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 24 ***重复文件***
+///
+/// case 1: 分组
+/// B下放10000字节的x.bin，B2下放一样的y.bin，A下放只有中间一个字节不一样的z.bin，A下放x.bin的硬链接，扫描A：
+/// x和y一组（硬链接只算一个），z头尾两块和x一样但整个文件不一样，7个一样的txt一组；
+/// 只有三个bin读了整个文件；min_size挡掉txt；不在表里的节点报错
+///
+/// case 2: hash缓存
+/// 再找一次不读文件；改掉y.bin的开头（大小不变），再找只重读y，x和y不再是一组
+mod dupes {
+    use super::*;
+
+    fn content() -> Vec<u8> {
+        (0..10000).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[allow(non_snake_case)]
+    fn init(serial: i32) -> Result<PathBuf, Box<dyn Error>> {
+        let path = init_test_dir(serial);
+        let A: PathBuf = [&path, "A"].iter().collect();
+        fs::write(A.join("B").join("x.bin"), content())?;
+        fs::write(A.join("B2").join("y.bin"), content())?;
+        let mut z = content();
+        z[5000] ^= 0xff;
+        fs::write(A.join("z.bin"), z)?;
+        fs::hard_link(A.join("B").join("x.bin"), A.join("x_link.bin"))?;
+        Ok(A.canonicalize()?)
+    }
+
+    /// case 1: 分组
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init(241)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+
+        let report = mng.find_dupes(Some(&h), 1)?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.groups.len(), 2);
+        let bins = &report.groups[0];
+        assert_eq!(bins.size, 10000);
+        assert_eq!(bins.files.len(), 2);
+        assert!(bins.files.contains(&A.join("B2").join("y.bin")));
+        assert!(!bins.files.contains(&A.join("z.bin")));
+        assert_eq!(bins.digest_hex().len(), 64);
+        let txts = &report.groups[1];
+        assert_eq!(txts.files.len() as u64, DEFAULT_FILE_CNT);
+        assert!(txts.files.iter().all(|f| f.extension().unwrap() == "txt"));
+        assert_eq!(report.reclaimable(), 10000 + 19 * (DEFAULT_FILE_CNT - 1));
+        assert_eq!(report.redundant() as u64, DEFAULT_FILE_CNT);
+        assert_eq!(report.partial_reads as u64, 3 + DEFAULT_FILE_CNT);
+        assert_eq!(report.full_reads, 3);
+
+        assert_eq!(mng.find_dupes(Some(&h), 20)?.groups.len(), 1);
+        assert_eq!(mng.find_dupes(None, 1)?.groups.len(), 2);
+        assert!(mng.find_dupes(Some(&u64::MAX), 1).is_err());
+        Ok(())
+    }
+
+    /// case 2: hash缓存
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let A = init(242)?;
+        let mut mng = JManager::new();
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+        mng.find_dupes(Some(&h), 1)?;
+
+        let y = A.join("B2").join("y.bin");
+        let y_h = mng.locate_node(&y)?;
+        assert!(mng.get_info(&y_h)?.content_hash().unwrap().full.is_some());
+        let report = mng.find_dupes(Some(&h), 1)?;
+        assert_eq!((report.partial_reads, report.full_reads), (0, 0));
+        assert_eq!(report.groups.len(), 2);
+
+        wait_mtime_tick();
+        let mut changed = content();
+        changed[0] ^= 0xff;
+        fs::write(&y, changed)?;
+        let report = mng.find_dupes(Some(&h), 1)?;
+        assert_eq!((report.partial_reads, report.full_reads), (1, 0));
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].files.len() as u64, DEFAULT_FILE_CNT);
+        Ok(())
+    }
+}
+
+/// 25 ***内容摘要***
+///
+/// case 1: 校验
+/// 打开BLAKE3摘要后扫描A，每个文件都有摘要，verify全对；
+/// 把file_a.txt换成一样长的别的内容再把mtime改回去，verify报它坏了，退出前不改表；
+/// file_b.txt追加内容是modified，不算坏；重新扫描后file_b.txt重新算了摘要，file_a.txt的还是旧的；
+/// 关掉摘要时扫描新文件不算；不在表里的节点报错
+///
+/// case 2: 跟着索引一起存
+/// SHA-256摘要dump成csv、jidx、json、ndjson，新manager按Trust load，摘要不变，verify全对
+mod digest {
+    use super::*;
+    use std::time::SystemTime;
+    use Jobs::{DigestAlgo, LoadMode};
+
+    /// case 1: 校验
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_1() -> Result<(), Box<dyn Error>> {
+        let A = init_test_dir(251);
+        let A: PathBuf = [&A, "A"].iter().collect::<PathBuf>().canonicalize()?;
+        let mut mng = JManager::new();
+        mng.set_digest(Some(DigestAlgo::Blake3));
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+
+        let a = A.join("file_a.txt");
+        let a_h = mng.locate_node(&a)?;
+        let expected = *mng.get_info(&a_h)?.digest().unwrap();
+        assert_eq!(expected.algo, DigestAlgo::Blake3);
+        assert_eq!(expected, expected.to_string().parse()?);
+        let report = mng.verify_digests(&h)?;
+        assert!(report.is_ok());
+        assert_eq!(report.checked as u64, DEFAULT_FILE_CNT);
+        assert_eq!((report.modified.len(), report.unhashed), (0, 0));
+
+        let old: SystemTime = fs::metadata(&a)?.modified()?;
+        let mut content = fs::read(&a)?;
+        content[0] ^= 0xff;
+        fs::write(&a, content)?;
+        fs::File::options().write(true).open(&a)?.set_modified(old)?;
+        let b = A.join("B").join("file_b.txt");
+        fs::OpenOptions::new().append(true).open(&b)?.write_all(b"12345")?;
+        let report = mng.verify_digests(&h)?;
+        assert!(!report.is_ok());
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].path, a);
+        assert_eq!(report.corrupted[0].expected, expected);
+        assert_ne!(report.corrupted[0].actual, expected);
+        assert_eq!(report.modified, vec![b.clone()]);
+        assert_eq!(mng.get_info(&a_h)?.digest(), Some(&expected));
+
+        let b_h = mng.locate_node(&b)?;
+        let b_old = *mng.get_info(&b_h)?.digest().unwrap();
+        mng.update_node(&h)?;
+        assert_ne!(mng.get_info(&b_h)?.digest(), Some(&b_old));
+        assert_eq!(mng.get_info(&a_h)?.digest(), Some(&expected));
+        assert!(mng.verify_digests(&h)?.modified.is_empty());
+
+        mng.set_digest(None);
+        fs::write(A.join("new.txt"), "123")?;
+        mng.update_node(&h)?;
+        let new_h = mng.locate_node(&A.join("new.txt"))?;
+        assert!(mng.get_info(&new_h)?.digest().is_none());
+        assert_eq!(mng.verify_digests(&h)?.unhashed, 1);
+        assert!(mng.verify_digests(&u64::MAX).is_err());
+        Ok(())
+    }
+
+    /// case 2: 跟着索引一起存
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mng_2() -> Result<(), Box<dyn Error>> {
+        let A = init_test_dir(252);
+        let A: PathBuf = [&A, "A"].iter().collect::<PathBuf>().canonicalize()?;
+        let mut mng = JManager::new();
+        mng.set_digest(Some(DigestAlgo::Sha256));
+        let h = mng.locate_node(&A)?;
+        mng.update_node(&h)?;
+        let files = [A.join("file_a.txt"), A.join("B").join("C").join("file_0.txt")];
+        let mut expected = vec![];
+        for file in files.iter() {
+            let h = mng.locate_node(file)?;
+            let digest = *mng.get_info(&h)?.digest().unwrap();
+            assert_eq!(digest.algo, DigestAlgo::Sha256);
+            expected.push(digest);
+        }
+
+        for name in ["cache.csv", "cache.jidx", "cache.json", "cache.ndjson"] {
+            let cache = A.parent().unwrap().join(name);
+            mng.dump(&cache)?;
+            let mut mng2 = JManager::new();
+            mng2.set_load_mode(LoadMode::Trust);
+            mng2.load(&cache)?;
+            for (file, digest) in files.iter().zip(expected.iter()) {
+                let h = mng2.locate_node(file)?;
+                assert_eq!(mng2.get_info(&h)?.digest(), Some(digest), "{name} {}", file.display());
+            }
+            let h = mng2.locate_node(&A)?;
+            let report = mng2.verify_digests(&h)?;
+            assert!(report.is_ok(), "{name}");
+            assert_eq!(report.checked as u64, DEFAULT_FILE_CNT, "{name}");
+        }
+        Ok(())
+    }
+}
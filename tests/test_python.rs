@@ -0,0 +1,70 @@
+#![cfg(feature = "python")]
+
+use std::{fs, path::PathBuf};
+
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use Jobs::jobs;
+
+// cargo test --features python --test test_python -- --nocapture
+
+#[allow(non_snake_case)]
+#[test]
+fn test_python1() -> PyResult<()> {
+    let TEMP_DIR = env!("TEMP");
+    let TEMP_DIR = PathBuf::from(format!("{TEMP_DIR}/Jobs_test_python"));
+    if TEMP_DIR.exists() {
+        fs::remove_dir_all(&TEMP_DIR)?;
+    }
+    fs::create_dir_all(TEMP_DIR.join("A/B"))?;
+    fs::write(TEMP_DIR.join("A/file_a.txt"), b"hellow word78787878")?;
+    fs::write(TEMP_DIR.join("A/B/file_b.txt"), b"hellow word78787878")?;
+
+    pyo3::append_to_inittab!(jobs);
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let locals = PyDict::new(py);
+        locals.set_item("root", TEMP_DIR.join("A"))?;
+        locals.set_item("dump", TEMP_DIR.join("dump.csv"))?;
+        py.run(
+            c_str!(
+                r#"
+import jobs
+
+mng = jobs.Manager(threads=2)
+h = mng.locate_node(root)
+mng.update_node(h)
+info = mng.get_info(h)
+assert info.is_dir
+assert info.size == 38
+assert (info.count_dir, info.count_file) == (1, 2)
+assert sorted(c.name for c in mng.children(h)) == ["B", "file_a.txt"]
+
+records = mng.records(h)
+assert len(records) == 4
+assert {r["name"] for r in records} == {"A", "B", "file_a.txt", "file_b.txt"}
+
+mng.dump(dump)
+mng2 = jobs.open(dump, trust=True)
+assert mng2.get_info(mng2.locate_node(root)).size == 38
+
+try:
+    mng.get_info(12345)
+    raise AssertionError("should raise")
+except jobs.NotExistingNodeError as e:
+    assert isinstance(e, jobs.JobsError)
+
+for scan in (mng.update_node, mng.scan):
+    try:
+        scan(12345)
+        raise AssertionError("should raise")
+    except jobs.NotExistingNodeError:
+        pass
+"#
+            ),
+            None,
+            Some(&locals),
+        )
+    })
+}
@@ -0,0 +1,91 @@
+use std::{error::Error, fs, net::SocketAddr, path::PathBuf, thread};
+
+use Jobs::{DumpData, JManager, JNodeAction, ManagerAction, SyncClient, SyncRow, SyncServer};
+
+// cargo test --test test_sync -- --nocapture
+
+fn start_server() -> SocketAddr {
+    let server = SyncServer::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.serve());
+    addr
+}
+
+#[allow(non_snake_case)]
+fn init_test_dir(name: &str) -> PathBuf {
+    let TEMP_DIR = env!("TEMP");
+    let TEMP_DIR = PathBuf::from(format!("{TEMP_DIR}/Jobs_test_sync/{name}"));
+    if TEMP_DIR.exists() {
+        fs::remove_dir_all(&TEMP_DIR).unwrap();
+    }
+    fs::create_dir_all(TEMP_DIR.join("A/B")).unwrap();
+    fs::write(TEMP_DIR.join("A/file_a.txt"), b"hellow word78787878").unwrap();
+    fs::write(TEMP_DIR.join("A/B/file_b.txt"), b"hellow word78787878").unwrap();
+    TEMP_DIR
+}
+
+/// pc1推，pc2拉下来建一棵一样的树
+/// pc1删掉一个文件再推，只推变化的行
+#[test]
+fn test_sync1() -> Result<(), Box<dyn Error>> {
+    let addr = start_server();
+    let dir = init_test_dir("1");
+    let A = dir.join("A");
+
+    let mut mng = JManager::new();
+    let node_h = mng.locate_node(&A)?;
+    mng.update_node(&node_h)?;
+    let rows = mng.dump_rows()?;
+
+    let mut pc1 = SyncClient::new(addr, "pc1");
+    assert_eq!(pc1.push(&mng)?, rows.len());
+    // 没变化就不推
+    assert_eq!(pc1.push(&mng)?, 0);
+
+    let pc2 = SyncClient::new(addr, "pc2");
+    assert_eq!(pc2.hosts()?, vec!["pc1"]);
+    let pulled = pc2.pull("pc1")?;
+    assert_eq!(pulled, rows);
+
+    let mut remote = JManager::new();
+    remote.load_rows(pulled);
+    assert_eq!(remote.get_node_cnt(), mng.get_node_cnt());
+
+    fs::remove_file(A.join("B/file_b.txt"))?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    mng.update_node(&node_h)?;
+    // file_b删了，B和A的数据变了
+    assert_eq!(pc1.push(&mng)?, 3);
+    let pulled = pc2.pull("pc1")?;
+    assert_eq!(pulled.len(), rows.len() - 1);
+    let root = pulled.iter().find(|v| PathBuf::from(&v.abspath) == A.canonicalize().unwrap()).unwrap();
+    assert_eq!(root.size, mng.get_info(&node_h)?.size());
+    Ok(())
+}
+
+/// 同一路径留last_write_time大的
+#[test]
+fn test_sync2() -> Result<(), Box<dyn Error>> {
+    let addr = start_server();
+    let pc = SyncClient::new(addr, "pc3");
+    let row = |last_write_time, size| SyncRow {
+        data: DumpData {
+            abspath: "/data".to_string(),
+            last_write_time,
+            size,
+            count_dir: 0,
+            count_file: 0,
+            _dirty: false,
+            is_dir: Some(true),
+            ..Default::default()
+        },
+        deleted: false,
+    };
+    assert_eq!(pc.push_rows(vec![row(200, 2)])?, 1);
+    assert_eq!(pc.push_rows(vec![row(100, 1)])?, 0);
+    assert_eq!(pc.pull("pc3")?[0].size, 2);
+    assert_eq!(pc.push_rows(vec![row(300, 3)])?, 1);
+    assert_eq!(pc.pull("pc3")?[0].size, 3);
+    assert!(pc.pull("nobody")?.is_empty());
+    Ok(())
+}